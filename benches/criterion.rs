@@ -1,7 +1,11 @@
+use arrow2::io::ipc::write::Compression;
 use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
 use peppi::{
 	self,
-	io::slippi::de::{read, Opts},
+	io::{
+		peppi as io_peppi,
+		slippi::de::{read, read_slice, Opts},
+	},
 };
 
 use std::{fs, io::Cursor, path::PathBuf, time::Duration};
@@ -54,6 +58,93 @@ pub fn skip_frames(c: &mut Criterion) {
 	}
 }
 
+pub fn read_slice_into_game(c: &mut Criterion) {
+	let dir = PathBuf::from("benches/data");
+	for replay in fs::read_dir(dir).unwrap() {
+		let path = replay.unwrap().path();
+		let name = path.file_name().unwrap().to_str().unwrap().to_string();
+		let contents = fs::read(path).unwrap();
+		c.bench_with_input(
+			BenchmarkId::new("read_slice_into_game", &name),
+			&contents,
+			|b, contents| {
+				b.iter_batched(
+					|| contents.as_slice(),
+					|buf| read_slice(buf, None),
+					BatchSize::LargeInput,
+				)
+			},
+		);
+	}
+}
+
+/// `.slpp` write options to compare, for size & speed.
+fn peppi_opts() -> Vec<(&'static str, io_peppi::ser::Opts)> {
+	let encodings = io_peppi::ser::Opts {
+		dictionary: vec![
+			"ports.*.*.post.state".to_string(),
+			"ports.*.*.post.character".to_string(),
+		],
+		delta: vec!["id".to_string(), "start.scene_frame_counter".to_string()],
+		..Default::default()
+	};
+	vec![
+		("uncompressed", Default::default()),
+		(
+			"lz4",
+			io_peppi::ser::Opts {
+				compression: Some(Compression::LZ4),
+				..Default::default()
+			},
+		),
+		(
+			"zstd",
+			io_peppi::ser::Opts {
+				compression: Some(Compression::ZSTD),
+				..Default::default()
+			},
+		),
+		(
+			"zstd_encoded",
+			io_peppi::ser::Opts {
+				compression: Some(Compression::ZSTD),
+				..encodings
+			},
+		),
+	]
+}
+
+pub fn peppi_encodings(c: &mut Criterion) {
+	let dir = PathBuf::from("benches/data");
+	for replay in fs::read_dir(dir).unwrap() {
+		let path = replay.unwrap().path();
+		let name = path.file_name().unwrap().to_str().unwrap().to_string();
+		let contents = fs::read(path).unwrap();
+		for (label, opts) in peppi_opts() {
+			let mut buf = Vec::new();
+			io_peppi::write(&mut buf, read_slice(&contents, None).unwrap(), Some(&opts)).unwrap();
+			// Criterion only measures time, so report sizes ourselves
+			println!("{} ({}): {} bytes", name, label, buf.len());
+			c.bench_with_input(
+				BenchmarkId::new(format!("peppi_write_{}", label), &name),
+				&contents,
+				|b, contents| {
+					b.iter_batched(
+						|| read_slice(contents, None).unwrap(),
+						|game| io_peppi::write(&mut Vec::new(), game, Some(&opts)),
+						BatchSize::LargeInput,
+					)
+				},
+			);
+			c.bench_with_input(
+				BenchmarkId::new(format!("peppi_read_{}", label), &name),
+				&buf,
+				|b, buf| b.iter(|| io_peppi::read(buf.as_slice(), None)),
+			);
+		}
+	}
+}
+
 criterion_group! {
 	name = bench_into_game;
 	config = Criterion::default()
@@ -68,4 +159,23 @@ criterion_group! {
 	targets = skip_frames
 }
 
-criterion_main!(bench_into_game, bench_skip_frames);
+criterion_group! {
+	name = bench_read_slice;
+	config = Criterion::default()
+		.warm_up_time(Duration::from_secs(1));
+	targets = read_slice_into_game
+}
+
+criterion_group! {
+	name = bench_peppi_encodings;
+	config = Criterion::default()
+		.warm_up_time(Duration::from_secs(1));
+	targets = peppi_encodings
+}
+
+criterion_main!(
+	bench_into_game,
+	bench_skip_frames,
+	bench_read_slice,
+	bench_peppi_encodings
+);
@@ -5,6 +5,7 @@ use std::{
 	mem::size_of,
 };
 
+use arrow2::array::FixedSizeBinaryArray;
 use byteorder::WriteBytesExt;
 
 use crate::{
@@ -17,6 +18,14 @@ use crate::{
 
 type BE = byteorder::BigEndian;
 
+/// Writes the `i`th element of `trailing` (if any), for round-tripping newer replays.
+fn write_trailing<W: Write>(w: &mut W, trailing: Option<&FixedSizeBinaryArray>, i: usize) -> Result<()> {
+	match trailing {
+		Some(t) => w.write_all(t.value(i)),
+		None => Ok(()),
+	}
+}
+
 impl Data {
 	fn write_pre<W: Write>(
 		&self,
@@ -35,6 +44,7 @@ impl Data {
 				_ => 0,
 			})?;
 			self.pre.write(w, version, idx)?;
+			write_trailing(w, self.pre_trailing.as_ref(), idx)?;
 		}
 		Ok(())
 	}
@@ -56,6 +66,7 @@ impl Data {
 				_ => 0,
 			})?;
 			self.post.write(w, version, idx)?;
+			write_trailing(w, self.post_trailing.as_ref(), idx)?;
 		}
 		Ok(())
 	}
@@ -144,6 +155,7 @@ impl Frame {
 				w.write_u8(Event::FrameStart as u8)?;
 				w.write_i32::<BE>(frame_id)?;
 				self.start.as_ref().unwrap().write(w, version, idx)?;
+				write_trailing(w, self.start_trailing.as_ref(), idx)?;
 			}
 			for port in &self.ports {
 				port.write_pre(w, version, idx, frame_id)?;
@@ -154,6 +166,7 @@ impl Frame {
 					w.write_u8(Event::Item as u8)?;
 					w.write_i32::<BE>(frame_id)?;
 					self.item.as_ref().unwrap().write(w, version, item_idx)?;
+					write_trailing(w, self.item_trailing.as_ref(), item_idx)?;
 				}
 			}
 			for port in &self.ports {
@@ -163,6 +176,7 @@ impl Frame {
 				w.write_u8(Event::FrameEnd as u8)?;
 				w.write_i32::<BE>(frame_id)?;
 				self.end.as_ref().unwrap().write(w, version, idx)?;
+				write_trailing(w, self.end_trailing.as_ref(), idx)?;
 			}
 		}
 		Ok(())
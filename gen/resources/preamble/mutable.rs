@@ -6,13 +6,13 @@
 #![allow(dead_code)]
 
 use arrow2::{
-	array::{MutableArray, MutablePrimitiveArray},
+	array::{MutableArray, MutableFixedSizeBinaryArray, MutablePrimitiveArray},
 	bitmap::MutableBitmap,
 	offset::Offsets,
 };
 
 use byteorder::ReadBytesExt;
-use std::io::Result;
+use std::io::{Error, ErrorKind, Result};
 
 use crate::{
 	io::slippi::Version,
@@ -27,6 +27,10 @@ pub struct Data {
 	pub pre: Pre,
 	pub post: Post,
 	pub validity: Option<MutableBitmap>,
+	/// Unrecognized bytes at the end of each Frame Pre event (from newer Slippi versions)
+	pub pre_trailing: Option<MutableFixedSizeBinaryArray>,
+	/// Unrecognized bytes at the end of each Frame Post event (from newer Slippi versions)
+	pub post_trailing: Option<MutableFixedSizeBinaryArray>,
 }
 
 impl Data {
@@ -35,6 +39,8 @@ impl Data {
 			pre: Pre::with_capacity(capacity, version),
 			post: Post::with_capacity(capacity, version),
 			validity: None,
+			pre_trailing: None,
+			post_trailing: None,
 		}
 	}
 
@@ -49,6 +55,27 @@ impl Data {
 			.push(false);
 		self.pre.push_null(version);
 		self.post.push_null(version);
+		if let Some(t) = &mut self.pre_trailing {
+			t.push_null();
+		}
+		if let Some(t) = &mut self.post_trailing {
+			t.push_null();
+		}
+	}
+
+	/// Appends a frame's data for this character (see [`transpose_one`](Self::transpose_one)).
+	pub fn push_transposed(&mut self, x: transpose::Data, version: Version) {
+		if let Some(v) = &mut self.validity {
+			v.push(true);
+		}
+		self.pre.push_transposed(x.pre, version);
+		self.post.push_transposed(x.post, version);
+		if let Some(t) = &mut self.pre_trailing {
+			t.push_null();
+		}
+		if let Some(t) = &mut self.post_trailing {
+			t.push_null();
+		}
 	}
 
 	pub fn transpose_one(&self, i: usize, version: Version) -> transpose::Data {
@@ -83,6 +110,18 @@ impl PortData {
 		self.leader.len()
 	}
 
+	/// Appends a frame's data for this port. Pushes a null follower if `x`
+	/// has none (e.g. when Nana is dead).
+	pub fn push_transposed(&mut self, x: transpose::PortData, version: Version) {
+		self.leader.push_transposed(x.leader, version);
+		if let Some(f) = &mut self.follower {
+			match x.follower {
+				Some(x) => f.push_transposed(x, version),
+				None => f.push_null(version),
+			}
+		}
+	}
+
 	pub fn transpose_one(&self, i: usize, version: Version) -> transpose::PortData {
 		transpose::PortData {
 			port: self.port,
@@ -106,6 +145,12 @@ pub struct Frame {
 	pub item_offset: Option<Offsets<i32>>,
 	/// Item data
 	pub item: Option<Item>,
+	/// Unrecognized bytes at the end of each Frame Start event (from newer Slippi versions)
+	pub start_trailing: Option<MutableFixedSizeBinaryArray>,
+	/// Unrecognized bytes at the end of each Frame End event (from newer Slippi versions)
+	pub end_trailing: Option<MutableFixedSizeBinaryArray>,
+	/// Unrecognized bytes at the end of each Item event (from newer Slippi versions)
+	pub item_trailing: Option<MutableFixedSizeBinaryArray>,
 }
 
 impl Frame {
@@ -120,6 +165,9 @@ impl Frame {
 			end: version.gte(3, 0).then(|| End::with_capacity(capacity, version)),
 			item_offset: version.gte(3, 0).then(|| Offsets::<i32>::with_capacity(capacity)),
 			item: version.gte(3, 0).then(|| Item::with_capacity(0, version)),
+			start_trailing: None,
+			end_trailing: None,
+			item_trailing: None,
 		}
 	}
 
@@ -127,6 +175,48 @@ impl Frame {
 		self.id.len()
 	}
 
+	/// Appends a single frame (the inverse of [`transpose_one`](Self::transpose_one)).
+	/// Fields that `x` is missing but `version` requires are filled with
+	/// defaults (or nulls). Fails if `x` has different ports than `self`.
+	pub fn push_transposed(&mut self, x: transpose::Frame, version: Version) -> Result<()> {
+		let ports: Vec<_> = x.ports.iter().map(|p| p.port).collect();
+		if ports != self.ports.iter().map(|p| p.port).collect::<Vec<_>>() {
+			return Err(Error::new(
+				ErrorKind::InvalidData,
+				format!("frame {}: wrong ports: {:?}", x.id, ports),
+			));
+		}
+		let items = x.items.unwrap_or_default();
+		if let Some(item_offset) = &mut self.item_offset {
+			item_offset
+				.try_push(items.len() as i32)
+				.map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+		}
+
+		self.id.push(Some(x.id));
+		for (p, x) in self.ports.iter_mut().zip(x.ports) {
+			p.push_transposed(x, version);
+		}
+		if let Some(start) = &mut self.start {
+			start.push_transposed(x.start.unwrap_or_default(), version);
+		}
+		if let Some(end) = &mut self.end {
+			end.push_transposed(x.end.unwrap_or_default(), version);
+		}
+		if let Some(item) = &mut self.item {
+			for i in items {
+				item.push_transposed(i, version);
+				if let Some(t) = &mut self.item_trailing {
+					t.push_null();
+				}
+			}
+		}
+		for t in [&mut self.start_trailing, &mut self.end_trailing].into_iter().flatten() {
+			t.push_null();
+		}
+		Ok(())
+	}
+
 	pub fn transpose_one(&self, i: usize, version: Version) -> transpose::Frame {
 		transpose::Frame {
 			id: self.id.values()[i],
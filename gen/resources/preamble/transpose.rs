@@ -2,22 +2,24 @@
 //!
 //! Transposing frame data is fairly slow. Work with Arrow arrays when possible.
 
+use serde::{Deserialize, Serialize};
+
 use crate::game::Port;
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
 pub struct Data {
 	pub pre: Pre,
 	pub post: Post,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
 pub struct PortData {
 	pub port: Port,
 	pub leader: Data,
 	pub follower: Option<Data>,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
 pub struct Frame {
 	pub id: i32,
 	pub ports: Vec<PortData>,
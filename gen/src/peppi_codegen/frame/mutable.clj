@@ -198,6 +198,41 @@
                                 [[:method-call "v" "push" ["true"]]]]]])
      true (append [:struct-init "Ok" [[nil [:unit]]]]))])
 
+(defn push-transposed-primitive
+  [target src ver]
+  [:method-call
+   target
+   "push"
+   [(if ver src [:struct-init "Some" [[nil src]]])]])
+
+(defn push-transposed-composite
+  [target src ver]
+  [:method-call
+   target
+   "push_transposed"
+   [(if ver [:method-call src "unwrap_or_default"] src)
+    "version"]])
+
+(defn push-transposed
+  [{nm :name, ty :type, ver :version, idx :index}]
+  (let [target (cond-> [:field-get "self" (or nm idx)]
+                 ver ((comp unwrap as-mut)))
+        src [:field-get "x" (or nm idx)]]
+    (if (primitive-types ty)
+      (push-transposed-primitive target src ver)
+      (push-transposed-composite target src ver))))
+
+(defn push-transposed-fn
+  [nm fields]
+  [:fn
+   {:visibility "pub"}
+   "push_transposed"
+   [["&mut self"]
+    ["x" (list "transpose" nm)]
+    ["version" "Version"]]
+   (cond->> (into [:block] (nested-version-ifs push-transposed fields))
+     (named? fields) (append [:raw "if let Some(v) = self.validity.as_mut() { v.push(true) }"]))])
+
 (defn struct-field
   [{nm :name, ty :type, ver :version, desc :description}]
   [:struct-field
@@ -240,6 +275,7 @@
              (len-fn fields)
              (push-null-fn fields)
              (read-push-fn fields)
+             (push-transposed-fn nm fields)
              (immutable/transpose-one-fn nm fields)]])
 
 (defn -main []
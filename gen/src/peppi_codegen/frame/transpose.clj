@@ -24,7 +24,7 @@
 (defmethod struct-decl true
   [[nm {:keys [fields]}]]
   [:struct
-   {:attrs {:derive ["PartialEq" "Clone" "Copy" "Debug", "Default"]}}
+   {:attrs {:derive ["PartialEq" "Clone" "Copy" "Debug" "Default" "Serialize" "Deserialize"]}}
    nm
    (->> fields
         (filter :type)
@@ -33,7 +33,7 @@
 (defmethod struct-decl false
   [[nm {:keys [fields]}]]
   [:tuple-struct
-   {:attrs {:derive ["PartialEq" "Clone" "Copy" "Debug", "Default"]}}
+   {:attrs {:derive ["PartialEq" "Clone" "Copy" "Debug" "Default" "Serialize" "Deserialize"]}}
    nm
    (->> fields
         (filter :type)
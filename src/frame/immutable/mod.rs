@@ -15,7 +15,12 @@ mod slippi;
 
 use std::fmt;
 
-use arrow2::{array::PrimitiveArray, bitmap::Bitmap, buffer::Buffer, offset::OffsetsBuffer};
+use arrow2::{
+	array::{FixedSizeBinaryArray, PrimitiveArray},
+	bitmap::Bitmap,
+	buffer::Buffer,
+	offset::OffsetsBuffer,
+};
 
 use crate::{
 	frame::{self, mutable, transpose, Rollbacks},
@@ -29,6 +34,10 @@ pub struct Data {
 	pub pre: Pre,
 	pub post: Post,
 	pub validity: Option<Bitmap>,
+	/// Unrecognized bytes at the end of each Frame Pre event (from newer Slippi versions)
+	pub pre_trailing: Option<FixedSizeBinaryArray>,
+	/// Unrecognized bytes at the end of each Frame Post event (from newer Slippi versions)
+	pub post_trailing: Option<FixedSizeBinaryArray>,
 }
 
 impl Data {
@@ -46,6 +55,8 @@ impl From<mutable::Data> for Data {
 			pre: d.pre.into(),
 			post: d.post.into(),
 			validity: d.validity.map(|v| v.into()),
+			pre_trailing: d.pre_trailing.map(|t| t.into()),
+			post_trailing: d.post_trailing.map(|t| t.into()),
 		}
 	}
 }
@@ -93,6 +104,12 @@ pub struct Frame {
 	pub item_offset: Option<OffsetsBuffer<i32>>,
 	/// Item data
 	pub item: Option<Item>,
+	/// Unrecognized bytes at the end of each Frame Start event (from newer Slippi versions)
+	pub start_trailing: Option<FixedSizeBinaryArray>,
+	/// Unrecognized bytes at the end of each Frame End event (from newer Slippi versions)
+	pub end_trailing: Option<FixedSizeBinaryArray>,
+	/// Unrecognized bytes at the end of each Item event (from newer Slippi versions)
+	pub item_trailing: Option<FixedSizeBinaryArray>,
 }
 
 impl Frame {
@@ -164,6 +181,9 @@ impl From<mutable::Frame> for Frame {
 				.item_offset
 				.map(|x| OffsetsBuffer::try_from(Buffer::from(x.into_inner())).unwrap()),
 			item: f.item.map(|x| x.into()),
+			start_trailing: f.start_trailing.map(|x| x.into()),
+			end_trailing: f.end_trailing.map(|x| x.into()),
+			item_trailing: f.item_trailing.map(|x| x.into()),
 		}
 	}
 }
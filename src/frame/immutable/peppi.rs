@@ -3,7 +3,7 @@
 #![allow(unused_variables)]
 
 use arrow2::{
-	array::{ListArray, PrimitiveArray, StructArray},
+	array::{Array, FixedSizeBinaryArray, ListArray, PrimitiveArray, StructArray},
 	datatypes::{DataType, Field},
 };
 
@@ -16,69 +16,106 @@ use crate::{
 	io::slippi::Version,
 };
 
-impl Data {
-	fn data_type(version: Version) -> DataType {
-		DataType::Struct(vec![
-			Field::new("pre", Pre::data_type(version).clone(), false),
-			Field::new("post", Post::data_type(version).clone(), false),
-		])
+/// Unrecognized trailing bytes (from newer Slippi versions) are stored as
+/// an extra field at the end of their event's struct, named `trailing`.
+const TRAILING: &str = "trailing";
+
+/// Builds a struct data type whose fields match `values`, since these may
+/// include `trailing` fields that `data_type(version)` doesn't know about.
+fn struct_data_type(names: &[&str], values: &[Box<dyn Array>]) -> DataType {
+	DataType::Struct(
+		std::iter::zip(names, values)
+			.map(|(name, v)| Field::new(*name, v.data_type().clone(), false))
+			.collect(),
+	)
+}
+
+fn with_trailing(array: StructArray, trailing: Option<FixedSizeBinaryArray>) -> StructArray {
+	match trailing {
+		Some(trailing) => {
+			let (mut fields, mut values, validity) = array.into_data();
+			fields.push(Field::new(TRAILING, trailing.data_type().clone(), true));
+			values.push(trailing.boxed());
+			StructArray::new(DataType::Struct(fields), values, validity)
+		}
+		None => array,
 	}
+}
 
+fn split_trailing(array: StructArray) -> (StructArray, Option<FixedSizeBinaryArray>) {
+	let (mut fields, mut values, validity) = array.into_data();
+	match fields.last() {
+		Some(f) if f.name == TRAILING => {
+			fields.pop();
+			let trailing = values
+				.pop()
+				.unwrap()
+				.as_any()
+				.downcast_ref::<FixedSizeBinaryArray>()
+				.unwrap()
+				.clone();
+			(
+				StructArray::new(DataType::Struct(fields), values, validity),
+				Some(trailing),
+			)
+		}
+		_ => (
+			StructArray::new(DataType::Struct(fields), values, validity),
+			None,
+		),
+	}
+}
+
+impl Data {
 	fn into_struct_array(self, version: Version) -> StructArray {
 		let values = vec![
-			self.pre.into_struct_array(version).boxed(),
-			self.post.into_struct_array(version).boxed(),
+			with_trailing(self.pre.into_struct_array(version), self.pre_trailing).boxed(),
+			with_trailing(self.post.into_struct_array(version), self.post_trailing).boxed(),
 		];
-		StructArray::new(Self::data_type(version), values, self.validity)
+		StructArray::new(
+			struct_data_type(&["pre", "post"], &values),
+			values,
+			self.validity,
+		)
 	}
 
 	fn from_struct_array(array: StructArray, version: Version) -> Self {
 		let (_, values, validity) = array.into_data();
+		let (pre, pre_trailing) = split_trailing(
+			values[0]
+				.as_any()
+				.downcast_ref::<StructArray>()
+				.unwrap()
+				.clone(),
+		);
+		let (post, post_trailing) = split_trailing(
+			values[1]
+				.as_any()
+				.downcast_ref::<StructArray>()
+				.unwrap()
+				.clone(),
+		);
 		Self {
-			pre: Pre::from_struct_array(
-				values[0]
-					.as_any()
-					.downcast_ref::<StructArray>()
-					.unwrap()
-					.clone(),
-				version,
-			),
-			post: Post::from_struct_array(
-				values[1]
-					.as_any()
-					.downcast_ref::<StructArray>()
-					.unwrap()
-					.clone(),
-				version,
-			),
+			pre: Pre::from_struct_array(pre, version),
+			post: Post::from_struct_array(post, version),
 			validity: validity,
+			pre_trailing,
+			post_trailing,
 		}
 	}
 }
 
 impl PortData {
-	fn data_type(version: Version, port: PortOccupancy) -> DataType {
-		let mut fields = vec![Field::new(
-			"leader",
-			Data::data_type(version).clone(),
-			false,
-		)];
-		if port.follower {
-			fields.push(Field::new(
-				"follower",
-				Data::data_type(version).clone(),
-				false,
-			));
-		}
-		DataType::Struct(fields)
-	}
-
 	fn into_struct_array(self, version: Version, port: PortOccupancy) -> StructArray {
 		let mut values = vec![self.leader.into_struct_array(version).boxed()];
 		if let Some(follower) = self.follower {
 			values.push(follower.into_struct_array(version).boxed());
 		}
-		StructArray::new(Self::data_type(version, port), values, None)
+		StructArray::new(
+			struct_data_type(&["leader", "follower"], &values),
+			values,
+			None,
+		)
 	}
 
 	fn from_struct_array(array: StructArray, version: Version, port: Port) -> Self {
@@ -106,70 +143,46 @@ impl PortData {
 }
 
 impl Frame {
-	fn port_data_type(version: Version, ports: &[PortOccupancy]) -> DataType {
-		DataType::Struct(
-			ports
-				.iter()
-				.map(|p| {
-					Field::new(
-						format!("{}", p.port),
-						PortData::data_type(version, *p).clone(),
-						false,
-					)
-				})
-				.collect(),
-		)
-	}
-
-	fn item_data_type(version: Version) -> DataType {
-		DataType::List(Box::new(Field::new(
-			"item",
-			Item::data_type(version),
-			false,
-		)))
-	}
-
-	fn data_type(version: Version, ports: &[PortOccupancy]) -> DataType {
-		let mut fields = vec![
-			Field::new("id", DataType::Int32, false),
-			Field::new("ports", Self::port_data_type(version, ports).clone(), false),
-		];
-		if version.gte(2, 2) {
-			fields.push(Field::new(
-				"start",
-				Start::data_type(version).clone(),
-				false,
-			));
-			if version.gte(3, 0) {
-				fields.push(Field::new("end", End::data_type(version).clone(), false));
-				fields.push(Field::new(
-					"item",
-					Self::item_data_type(version).clone(),
-					false,
-				));
-			}
-		}
-		DataType::Struct(fields)
-	}
-
 	pub fn into_struct_array(self, version: Version, ports: &[PortOccupancy]) -> StructArray {
 		let values: Vec<_> = std::iter::zip(ports, self.ports)
 			.map(|(occupancy, data)| data.into_struct_array(version, *occupancy).boxed())
 			.collect();
+		let port_names: Vec<_> = ports.iter().map(|p| format!("{}", p.port)).collect();
+		let port_names: Vec<_> = port_names.iter().map(|n| n.as_str()).collect();
 
 		let mut arrays = vec![
 			self.id.boxed(),
-			StructArray::new(Self::port_data_type(version, ports), values, None).boxed(),
+			StructArray::new(struct_data_type(&port_names, &values), values, None).boxed(),
 		];
 
 		if version.gte(2, 2) {
-			arrays.push(self.start.unwrap().into_struct_array(version).boxed());
+			arrays.push(
+				with_trailing(
+					self.start.unwrap().into_struct_array(version),
+					self.start_trailing,
+				)
+				.boxed(),
+			);
 			if version.gte(3, 0) {
-				arrays.push(self.end.unwrap().into_struct_array(version).boxed());
-				let item_values = self.item.unwrap().into_struct_array(version).boxed();
+				arrays.push(
+					with_trailing(
+						self.end.unwrap().into_struct_array(version),
+						self.end_trailing,
+					)
+					.boxed(),
+				);
+				let item_values = with_trailing(
+					self.item.unwrap().into_struct_array(version),
+					self.item_trailing,
+				)
+				.boxed();
 				arrays.push(
 					ListArray::new(
-						Self::item_data_type(version),
+						DataType::List(Box::new(Field::new(
+							"item",
+							item_values.data_type().clone(),
+							false,
+						))),
 						self.item_offset.unwrap(),
 						item_values,
 						None,
@@ -179,7 +192,11 @@ impl Frame {
 			}
 		}
 
-		StructArray::new(Self::data_type(version, ports), arrays, None)
+		StructArray::new(
+			struct_data_type(&["id", "ports", "start", "end", "item"], &arrays),
+			arrays,
+			None,
+		)
 	}
 
 	fn port_data_from_struct_array(array: StructArray, version: Version) -> Vec<PortData> {
@@ -209,19 +226,34 @@ impl Frame {
 			}
 		}
 
-		let (item, item_offset) = values.get(4).map_or((None, None), |v| {
+		let (item, item_offset, item_trailing) = values.get(4).map_or((None, None, None), |v| {
 			let arrays = v.as_any().downcast_ref::<ListArray<i32>>().unwrap().clone();
 			let item_offset = arrays.offsets().clone();
-			let item = Item::from_struct_array(
+			let (item, item_trailing) = split_trailing(
 				arrays
 					.values()
 					.as_any()
 					.downcast_ref::<StructArray>()
 					.unwrap()
 					.clone(),
-				version,
 			);
-			(Some(item), Some(item_offset))
+			(
+				Some(Item::from_struct_array(item, version)),
+				Some(item_offset),
+				item_trailing,
+			)
+		});
+
+		let (start, start_trailing) = values.get(2).map_or((None, None), |v| {
+			let (start, trailing) =
+				split_trailing(v.as_any().downcast_ref::<StructArray>().unwrap().clone());
+			(Some(Start::from_struct_array(start, version)), trailing)
+		});
+
+		let (end, end_trailing) = values.get(3).map_or((None, None), |v| {
+			let (end, trailing) =
+				split_trailing(v.as_any().downcast_ref::<StructArray>().unwrap().clone());
+			(Some(End::from_struct_array(end, version)), trailing)
 		});
 
 		Self {
@@ -238,20 +270,13 @@ impl Frame {
 					.clone(),
 				version,
 			),
-			start: values.get(2).map(|v| {
-				Start::from_struct_array(
-					v.as_any().downcast_ref::<StructArray>().unwrap().clone(),
-					version,
-				)
-			}),
-			end: values.get(3).map(|v| {
-				End::from_struct_array(
-					v.as_any().downcast_ref::<StructArray>().unwrap().clone(),
-					version,
-				)
-			}),
-			item_offset: item_offset,
-			item: item,
+			start,
+			end,
+			item_offset,
+			item,
+			start_trailing,
+			end_trailing,
+			item_trailing,
 		}
 	}
 }
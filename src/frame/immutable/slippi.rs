@@ -7,6 +7,7 @@ use std::{
 	mem::size_of,
 };
 
+use arrow2::array::FixedSizeBinaryArray;
 use byteorder::WriteBytesExt;
 
 use crate::{
@@ -19,6 +20,18 @@ use crate::{
 
 type BE = byteorder::BigEndian;
 
+/// Writes the `i`th element of `trailing` (if any), for round-tripping newer replays.
+fn write_trailing<W: Write>(
+	w: &mut W,
+	trailing: Option<&FixedSizeBinaryArray>,
+	i: usize,
+) -> Result<()> {
+	match trailing {
+		Some(t) => w.write_all(t.value(i)),
+		None => Ok(()),
+	}
+}
+
 impl Data {
 	fn write_pre<W: Write>(
 		&self,
@@ -37,6 +50,7 @@ impl Data {
 				_ => 0,
 			})?;
 			self.pre.write(w, version, idx)?;
+			write_trailing(w, self.pre_trailing.as_ref(), idx)?;
 		}
 		Ok(())
 	}
@@ -58,6 +72,7 @@ impl Data {
 				_ => 0,
 			})?;
 			self.post.write(w, version, idx)?;
+			write_trailing(w, self.post_trailing.as_ref(), idx)?;
 		}
 		Ok(())
 	}
@@ -142,6 +157,7 @@ impl Frame {
 				w.write_u8(Event::FrameStart as u8)?;
 				w.write_i32::<BE>(frame_id)?;
 				self.start.as_ref().unwrap().write(w, version, idx)?;
+				write_trailing(w, self.start_trailing.as_ref(), idx)?;
 			}
 			for port in &self.ports {
 				port.write_pre(w, version, idx, frame_id)?;
@@ -152,6 +168,7 @@ impl Frame {
 					w.write_u8(Event::Item as u8)?;
 					w.write_i32::<BE>(frame_id)?;
 					self.item.as_ref().unwrap().write(w, version, item_idx)?;
+					write_trailing(w, self.item_trailing.as_ref(), item_idx)?;
 				}
 			}
 			for port in &self.ports {
@@ -161,6 +178,7 @@ impl Frame {
 				w.write_u8(Event::FrameEnd as u8)?;
 				w.write_i32::<BE>(frame_id)?;
 				self.end.as_ref().unwrap().write(w, version, idx)?;
+				write_trailing(w, self.end_trailing.as_ref(), idx)?;
 			}
 		}
 		Ok(())
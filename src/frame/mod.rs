@@ -11,6 +11,7 @@ use crate::game::Port;
 
 pub mod immutable;
 pub mod mutable;
+mod tidy;
 pub mod transpose;
 
 /// Frame indexes start at -123, and reach 0 at "Go!".
@@ -8,13 +8,13 @@
 #![allow(dead_code)]
 
 use arrow2::{
-	array::{MutableArray, MutablePrimitiveArray},
+	array::{MutableArray, MutableFixedSizeBinaryArray, MutablePrimitiveArray},
 	bitmap::MutableBitmap,
 	offset::Offsets,
 };
 
 use byteorder::ReadBytesExt;
-use std::io::Result;
+use std::io::{Error, ErrorKind, Result};
 
 use crate::{
 	frame::{transpose, PortOccupancy},
@@ -29,6 +29,10 @@ pub struct Data {
 	pub pre: Pre,
 	pub post: Post,
 	pub validity: Option<MutableBitmap>,
+	/// Unrecognized bytes at the end of each Frame Pre event (from newer Slippi versions)
+	pub pre_trailing: Option<MutableFixedSizeBinaryArray>,
+	/// Unrecognized bytes at the end of each Frame Post event (from newer Slippi versions)
+	pub post_trailing: Option<MutableFixedSizeBinaryArray>,
 }
 
 impl Data {
@@ -37,6 +41,8 @@ impl Data {
 			pre: Pre::with_capacity(capacity, version),
 			post: Post::with_capacity(capacity, version),
 			validity: None,
+			pre_trailing: None,
+			post_trailing: None,
 		}
 	}
 
@@ -51,6 +57,27 @@ impl Data {
 			.push(false);
 		self.pre.push_null(version);
 		self.post.push_null(version);
+		if let Some(t) = &mut self.pre_trailing {
+			t.push_null();
+		}
+		if let Some(t) = &mut self.post_trailing {
+			t.push_null();
+		}
+	}
+
+	/// Appends a frame's data for this character (see [`transpose_one`](Self::transpose_one)).
+	pub fn push_transposed(&mut self, x: transpose::Data, version: Version) {
+		if let Some(v) = &mut self.validity {
+			v.push(true);
+		}
+		self.pre.push_transposed(x.pre, version);
+		self.post.push_transposed(x.post, version);
+		if let Some(t) = &mut self.pre_trailing {
+			t.push_null();
+		}
+		if let Some(t) = &mut self.post_trailing {
+			t.push_null();
+		}
 	}
 
 	pub fn transpose_one(&self, i: usize, version: Version) -> transpose::Data {
@@ -85,6 +112,18 @@ impl PortData {
 		self.leader.len()
 	}
 
+	/// Appends a frame's data for this port. Pushes a null follower if `x`
+	/// has none (e.g. when Nana is dead).
+	pub fn push_transposed(&mut self, x: transpose::PortData, version: Version) {
+		self.leader.push_transposed(x.leader, version);
+		if let Some(f) = &mut self.follower {
+			match x.follower {
+				Some(x) => f.push_transposed(x, version),
+				None => f.push_null(version),
+			}
+		}
+	}
+
 	pub fn transpose_one(&self, i: usize, version: Version) -> transpose::PortData {
 		transpose::PortData {
 			port: self.port,
@@ -108,6 +147,12 @@ pub struct Frame {
 	pub item_offset: Option<Offsets<i32>>,
 	/// Item data
 	pub item: Option<Item>,
+	/// Unrecognized bytes at the end of each Frame Start event (from newer Slippi versions)
+	pub start_trailing: Option<MutableFixedSizeBinaryArray>,
+	/// Unrecognized bytes at the end of each Frame End event (from newer Slippi versions)
+	pub end_trailing: Option<MutableFixedSizeBinaryArray>,
+	/// Unrecognized bytes at the end of each Item event (from newer Slippi versions)
+	pub item_trailing: Option<MutableFixedSizeBinaryArray>,
 }
 
 impl Frame {
@@ -128,6 +173,9 @@ impl Frame {
 				.gte(3, 0)
 				.then(|| Offsets::<i32>::with_capacity(capacity)),
 			item: version.gte(3, 0).then(|| Item::with_capacity(0, version)),
+			start_trailing: None,
+			end_trailing: None,
+			item_trailing: None,
 		}
 	}
 
@@ -135,6 +183,51 @@ impl Frame {
 		self.id.len()
 	}
 
+	/// Appends a single frame (the inverse of [`transpose_one`](Self::transpose_one)).
+	/// Fields that `x` is missing but `version` requires are filled with
+	/// defaults (or nulls). Fails if `x` has different ports than `self`.
+	pub fn push_transposed(&mut self, x: transpose::Frame, version: Version) -> Result<()> {
+		let ports: Vec<_> = x.ports.iter().map(|p| p.port).collect();
+		if ports != self.ports.iter().map(|p| p.port).collect::<Vec<_>>() {
+			return Err(Error::new(
+				ErrorKind::InvalidData,
+				format!("frame {}: wrong ports: {:?}", x.id, ports),
+			));
+		}
+		let items = x.items.unwrap_or_default();
+		if let Some(item_offset) = &mut self.item_offset {
+			item_offset
+				.try_push(items.len() as i32)
+				.map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+		}
+
+		self.id.push(Some(x.id));
+		for (p, x) in self.ports.iter_mut().zip(x.ports) {
+			p.push_transposed(x, version);
+		}
+		if let Some(start) = &mut self.start {
+			start.push_transposed(x.start.unwrap_or_default(), version);
+		}
+		if let Some(end) = &mut self.end {
+			end.push_transposed(x.end.unwrap_or_default(), version);
+		}
+		if let Some(item) = &mut self.item {
+			for i in items {
+				item.push_transposed(i, version);
+				if let Some(t) = &mut self.item_trailing {
+					t.push_null();
+				}
+			}
+		}
+		for t in [&mut self.start_trailing, &mut self.end_trailing]
+			.into_iter()
+			.flatten()
+		{
+			t.push_null();
+		}
+		Ok(())
+	}
+
 	pub fn transpose_one(&self, i: usize, version: Version) -> transpose::Frame {
 		transpose::Frame {
 			id: self.id.values()[i],
@@ -206,6 +299,18 @@ impl End {
 		Ok(())
 	}
 
+	pub fn push_transposed(&mut self, x: transpose::End, version: Version) {
+		if version.gte(3, 7) {
+			self.latest_finalized_frame
+				.as_mut()
+				.unwrap()
+				.push(x.latest_finalized_frame)
+		};
+		if let Some(v) = self.validity.as_mut() {
+			v.push(true)
+		}
+	}
+
 	pub fn transpose_one(&self, i: usize, version: Version) -> transpose::End {
 		transpose::End {
 			latest_finalized_frame: self.latest_finalized_frame.as_ref().map(|x| x.values()[i]),
@@ -318,6 +423,32 @@ impl Item {
 		Ok(())
 	}
 
+	pub fn push_transposed(&mut self, x: transpose::Item, version: Version) {
+		self.r#type.push(Some(x.r#type));
+		self.state.push(Some(x.state));
+		self.direction.push(Some(x.direction));
+		self.velocity.push_transposed(x.velocity, version);
+		self.position.push_transposed(x.position, version);
+		self.damage.push(Some(x.damage));
+		self.timer.push(Some(x.timer));
+		self.id.push(Some(x.id));
+		if version.gte(3, 2) {
+			self.misc
+				.as_mut()
+				.unwrap()
+				.push_transposed(x.misc.unwrap_or_default(), version);
+			if version.gte(3, 6) {
+				self.owner.as_mut().unwrap().push(x.owner);
+				if version.gte(3, 16) {
+					self.instance_id.as_mut().unwrap().push(x.instance_id)
+				}
+			}
+		};
+		if let Some(v) = self.validity.as_mut() {
+			v.push(true)
+		}
+	}
+
 	pub fn transpose_one(&self, i: usize, version: Version) -> transpose::Item {
 		transpose::Item {
 			r#type: self.r#type.values()[i],
@@ -373,6 +504,13 @@ impl ItemMisc {
 		Ok(())
 	}
 
+	pub fn push_transposed(&mut self, x: transpose::ItemMisc, version: Version) {
+		self.0.push(Some(x.0));
+		self.1.push(Some(x.1));
+		self.2.push(Some(x.2));
+		self.3.push(Some(x.3))
+	}
+
 	pub fn transpose_one(&self, i: usize, version: Version) -> transpose::ItemMisc {
 		transpose::ItemMisc(
 			self.0.values()[i],
@@ -421,6 +559,14 @@ impl Position {
 		Ok(())
 	}
 
+	pub fn push_transposed(&mut self, x: transpose::Position, version: Version) {
+		self.x.push(Some(x.x));
+		self.y.push(Some(x.y));
+		if let Some(v) = self.validity.as_mut() {
+			v.push(true)
+		}
+	}
+
 	pub fn transpose_one(&self, i: usize, version: Version) -> transpose::Position {
 		transpose::Position {
 			x: self.x.values()[i],
@@ -643,6 +789,61 @@ impl Post {
 		Ok(())
 	}
 
+	pub fn push_transposed(&mut self, x: transpose::Post, version: Version) {
+		self.character.push(Some(x.character));
+		self.state.push(Some(x.state));
+		self.position.push_transposed(x.position, version);
+		self.direction.push(Some(x.direction));
+		self.percent.push(Some(x.percent));
+		self.shield.push(Some(x.shield));
+		self.last_attack_landed.push(Some(x.last_attack_landed));
+		self.combo_count.push(Some(x.combo_count));
+		self.last_hit_by.push(Some(x.last_hit_by));
+		self.stocks.push(Some(x.stocks));
+		if version.gte(0, 2) {
+			self.state_age.as_mut().unwrap().push(x.state_age);
+			if version.gte(2, 0) {
+				self.state_flags
+					.as_mut()
+					.unwrap()
+					.push_transposed(x.state_flags.unwrap_or_default(), version);
+				self.misc_as.as_mut().unwrap().push(x.misc_as);
+				self.airborne.as_mut().unwrap().push(x.airborne);
+				self.ground.as_mut().unwrap().push(x.ground);
+				self.jumps.as_mut().unwrap().push(x.jumps);
+				self.l_cancel.as_mut().unwrap().push(x.l_cancel);
+				if version.gte(2, 1) {
+					self.hurtbox_state.as_mut().unwrap().push(x.hurtbox_state);
+					if version.gte(3, 5) {
+						self.velocities
+							.as_mut()
+							.unwrap()
+							.push_transposed(x.velocities.unwrap_or_default(), version);
+						if version.gte(3, 8) {
+							self.hitlag.as_mut().unwrap().push(x.hitlag);
+							if version.gte(3, 11) {
+								self.animation_index
+									.as_mut()
+									.unwrap()
+									.push(x.animation_index);
+								if version.gte(3, 16) {
+									self.last_hit_by_instance
+										.as_mut()
+										.unwrap()
+										.push(x.last_hit_by_instance);
+									self.instance_id.as_mut().unwrap().push(x.instance_id)
+								}
+							}
+						}
+					}
+				}
+			}
+		};
+		if let Some(v) = self.validity.as_mut() {
+			v.push(true)
+		}
+	}
+
 	pub fn transpose_one(&self, i: usize, version: Version) -> transpose::Post {
 		transpose::Post {
 			character: self.character.values()[i],
@@ -797,6 +998,32 @@ impl Pre {
 		Ok(())
 	}
 
+	pub fn push_transposed(&mut self, x: transpose::Pre, version: Version) {
+		self.random_seed.push(Some(x.random_seed));
+		self.state.push(Some(x.state));
+		self.position.push_transposed(x.position, version);
+		self.direction.push(Some(x.direction));
+		self.joystick.push_transposed(x.joystick, version);
+		self.cstick.push_transposed(x.cstick, version);
+		self.triggers.push(Some(x.triggers));
+		self.buttons.push(Some(x.buttons));
+		self.buttons_physical.push(Some(x.buttons_physical));
+		self.triggers_physical
+			.push_transposed(x.triggers_physical, version);
+		if version.gte(1, 2) {
+			self.raw_analog_x.as_mut().unwrap().push(x.raw_analog_x);
+			if version.gte(1, 4) {
+				self.percent.as_mut().unwrap().push(x.percent);
+				if version.gte(3, 15) {
+					self.raw_analog_y.as_mut().unwrap().push(x.raw_analog_y)
+				}
+			}
+		};
+		if let Some(v) = self.validity.as_mut() {
+			v.push(true)
+		}
+	}
+
 	pub fn transpose_one(&self, i: usize, version: Version) -> transpose::Pre {
 		transpose::Pre {
 			random_seed: self.random_seed.values()[i],
@@ -863,6 +1090,19 @@ impl Start {
 		Ok(())
 	}
 
+	pub fn push_transposed(&mut self, x: transpose::Start, version: Version) {
+		self.random_seed.push(Some(x.random_seed));
+		if version.gte(3, 10) {
+			self.scene_frame_counter
+				.as_mut()
+				.unwrap()
+				.push(x.scene_frame_counter)
+		};
+		if let Some(v) = self.validity.as_mut() {
+			v.push(true)
+		}
+	}
+
 	pub fn transpose_one(&self, i: usize, version: Version) -> transpose::Start {
 		transpose::Start {
 			random_seed: self.random_seed.values()[i],
@@ -913,6 +1153,14 @@ impl StateFlags {
 		Ok(())
 	}
 
+	pub fn push_transposed(&mut self, x: transpose::StateFlags, version: Version) {
+		self.0.push(Some(x.0));
+		self.1.push(Some(x.1));
+		self.2.push(Some(x.2));
+		self.3.push(Some(x.3));
+		self.4.push(Some(x.4))
+	}
+
 	pub fn transpose_one(&self, i: usize, version: Version) -> transpose::StateFlags {
 		transpose::StateFlags(
 			self.0.values()[i],
@@ -962,6 +1210,14 @@ impl TriggersPhysical {
 		Ok(())
 	}
 
+	pub fn push_transposed(&mut self, x: transpose::TriggersPhysical, version: Version) {
+		self.l.push(Some(x.l));
+		self.r.push(Some(x.r));
+		if let Some(v) = self.validity.as_mut() {
+			v.push(true)
+		}
+	}
+
 	pub fn transpose_one(&self, i: usize, version: Version) -> transpose::TriggersPhysical {
 		transpose::TriggersPhysical {
 			l: self.l.values()[i],
@@ -1026,6 +1282,17 @@ impl Velocities {
 		Ok(())
 	}
 
+	pub fn push_transposed(&mut self, x: transpose::Velocities, version: Version) {
+		self.self_x_air.push(Some(x.self_x_air));
+		self.self_y.push(Some(x.self_y));
+		self.knockback_x.push(Some(x.knockback_x));
+		self.knockback_y.push(Some(x.knockback_y));
+		self.self_x_ground.push(Some(x.self_x_ground));
+		if let Some(v) = self.validity.as_mut() {
+			v.push(true)
+		}
+	}
+
 	pub fn transpose_one(&self, i: usize, version: Version) -> transpose::Velocities {
 		transpose::Velocities {
 			self_x_air: self.self_x_air.values()[i],
@@ -1075,6 +1342,14 @@ impl Velocity {
 		Ok(())
 	}
 
+	pub fn push_transposed(&mut self, x: transpose::Velocity, version: Version) {
+		self.x.push(Some(x.x));
+		self.y.push(Some(x.y));
+		if let Some(v) = self.validity.as_mut() {
+			v.push(true)
+		}
+	}
+
 	pub fn transpose_one(&self, i: usize, version: Version) -> transpose::Velocity {
 		transpose::Velocity {
 			x: self.x.values()[i],
@@ -0,0 +1,136 @@
+//! Conversion of frame data to a "tidy" (long-format) table.
+
+use arrow2::{
+	array::{Array, BooleanArray, PrimitiveArray, StructArray},
+	bitmap::Bitmap,
+	compute::{concatenate::concatenate, take::take},
+	datatypes::{DataType, Field},
+};
+
+use crate::{
+	frame::{immutable::Frame, PortOccupancy},
+	game::Port,
+	io::{flat, slippi::Version},
+};
+
+/// Frame data for one character (leader or follower) on one port.
+struct Character {
+	port: Port,
+	is_follower: bool,
+	validity: Option<Bitmap>,
+	fields: Vec<Field>,
+	columns: Vec<Box<dyn Array>>,
+}
+
+fn concat(arrays: Vec<&dyn Array>) -> Box<dyn Array> {
+	concatenate(&arrays).expect("arrays should have the same type")
+}
+
+impl Frame {
+	/// Converts to a "tidy" table, with one row per character per frame
+	/// (ordered by frame, then port, with leaders before followers). Rows are
+	/// skipped where a character has no data (e.g. when Nana is dead).
+	///
+	/// The columns are `frame_id`, `port`, `is_follower`, `character` (from
+	/// `post.character`, so null if the frame is incomplete), and the
+	/// character's other frame fields, flattened (e.g. `pre.joystick.x`,
+	/// `post.position.y`).
+	pub fn into_tidy(self, version: Version) -> StructArray {
+		let len = self.len();
+		let ports: Vec<_> = self
+			.ports
+			.iter()
+			.map(|p| PortOccupancy {
+				port: p.port,
+				follower: p.follower.is_some(),
+			})
+			.collect();
+		let id = self.id.clone();
+		let frame = self.into_struct_array(version, &ports);
+
+		let mut characters = vec![];
+		for (occupancy, (_, array)) in ports.iter().zip(flat::ports(&frame)) {
+			for (i, data) in array.values().iter().enumerate() {
+				let data = data
+					.as_any()
+					.downcast_ref::<StructArray>()
+					.expect("expected a `StructArray`");
+				let (fields, columns) = flat::columns(data);
+				characters.push(Character {
+					port: occupancy.port,
+					is_follower: i == 1,
+					validity: data.validity().cloned(),
+					fields,
+					columns,
+				});
+			}
+		}
+
+		let mut fields = vec![
+			Field::new("frame_id", DataType::Int32, false),
+			Field::new("port", DataType::UInt8, false),
+			Field::new("is_follower", DataType::Boolean, false),
+			Field::new("character", DataType::UInt8, true),
+		];
+		let first = match characters.first() {
+			Some(c) => c,
+			None => return StructArray::new_empty(DataType::Struct(fields)),
+		};
+
+		// indexes of valid rows, as if all characters were concatenated
+		let mut indexes = vec![];
+		for i in 0..len {
+			for (c, character) in characters.iter().enumerate() {
+				let valid = match &character.validity {
+					Some(v) => v.get_bit(i),
+					None => true,
+				};
+				if valid {
+					indexes.push((c * len + i) as u32);
+				}
+			}
+		}
+		let indexes = PrimitiveArray::from_vec(indexes);
+
+		let port_ids: Vec<_> = characters
+			.iter()
+			.map(|c| PrimitiveArray::from_vec(vec![u8::from(c.port); len]))
+			.collect();
+		let is_follower: Vec<_> = characters
+			.iter()
+			.map(|c| BooleanArray::from_slice(vec![c.is_follower; len]))
+			.collect();
+		let mut columns = vec![
+			concat(characters.iter().map(|_| &id as &dyn Array).collect()),
+			concat(port_ids.iter().map(|p| p as &dyn Array).collect()),
+			concat(is_follower.iter().map(|f| f as &dyn Array).collect()),
+		];
+
+		let character = first
+			.fields
+			.iter()
+			.position(|f| f.name == "post.character")
+			.expect("missing `post.character`");
+		columns.push(concat(
+			characters
+				.iter()
+				.map(|c| c.columns[character].as_ref())
+				.collect(),
+		));
+		for (i, field) in first.fields.iter().enumerate() {
+			if i == character {
+				continue;
+			}
+			fields.push(field.clone());
+			columns.push(concat(
+				characters.iter().map(|c| c.columns[i].as_ref()).collect(),
+			));
+		}
+
+		let columns: Vec<_> = columns
+			.iter()
+			.map(|c| take(c.as_ref(), &indexes).expect("indexes should be in bounds"))
+			.collect();
+		StructArray::new(DataType::Struct(fields), columns, None)
+	}
+}
@@ -4,22 +4,24 @@
 //!
 //! Transposing frame data is fairly slow. Work with Arrow arrays when possible.
 
+use serde::{Deserialize, Serialize};
+
 use crate::game::Port;
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
 pub struct Data {
 	pub pre: Pre,
 	pub post: Post,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
 pub struct PortData {
 	pub port: Port,
 	pub leader: Data,
 	pub follower: Option<Data>,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
 pub struct Frame {
 	pub id: i32,
 	pub ports: Vec<PortData>,
@@ -28,12 +30,12 @@ pub struct Frame {
 	pub items: Option<Vec<Item>>,
 }
 
-#[derive(PartialEq, Clone, Copy, Debug, Default)]
+#[derive(PartialEq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct End {
 	pub latest_finalized_frame: Option<i32>,
 }
 
-#[derive(PartialEq, Clone, Copy, Debug, Default)]
+#[derive(PartialEq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct Item {
 	pub r#type: u16,
 	pub state: u8,
@@ -48,16 +50,16 @@ pub struct Item {
 	pub instance_id: Option<u16>,
 }
 
-#[derive(PartialEq, Clone, Copy, Debug, Default)]
+#[derive(PartialEq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct ItemMisc(pub u8, pub u8, pub u8, pub u8);
 
-#[derive(PartialEq, Clone, Copy, Debug, Default)]
+#[derive(PartialEq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct Position {
 	pub x: f32,
 	pub y: f32,
 }
 
-#[derive(PartialEq, Clone, Copy, Debug, Default)]
+#[derive(PartialEq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct Post {
 	pub character: u8,
 	pub state: u16,
@@ -84,7 +86,7 @@ pub struct Post {
 	pub instance_id: Option<u16>,
 }
 
-#[derive(PartialEq, Clone, Copy, Debug, Default)]
+#[derive(PartialEq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct Pre {
 	pub random_seed: u32,
 	pub state: u16,
@@ -101,22 +103,22 @@ pub struct Pre {
 	pub raw_analog_y: Option<i8>,
 }
 
-#[derive(PartialEq, Clone, Copy, Debug, Default)]
+#[derive(PartialEq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct Start {
 	pub random_seed: u32,
 	pub scene_frame_counter: Option<u32>,
 }
 
-#[derive(PartialEq, Clone, Copy, Debug, Default)]
+#[derive(PartialEq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct StateFlags(pub u8, pub u8, pub u8, pub u8, pub u8);
 
-#[derive(PartialEq, Clone, Copy, Debug, Default)]
+#[derive(PartialEq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct TriggersPhysical {
 	pub l: f32,
 	pub r: f32,
 }
 
-#[derive(PartialEq, Clone, Copy, Debug, Default)]
+#[derive(PartialEq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct Velocities {
 	pub self_x_air: f32,
 	pub self_y: f32,
@@ -125,7 +127,7 @@ pub struct Velocities {
 	pub self_x_ground: f32,
 }
 
-#[derive(PartialEq, Clone, Copy, Debug, Default)]
+#[derive(PartialEq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct Velocity {
 	pub x: f32,
 	pub y: f32,
@@ -7,7 +7,8 @@ use serde_json::{Map, Value};
 
 use crate::{
 	frame::{immutable::Frame, transpose},
-	game::{self, End, GeckoCodes, Quirks, Start},
+	game::{self, mutable, End, GeckoCodes, Quirks, Start, UnknownEvent},
+	io::Warning,
 };
 
 #[derive(Debug)]
@@ -19,6 +20,10 @@ pub struct Game {
 	pub gecko_codes: Option<GeckoCodes>,
 	pub hash: Option<String>,
 	pub quirks: Option<Quirks>,
+	/// Events of unrecognized types, in the order they occurred.
+	pub unknown_events: Vec<UnknownEvent>,
+	/// Problems encountered while reading the replay, if any.
+	pub warnings: Vec<Warning>,
 }
 
 impl game::Game for Game {
@@ -46,3 +51,19 @@ impl game::Game for Game {
 		self.frames.transpose_one(idx, self.start.slippi.version)
 	}
 }
+
+impl From<mutable::Game> for Game {
+	fn from(game: mutable::Game) -> Self {
+		Self {
+			start: game.start,
+			end: game.end,
+			frames: game.frames.into(),
+			metadata: game.metadata,
+			gecko_codes: game.gecko_codes,
+			hash: game.hash,
+			quirks: game.quirks,
+			unknown_events: game.unknown_events,
+			warnings: game.warnings,
+		}
+	}
+}
@@ -6,7 +6,7 @@
 use std::fmt::{self, Debug, Display, Formatter};
 
 use num_enum::{IntoPrimitive, TryFromPrimitive};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::{Map, Value};
 
 use crate::{
@@ -31,7 +31,17 @@ pub const ICE_CLIMBERS: u8 = 14;
 /// A slot that can be occupied by a player.
 #[repr(u8)]
 #[derive(
-	Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, IntoPrimitive, TryFromPrimitive,
+	Clone,
+	Copy,
+	Debug,
+	PartialEq,
+	Eq,
+	PartialOrd,
+	Ord,
+	Serialize,
+	Deserialize,
+	IntoPrimitive,
+	TryFromPrimitive,
 )]
 pub enum Port {
 	P1 = 0,
@@ -72,7 +82,7 @@ impl Default for Port {
 
 /// How a player is controlled.
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, TryFromPrimitive)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, TryFromPrimitive)]
 pub enum PlayerType {
 	Human = 0,
 	Cpu = 1,
@@ -80,7 +90,7 @@ pub enum PlayerType {
 }
 
 /// Information about the team a player belongs to.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Team {
 	pub color: u8,
 	pub shade: u8,
@@ -88,7 +98,7 @@ pub struct Team {
 
 /// Dashback fix type.
 #[repr(u32)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, TryFromPrimitive)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, TryFromPrimitive)]
 pub enum DashBack {
 	Ucf = 1,
 	Arduino = 2,
@@ -96,7 +106,7 @@ pub enum DashBack {
 
 /// Shield drop fix type.
 #[repr(u32)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, TryFromPrimitive)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, TryFromPrimitive)]
 pub enum ShieldDrop {
 	Ucf = 1,
 	Arduino = 2,
@@ -104,21 +114,21 @@ pub enum ShieldDrop {
 
 /// The language the game is set to.
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, TryFromPrimitive)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, TryFromPrimitive)]
 pub enum Language {
 	Japanese = 0,
 	English = 1,
 }
 
 /// Information about the "Universal Controller Fix" mod.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Ucf {
 	pub dash_back: Option<DashBack>,
 	pub shield_drop: Option<ShieldDrop>,
 }
 
 /// Netplay name, connect code, and Slippi UID.
-#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Netplay {
 	pub name: MeleeString,
 
@@ -130,7 +140,7 @@ pub struct Netplay {
 }
 
 /// Information about each player such as character, team, stock count, etc.
-#[derive(Clone, Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Player {
 	pub port: Port,
 
@@ -175,14 +185,14 @@ pub struct Player {
 }
 
 /// Major & minor scene numbers.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Scene {
 	pub minor: u8,
 	pub major: u8,
 }
 
 /// Container for raw bytes of `Start` & `End` events.
-#[derive(PartialEq, Eq, Clone)]
+#[derive(PartialEq, Eq, Clone, Default)]
 pub struct Bytes(pub Vec<u8>);
 
 impl Debug for Bytes {
@@ -192,7 +202,7 @@ impl Debug for Bytes {
 }
 
 /// Information about the match a game belongs to.
-#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Match {
 	pub id: String,
 	pub game: u32,
@@ -200,7 +210,7 @@ pub struct Match {
 }
 
 /// Information used to initialize the game such as the game mode, settings, characters & stage.
-#[derive(Clone, Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Start {
 	pub slippi: slippi::Slippi,
 
@@ -252,9 +262,35 @@ pub struct Start {
 	pub r#match: Option<Match>,
 }
 
+impl Start {
+	pub(crate) fn size(version: Version) -> usize {
+		if version.gte(3, 14) {
+			760
+		} else if version.gte(3, 12) {
+			701
+		} else if version.gte(3, 11) {
+			700
+		} else if version.gte(3, 9) {
+			584
+		} else if version.gte(3, 7) {
+			420
+		} else if version.gte(2, 0) {
+			418
+		} else if version.gte(1, 5) {
+			417
+		} else if version.gte(1, 3) {
+			416
+		} else if version.gte(1, 0) {
+			352
+		} else {
+			320
+		}
+	}
+}
+
 /// How the game ended.
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, TryFromPrimitive)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, TryFromPrimitive)]
 pub enum EndMethod {
 	Unresolved = 0,
 	Time = 1,
@@ -264,14 +300,14 @@ pub enum EndMethod {
 }
 
 /// Player placements.
-#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PlayerEnd {
 	pub port: Port,
 	pub placement: u8,
 }
 
 /// Information about the end of the game.
-#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct End {
 	/// how the game ended
 	pub method: EndMethod,
@@ -282,7 +318,11 @@ pub struct End {
 	pub bytes: Bytes,
 
 	/// player who LRAS'd, if any (added: v2.0)
-	#[serde(skip_serializing_if = "Option::is_none")]
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		deserialize_with = "some"
+	)]
 	pub lras_initiator: Option<Option<Port>>,
 
 	/// player-specific data (added: v3.13)
@@ -290,6 +330,11 @@ pub struct End {
 	pub players: Option<Vec<PlayerEnd>>,
 }
 
+/// Deserializes a present-but-null value as `Some(None)` rather than `None`.
+fn some<'de, D: Deserializer<'de>, T: Deserialize<'de>>(d: D) -> Result<Option<T>, D::Error> {
+	T::deserialize(d).map(Some)
+}
+
 impl End {
 	pub(crate) fn size(version: Version) -> usize {
 		if version.gte(3, 13) {
@@ -305,12 +350,26 @@ impl End {
 /// Binary blob of Gecko codes in use.
 ///
 /// Currently unparsed, but still needed for round-tripping.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct GeckoCodes {
 	pub bytes: Vec<u8>,
 	pub actual_size: u32,
 }
 
+/// An event of a type Peppi doesn't recognize (e.g. from a newer Slippi version).
+///
+/// Unparsed, but still needed for round-tripping. An unknown event that was
+/// split across Message Splitter events is kept as those Message Splitter events.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnknownEvent {
+	pub code: u8,
+	/// Position in the raw event stream, as the number of known events
+	/// (excluding Event Payloads) that precede this one.
+	pub index: usize,
+	/// Payload, not including the event code.
+	pub bytes: Vec<u8>,
+}
+
 pub trait Game {
 	fn start(&self) -> &Start;
 	fn end(&self) -> &Option<End>;
@@ -325,10 +384,23 @@ pub trait Game {
 	fn frame(&self, idx: usize) -> transpose::Frame;
 }
 
-#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 /// Slippi quirks that we need to track for round-trip integrity.
 pub struct Quirks {
 	pub double_game_end: bool,
+	/// Event Payloads entries for event types Peppi doesn't recognize.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub unknown_payloads: Vec<UnknownPayload>,
+}
+
+/// An Event Payloads entry for an event type Peppi doesn't recognize,
+/// whether or not any such events occur.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnknownPayload {
+	/// Position among the Event Payloads entries.
+	pub index: usize,
+	pub code: u8,
+	pub size: u16,
 }
 
 pub fn port_occupancy(start: &Start) -> Vec<PortOccupancy> {
@@ -4,7 +4,7 @@
 
 use serde_json;
 
-use crate::{frame::mutable::Frame, game};
+use crate::{frame::mutable::Frame, game, io::Warning};
 
 pub struct Game {
 	pub start: game::Start,
@@ -14,4 +14,19 @@ pub struct Game {
 	pub gecko_codes: Option<game::GeckoCodes>,
 	pub hash: Option<String>,
 	pub quirks: Option<game::Quirks>,
+	pub unknown_events: Vec<game::UnknownEvent>,
+	pub warnings: Vec<Warning>,
+}
+
+impl Game {
+	/// Removes & returns the frames so far, leaving none (e.g. to hand them
+	/// to [`crate::io::peppi::ser::StreamWriter::write_frames`]).
+	pub fn take_frames(&mut self) -> Frame {
+		let frames = Frame::with_capacity(
+			0,
+			self.start.slippi.version,
+			&game::port_occupancy(&self.start),
+		);
+		std::mem::replace(&mut self.frames, frames)
+	}
 }
@@ -1,7 +1,7 @@
 //! Melee's internal string encoding.
 
 use encoding_rs::SHIFT_JIS;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::io::{err, Error, Result};
 
@@ -11,7 +11,7 @@ use crate::io::{err, Error, Result};
 /// unusually or inconsistently. For that reason, most users should use
 /// [to_normalized](crate::game::shift_jis::MeleeString::to_normalized) when
 /// working with this type.
-#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MeleeString(pub String);
 
 impl MeleeString {
@@ -30,6 +30,14 @@ impl MeleeString {
 	pub fn to_normalized(&self) -> String {
 		self.0.clone().chars().map(fix_char).collect::<String>()
 	}
+
+	/// Encodes the string back to Shift JIS (without a null terminator).
+	pub fn to_bytes(&self) -> Result<Vec<u8>> {
+		match SHIFT_JIS.encode(&self.0) {
+			(cow, _, false) => Ok(cow.into_owned()),
+			_ => Err(err!("can't encode as Shift JIS: {}", self.0)),
+		}
+	}
 }
 
 impl TryFrom<&[u8]> for MeleeString {
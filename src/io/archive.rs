@@ -0,0 +1,167 @@
+//! Reading replays directly out of zip & tar archives.
+//!
+//! Each `.slp` or `.slpp` entry is read into memory and parsed (see
+//! [`detect::read_slice`]), without extracting anything to disk. Other
+//! entries are ignored.
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use peppi::io::archive;
+//!
+//! let f = File::open("tournament.zip").unwrap();
+//! archive::read(f, None, |name, result| match result {
+//!     Ok(game) => println!("{}: {} frames", name, game.frames.len()),
+//!     Err(e) => eprintln!("{}: {}", name, e),
+//! })
+//! .unwrap();
+//! ```
+
+use std::io::{BufRead, BufReader, Read, Seek};
+
+use log::debug;
+
+use crate::{
+	game::immutable::Game,
+	io::{
+		detect::{self, Compression, Detected, Opts},
+		Result,
+	},
+};
+
+/// Zip archives start with a local file header.
+const ZIP_MAGIC: [u8; 4] = [0x50, 0x4b, 0x03, 0x04];
+
+/// Offset & value of the magic string in a (POSIX or GNU) tar header.
+const TAR_MAGIC_OFFSET: usize = 257;
+const TAR_MAGIC: [u8; 5] = [0x75, 0x73, 0x74, 0x61, 0x72];
+
+/// Most memory we'll allocate up front for an entry, based on its declared size.
+const MAX_PREALLOC: u64 = 1 << 20;
+
+/// Whether we should parse an entry named `name`.
+fn is_replay(name: &str) -> bool {
+	let name = name.to_ascii_lowercase();
+	name.ends_with(".slp") || name.ends_with(".slpp")
+}
+
+fn read_entry<R: Read>(mut r: R, size: u64, opts: Option<&Opts>) -> Result<Game> {
+	// don't trust the header's size for more than a reasonable replay's worth
+	let mut buf = Vec::with_capacity(size.min(MAX_PREALLOC) as usize);
+	r.read_to_end(&mut buf)?;
+	detect::read_slice(&buf, opts)
+}
+
+fn read_entries<R: Read, F: FnMut(&str, Result<Game>)>(
+	r: R,
+	opts: Option<&Opts>,
+	mut f: F,
+) -> Result<()> {
+	let mut archive = tar::Archive::new(r);
+	for entry in archive.entries()? {
+		let entry = entry?;
+		if !entry.header().entry_type().is_file() {
+			continue;
+		}
+		let name = entry.path()?.to_string_lossy().into_owned();
+		if !is_replay(&name) {
+			debug!("Skipping entry: {}", name);
+			continue;
+		}
+		let size = entry.size();
+		f(&name, read_entry(entry, size, opts));
+	}
+	Ok(())
+}
+
+fn read_tar_buffered<R: BufRead, F: FnMut(&str, Result<Game>)>(
+	mut r: R,
+	opts: Option<&Opts>,
+	f: F,
+) -> Result<()> {
+	let head = r.fill_buf()?;
+	match detect::detect(head) {
+		Some(Detected::Compressed(Compression::Gzip)) => {
+			read_entries(flate2::read::MultiGzDecoder::new(r), opts, f)
+		}
+		Some(Detected::Compressed(Compression::Zstd)) => {
+			read_entries(zstd::stream::read::Decoder::with_buffer(r)?, opts, f)
+		}
+		#[cfg(feature = "xz")]
+		Some(Detected::Compressed(Compression::Xz)) => {
+			read_entries(xz2::read::XzDecoder::new_multi_decoder(r), opts, f)
+		}
+		#[cfg(not(feature = "xz"))]
+		Some(Detected::Compressed(Compression::Xz)) => {
+			Err(err!("xz decompression requires the `xz` feature"))
+		}
+		_ if head.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + TAR_MAGIC.len()) == Some(&TAR_MAGIC) => {
+			read_entries(r, opts, f)
+		}
+		_ => Err(err!("unknown archive format")),
+	}
+}
+
+/// Parses each replay in the tar archive `r` (optionally compressed, as in
+/// `.tar.gz`), calling `f` with the entry's name and the result.
+///
+/// Unlike [`read`], `r` needn't be seekable.
+///
+/// Errors reading the archive itself are returned, and stop iteration.
+pub fn read_tar<R: Read, F: FnMut(&str, Result<Game>)>(
+	r: R,
+	opts: Option<&Opts>,
+	f: F,
+) -> Result<()> {
+	read_tar_buffered(BufReader::new(r), opts, f)
+}
+
+/// Parses each replay in the zip archive `r`, calling `f` with the entry's
+/// name and the result.
+///
+/// Errors reading the archive itself are returned, and stop iteration.
+#[cfg(feature = "zip")]
+pub fn read_zip<R: Read + Seek, F: FnMut(&str, Result<Game>)>(
+	r: R,
+	opts: Option<&Opts>,
+	mut f: F,
+) -> Result<()> {
+	let mut archive = zip::ZipArchive::new(r).map_err(|e| err!("invalid zip: {}", e))?;
+	for i in 0..archive.len() {
+		let entry = archive
+			.by_index(i)
+			.map_err(|e| err!("invalid zip entry: {}", e))?;
+		if !entry.is_file() {
+			continue;
+		}
+		let name = entry.name().to_string();
+		if !is_replay(&name) {
+			debug!("Skipping entry: {}", name);
+			continue;
+		}
+		let size = entry.size();
+		f(&name, read_entry(entry, size, opts));
+	}
+	Ok(())
+}
+
+/// Parses each replay in `r`, which may be a zip archive (requires the `zip`
+/// feature) or a tar archive (optionally compressed, as in `.tar.gz`).
+///
+/// Only zip archives need `r` to be seekable; use [`read_tar`] for streams.
+///
+/// See [`read_tar`] & [`read_zip`].
+pub fn read<R: Read + Seek, F: FnMut(&str, Result<Game>)>(
+	r: R,
+	opts: Option<&Opts>,
+	f: F,
+) -> Result<()> {
+	let mut r = BufReader::new(r);
+	let head = r.fill_buf()?;
+	if head.starts_with(&ZIP_MAGIC) {
+		#[cfg(feature = "zip")]
+		return read_zip(r, opts, f);
+		#[cfg(not(feature = "zip"))]
+		return Err(err!("zip archives require the `zip` feature"));
+	}
+	read_tar_buffered(r, opts, f)
+}
@@ -0,0 +1,164 @@
+//! Parsing many replays in parallel.
+//!
+//! ```no_run
+//! use peppi::io::batch;
+//!
+//! for (path, result) in batch::read_glob("tournament/**/*.slp", Default::default()).unwrap() {
+//!     match result {
+//!         Ok(game) => println!("{}: {} frames", path.display(), game.frames.len()),
+//!         Err(e) => eprintln!("{}: {}", path.display(), e),
+//!     }
+//! }
+//! ```
+
+use std::{
+	fs,
+	io::BufReader,
+	path::{Path, PathBuf},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		mpsc, Arc, Mutex,
+	},
+	thread,
+};
+
+use log::debug;
+
+use crate::{
+	game::immutable::Game,
+	io::{peppi, slippi, Error, Result},
+};
+
+/// Options for batch parsing.
+#[derive(Clone, Debug, Default)]
+pub struct Opts {
+	/// Number of worker threads. If 0, uses the available parallelism.
+	pub workers: usize,
+	/// Options for each `.slp` file.
+	pub slippi: Option<slippi::de::Opts>,
+	/// Options for each `.slpp` file.
+	pub peppi: Option<peppi::de::Opts>,
+	/// Stop after the first error (which is still yielded).
+	pub abort_on_error: bool,
+}
+
+/// Parses a single replay, choosing the format based on its extension
+/// (`.slpp` for Peppi, anything else for Slippi).
+pub fn read_path(path: &Path, opts: &Opts) -> Result<Game> {
+	match path.extension().and_then(|e| e.to_str()) {
+		Some("slpp") => peppi::read(BufReader::new(fs::File::open(path)?), opts.peppi.as_ref()),
+		_ => slippi::read_slice(&fs::read(path)?, opts.slippi.as_ref()),
+	}
+}
+
+/// Iterator over the results of a batch, in the order they complete.
+pub struct Batch {
+	rx: mpsc::Receiver<(PathBuf, Result<Game>)>,
+	abort: Arc<AtomicBool>,
+	abort_on_error: bool,
+}
+
+impl Iterator for Batch {
+	type Item = (PathBuf, Result<Game>);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.abort.load(Ordering::Relaxed) {
+			return None;
+		}
+		let (path, result) = self.rx.recv().ok()?;
+		if result.is_err() && self.abort_on_error {
+			debug!("Aborting batch after error in {}", path.display());
+			self.abort.store(true, Ordering::Relaxed);
+		}
+		Some((path, result))
+	}
+}
+
+impl Drop for Batch {
+	fn drop(&mut self) {
+		// workers finish their current file, but don't start any more
+		self.abort.store(true, Ordering::Relaxed);
+	}
+}
+
+/// Parses the replays at `paths` in parallel.
+///
+/// Returns immediately; the replays are parsed in the background as you
+/// iterate. Dropping the iterator stops any further parsing.
+pub fn read_all<I: IntoIterator<Item = PathBuf>>(paths: I, opts: Opts) -> Batch {
+	read_entries(paths.into_iter().map(Ok).collect(), opts)
+}
+
+/// Like [`read_all`], but entries we already failed to find (e.g. in an
+/// unreadable directory) are passed through as errors.
+fn read_entries(paths: Vec<std::result::Result<PathBuf, (PathBuf, Error)>>, opts: Opts) -> Batch {
+	let workers = match opts.workers {
+		0 => thread::available_parallelism().map_or(1, |n| n.get()),
+		n => n,
+	}
+	.min(paths.len().max(1));
+	debug!("Parsing {} replays with {} workers", paths.len(), workers);
+
+	let queue = Arc::new(Mutex::new(paths.into_iter()));
+	let abort = Arc::new(AtomicBool::new(false));
+	let opts = Arc::new(opts);
+	let (tx, rx) = mpsc::channel();
+	for _ in 0..workers {
+		let (queue, abort, opts, tx) = (queue.clone(), abort.clone(), opts.clone(), tx.clone());
+		thread::spawn(move || {
+			while !abort.load(Ordering::Relaxed) {
+				let (path, result) = match queue.lock().unwrap().next() {
+					Some(Ok(path)) => {
+						let result = read_path(&path, &opts);
+						(path, result)
+					}
+					Some(Err((path, e))) => (path, Err(e)),
+					None => break,
+				};
+				if tx.send((path, result)).is_err() {
+					break;
+				}
+			}
+		});
+	}
+
+	Batch {
+		rx,
+		abort,
+		abort_on_error: opts.abort_on_error,
+	}
+}
+
+/// Parses all `.slp` & `.slpp` files in `dir` (not including subdirectories)
+/// in parallel. See [`read_all`].
+pub fn read_dir<P: AsRef<Path>>(dir: P, opts: Opts) -> Result<Batch> {
+	let mut paths = Vec::new();
+	for entry in fs::read_dir(dir)? {
+		let path = entry?.path();
+		if path.is_file()
+			&& matches!(
+				path.extension().and_then(|e| e.to_str()),
+				Some("slp") | Some("slpp")
+			) {
+			paths.push(path);
+		}
+	}
+	paths.sort();
+	Ok(read_all(paths, opts))
+}
+
+/// Parses all files matching the glob `pattern` (e.g. `replays/**/*.slp`)
+/// in parallel. See [`read_all`].
+///
+/// Paths we can't read while matching (e.g. unreadable directories) are
+/// yielded along with their errors.
+pub fn read_glob(pattern: &str, opts: Opts) -> Result<Batch> {
+	let paths = glob::glob(pattern)
+		.map_err(|e| err!("invalid glob pattern: {}", e))?
+		.filter_map(|p| match p {
+			Ok(p) => p.is_file().then_some(Ok(p)),
+			Err(e) => Some(Err((e.path().to_path_buf(), Error::Io(e.into())))),
+		})
+		.collect();
+	Ok(read_entries(paths, opts))
+}
@@ -0,0 +1,372 @@
+//! Writing many games as a single dataset, for analyzing e.g. a whole season.
+//!
+//! A dataset is a directory containing two tables:
+//!
+//! * `player_frames/`: one row per port per frame, partitioned into one file
+//!   per game (`player_frames/<game ID>.arrow`). Columns are `game_id`,
+//!   `frame_id`, `port`, and each of the port's frame fields, flattened (e.g.
+//!   `leader.post.position.x`).
+//! * `games.arrow`: one row per game, from its [`Start`](crate::game::Start)
+//!   & [`End`](crate::game::End).
+//!
+//! (Or `.parquet`, with [`Format::Parquet`].)
+//!
+//! Every file has the same schema, regardless of each game's Slippi version
+//! or which ports it uses. Fields that a game's version doesn't have are
+//! null, as are `follower.*` fields for characters other than ICs. Games with
+//! fields too new for this version of Peppi (`*.trailing`) can't be written.
+//!
+//! ```no_run
+//! use peppi::io::{batch, dataset};
+//!
+//! let mut writer = dataset::Writer::new("season", None).unwrap();
+//! let opts = batch::Opts {
+//!     slippi: Some(peppi::io::slippi::de::Opts {
+//!         compute_hash: true,
+//!         ..Default::default()
+//!     }),
+//!     ..Default::default()
+//! };
+//! for (_, result) in batch::read_glob("season/**/*.slp", opts).unwrap() {
+//!     writer.write(result.unwrap()).unwrap();
+//! }
+//! writer.finish().unwrap();
+//! ```
+
+use std::{
+	collections::HashSet,
+	fs::{self, File},
+	io::BufWriter,
+	path::{Path, PathBuf},
+};
+
+use arrow2::{
+	array::{new_null_array, Array, BooleanArray, PrimitiveArray, Utf8Array},
+	chunk::Chunk,
+	datatypes::{DataType, Field, Schema},
+	io::ipc::write::{self as ipc, WriteOptions},
+};
+
+use crate::{
+	frame::{immutable::Frame, mutable::Frame as MutableFrame, PortOccupancy},
+	game::{immutable::Game, port_occupancy, End, Port, Start},
+	io::{flat, slippi::MAX_SUPPORTED_VERSION, Result},
+};
+
+#[cfg(feature = "parquet")]
+use arrow2::io::parquet::write::CompressionOptions;
+
+/// On-disk format of a dataset's files.
+#[derive(Clone, Copy, Debug)]
+pub enum Format {
+	/// Arrow IPC files (`.arrow`), optionally compressed.
+	Arrow(Option<ipc::Compression>),
+	/// Parquet files (`.parquet`), optionally compressed.
+	#[cfg(feature = "parquet")]
+	Parquet(Option<CompressionOptions>),
+}
+
+impl Default for Format {
+	fn default() -> Self {
+		Format::Arrow(None)
+	}
+}
+
+impl Format {
+	fn extension(&self) -> &'static str {
+		match self {
+			Format::Arrow(_) => "arrow",
+			#[cfg(feature = "parquet")]
+			Format::Parquet(_) => "parquet",
+		}
+	}
+}
+
+/// Options for writing datasets.
+#[derive(Clone, Debug, Default)]
+pub struct Opts {
+	pub format: Format,
+}
+
+/// Identifies a game within a dataset: its hash if known (see
+/// [`slippi::de::Opts::compute_hash`](crate::io::slippi::de::Opts::compute_hash)),
+/// or else its match ID plus game & tiebreaker numbers (v3.14+).
+pub fn game_id(game: &Game) -> Option<String> {
+	game.hash.clone().or_else(|| {
+		game.start
+			.r#match
+			.as_ref()
+			.filter(|m| !m.id.is_empty())
+			.map(|m| format!("{}.{}.{}", m.id, m.game, m.tiebreaker))
+	})
+}
+
+/// Escapes characters that aren't safe in file names (e.g. the `:` in
+/// `xxh3:…`), URL-style.
+fn file_stem(game_id: &str) -> String {
+	game_id
+		.bytes()
+		.map(|b| match b {
+			b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' => (b as char).to_string(),
+			_ => format!("%{:02X}", b),
+		})
+		.collect()
+}
+
+/// Frame fields for a single port, as of the latest Slippi version we know
+/// about (plus a follower, so we get those fields too).
+fn port_fields() -> Vec<Field> {
+	let version = MAX_SUPPORTED_VERSION;
+	let ports = [PortOccupancy {
+		port: Port::P1,
+		follower: true,
+	}];
+	let frame: Frame = MutableFrame::with_capacity(0, version, &ports).into();
+	let array = frame.into_struct_array(version, &ports);
+	let (fields, _) = flat::columns(flat::ports(&array).next().unwrap().1);
+	fields
+}
+
+fn write_file(
+	path: &Path,
+	schema: Schema,
+	chunks: Vec<Chunk<Box<dyn Array>>>,
+	format: Format,
+) -> Result<()> {
+	let w = BufWriter::new(File::create(path)?);
+	match format {
+		Format::Arrow(compression) => {
+			let mut writer =
+				ipc::FileWriter::try_new(w, schema, None, WriteOptions { compression })?;
+			for chunk in chunks {
+				writer.write(&chunk, None)?;
+			}
+			writer.finish()?;
+		}
+		#[cfg(feature = "parquet")]
+		Format::Parquet(compression) => {
+			super::parquet::write_chunks(w, schema, chunks, compression, None)?
+		}
+	}
+	Ok(())
+}
+
+/// Per-game table, built up as games are written.
+#[derive(Default)]
+struct Games {
+	game_id: Vec<String>,
+	slippi_version: Vec<String>,
+	frame_count: Vec<u32>,
+	stage: Vec<u16>,
+	timer: Vec<u32>,
+	is_teams: Vec<bool>,
+	is_pal: Vec<Option<bool>>,
+	match_id: Vec<Option<String>>,
+	match_game: Vec<Option<u32>>,
+	match_tiebreaker: Vec<Option<u32>>,
+	end_method: Vec<Option<u8>>,
+	lras_initiator: Vec<Option<u8>>,
+	start: Vec<String>,
+	end: Vec<Option<String>>,
+}
+
+impl Games {
+	fn push(
+		&mut self,
+		game_id: String,
+		start: &Start,
+		end: Option<&End>,
+		frame_count: usize,
+	) -> Result<()> {
+		let m = start.r#match.as_ref();
+		self.game_id.push(game_id);
+		self.slippi_version.push(start.slippi.version.to_string());
+		self.frame_count.push(frame_count as u32);
+		self.stage.push(start.stage);
+		self.timer.push(start.timer);
+		self.is_teams.push(start.is_teams);
+		self.is_pal.push(start.is_pal);
+		self.match_id.push(m.map(|m| m.id.clone()));
+		self.match_game.push(m.map(|m| m.game));
+		self.match_tiebreaker.push(m.map(|m| m.tiebreaker));
+		self.end_method.push(end.map(|e| e.method as u8));
+		self.lras_initiator
+			.push(end.and_then(|e| e.lras_initiator).flatten().map(u8::from));
+		self.start.push(serde_json::to_string(start)?);
+		self.end.push(end.map(serde_json::to_string).transpose()?);
+		Ok(())
+	}
+
+	fn into_chunk(self) -> (Schema, Chunk<Box<dyn Array>>) {
+		let columns: Vec<(&str, Box<dyn Array>, bool)> = vec![
+			(
+				"game_id",
+				Utf8Array::<i32>::from_slice(self.game_id).boxed(),
+				false,
+			),
+			(
+				"slippi_version",
+				Utf8Array::<i32>::from_slice(self.slippi_version).boxed(),
+				false,
+			),
+			(
+				"frame_count",
+				PrimitiveArray::from_vec(self.frame_count).boxed(),
+				false,
+			),
+			("stage", PrimitiveArray::from_vec(self.stage).boxed(), false),
+			("timer", PrimitiveArray::from_vec(self.timer).boxed(), false),
+			(
+				"is_teams",
+				BooleanArray::from_slice(self.is_teams).boxed(),
+				false,
+			),
+			("is_pal", BooleanArray::from(self.is_pal).boxed(), true),
+			(
+				"match_id",
+				Utf8Array::<i32>::from(self.match_id).boxed(),
+				true,
+			),
+			(
+				"match_game",
+				PrimitiveArray::from(self.match_game).boxed(),
+				true,
+			),
+			(
+				"match_tiebreaker",
+				PrimitiveArray::from(self.match_tiebreaker).boxed(),
+				true,
+			),
+			(
+				"end_method",
+				PrimitiveArray::from(self.end_method).boxed(),
+				true,
+			),
+			(
+				"lras_initiator",
+				PrimitiveArray::from(self.lras_initiator).boxed(),
+				true,
+			),
+			(
+				"start",
+				Utf8Array::<i32>::from_slice(self.start).boxed(),
+				false,
+			),
+			("end", Utf8Array::<i32>::from(self.end).boxed(), true),
+		];
+		let fields: Vec<_> = columns
+			.iter()
+			.map(|(name, array, nullable)| Field::new(*name, array.data_type().clone(), *nullable))
+			.collect();
+		(
+			Schema::from(fields),
+			Chunk::new(columns.into_iter().map(|(_, a, _)| a).collect()),
+		)
+	}
+}
+
+/// Writes games to a dataset directory, one at a time.
+pub struct Writer {
+	dir: PathBuf,
+	opts: Opts,
+	/// Schema of the `player_frames` table.
+	schema: Schema,
+	game_ids: HashSet<String>,
+	games: Games,
+}
+
+impl Writer {
+	/// Starts writing a dataset to `dir`, creating it if necessary.
+	pub fn new<P: AsRef<Path>>(dir: P, opts: Option<&Opts>) -> Result<Self> {
+		let dir = dir.as_ref().to_path_buf();
+		fs::create_dir_all(dir.join("player_frames"))?;
+		let mut fields = vec![
+			Field::new("game_id", DataType::Utf8, false),
+			Field::new("frame_id", DataType::Int32, false),
+			Field::new("port", DataType::UInt8, false),
+		];
+		fields.extend(port_fields());
+		Ok(Self {
+			dir,
+			opts: opts.cloned().unwrap_or_default(),
+			schema: Schema::from(fields),
+			game_ids: HashSet::new(),
+			games: Default::default(),
+		})
+	}
+
+	/// Schema of the `player_frames` table.
+	pub fn schema(&self) -> &Schema {
+		&self.schema
+	}
+
+	/// Writes a game's frames to `player_frames`, and adds it to `games`.
+	/// Returns the game's ID (see [`game_id`]).
+	///
+	/// Fails if the game has no ID, or if its ID is already in the dataset.
+	pub fn write(&mut self, game: Game) -> Result<String> {
+		let id = game_id(&game).ok_or(err!("game has no hash or match ID"))?;
+		if self.game_ids.contains(&id) {
+			return Err(err!("duplicate game ID: {}", id));
+		}
+		let version = game.start.slippi.version;
+		let len = game.frames.len();
+		let ports = port_occupancy(&game.start);
+		let frame = game.frames.into_struct_array(version, &ports);
+		let mut chunks = vec![];
+		for (name, array) in flat::ports(&frame) {
+			let port = Port::parse(name).map_err(|e| err!("{}", e))?;
+			let (fields, columns) = flat::columns(array);
+			// e.g. `trailing` fields, from a Slippi version newer than we know
+			if let Some(f) = fields
+				.iter()
+				.find(|f| !self.schema.fields.iter().any(|t| t.name == f.name))
+			{
+				return Err(err!("unsupported field: {}", f.name));
+			}
+
+			let mut arrays = vec![
+				Utf8Array::<i32>::from_iter_values(vec![&id; len].into_iter()).boxed(),
+				frame.values()[0].clone(),
+				PrimitiveArray::from_vec(vec![u8::from(port); len]).boxed(),
+			];
+			for target in &self.schema.fields[arrays.len()..] {
+				let column = match fields.iter().position(|f| f.name == target.name) {
+					Some(i) if columns[i].data_type() == &target.data_type => columns[i].clone(),
+					Some(i) => {
+						return Err(err!(
+							"type mismatch for {}: expected {:?}, got {:?}",
+							target.name,
+							target.data_type,
+							columns[i].data_type()
+						))
+					}
+					None => new_null_array(target.data_type.clone(), len),
+				};
+				arrays.push(column);
+			}
+			chunks.push(Chunk::new(arrays));
+		}
+
+		let path = self.dir.join("player_frames").join(format!(
+			"{}.{}",
+			file_stem(&id),
+			self.opts.format.extension()
+		));
+		write_file(&path, self.schema.clone(), chunks, self.opts.format)?;
+		self.games
+			.push(id.clone(), &game.start, game.end.as_ref(), len)?;
+		self.game_ids.insert(id.clone());
+		Ok(id)
+	}
+
+	/// Writes the `games` table, and returns the dataset's directory.
+	pub fn finish(self) -> Result<PathBuf> {
+		let (schema, chunk) = self.games.into_chunk();
+		let path = self
+			.dir
+			.join(format!("games.{}", self.opts.format.extension()));
+		write_file(&path, schema, vec![chunk], self.opts.format)?;
+		Ok(self.dir)
+	}
+}
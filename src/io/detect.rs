@@ -0,0 +1,112 @@
+//! Reading replays without knowing their format in advance.
+
+use std::io::Read;
+
+use log::debug;
+
+use crate::{
+	game::immutable::Game,
+	io::{peppi, slippi, Result},
+};
+
+/// Replay file format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+	/// Slippi (`.slp`).
+	Slippi,
+	/// Peppi (`.slpp`).
+	Peppi,
+}
+
+/// Compression wrapped around a replay file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+	Gzip,
+	Zstd,
+	/// Requires the `xz` feature to decompress.
+	Xz,
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const XZ_MAGIC: [u8; 6] = [0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00];
+
+/// What we found at the start of a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Detected {
+	Replay(Format),
+	Compressed(Compression),
+}
+
+/// Identifies a file from its first few bytes (at least 11, to be sure).
+pub fn detect(bytes: &[u8]) -> Option<Detected> {
+	if bytes.starts_with(&slippi::FILE_SIGNATURE) {
+		Some(Detected::Replay(Format::Slippi))
+	} else if bytes.starts_with(&peppi::FILE_SIGNATURE) {
+		Some(Detected::Replay(Format::Peppi))
+	} else if bytes.starts_with(&GZIP_MAGIC) {
+		Some(Detected::Compressed(Compression::Gzip))
+	} else if bytes.starts_with(&ZSTD_MAGIC) {
+		Some(Detected::Compressed(Compression::Zstd))
+	} else if bytes.starts_with(&XZ_MAGIC) {
+		Some(Detected::Compressed(Compression::Xz))
+	} else {
+		None
+	}
+}
+
+/// Options for reading replays of either format.
+#[derive(Clone, Debug, Default)]
+pub struct Opts {
+	/// Options for Slippi (`.slp`) replays.
+	pub slippi: Option<slippi::de::Opts>,
+	/// Options for Peppi (`.slpp`) replays.
+	pub peppi: Option<peppi::de::Opts>,
+}
+
+fn decompress(bytes: &[u8], compression: Compression) -> Result<Vec<u8>> {
+	let mut buf = Vec::new();
+	match compression {
+		Compression::Gzip => flate2::read::MultiGzDecoder::new(bytes).read_to_end(&mut buf)?,
+		Compression::Zstd => zstd::stream::read::Decoder::new(bytes)?.read_to_end(&mut buf)?,
+		#[cfg(feature = "xz")]
+		Compression::Xz => xz2::read::XzDecoder::new_multi_decoder(bytes).read_to_end(&mut buf)?,
+		#[cfg(not(feature = "xz"))]
+		Compression::Xz => return Err(err!("xz decompression requires the `xz` feature")),
+	};
+	Ok(buf)
+}
+
+/// Reads a replay of either format from `bytes`, decompressing it first if
+/// necessary.
+pub fn read_slice(bytes: &[u8], opts: Option<&Opts>) -> Result<Game> {
+	match detect(bytes) {
+		Some(Detected::Replay(Format::Slippi)) => {
+			slippi::read_slice(bytes, opts.and_then(|o| o.slippi.as_ref()))
+		}
+		Some(Detected::Replay(Format::Peppi)) => {
+			peppi::read(bytes, opts.and_then(|o| o.peppi.as_ref()))
+		}
+		Some(Detected::Compressed(compression)) => {
+			debug!("Decompressing ({:?})", compression);
+			let bytes = decompress(bytes, compression)?;
+			// only one layer of compression is allowed
+			match detect(&bytes) {
+				Some(Detected::Compressed(_)) | None => Err(err!("unknown replay format")),
+				_ => read_slice(&bytes, opts),
+			}
+		}
+		None => Err(err!("unknown replay format")),
+	}
+}
+
+/// Reads a replay of either format from `r`, decompressing it first if
+/// necessary.
+///
+/// This reads all of `r` into memory. If you know the format in advance,
+/// [`slippi::read`] or [`peppi::read`] may use less memory.
+pub fn read<R: Read>(mut r: R, opts: Option<&Opts>) -> Result<Game> {
+	let mut buf = Vec::new();
+	r.read_to_end(&mut buf)?;
+	read_slice(&buf, opts)
+}
@@ -0,0 +1,180 @@
+//! Plain-text exports of frame data (CSV & NDJSON), e.g. for spreadsheets.
+//!
+//! Each row is a frame, and each column a (non-struct) frame field, named by
+//! its dot-separated path (e.g. `ports.P1.leader.post.position.x`). Columns
+//! cover every field in the latest Slippi version, so games with the same
+//! ports always get the same columns. Fields that a game's version doesn't
+//! have are empty cells (CSV) or `null`s (NDJSON), as are follower fields on
+//! frames where the follower has no data.
+//!
+//! ```no_run
+//! use std::{fs, io};
+//! use peppi::{frame::Rollbacks, io::{export, slippi}};
+//!
+//! let game = slippi::read(io::BufReader::new(fs::File::open("game.slp").unwrap()), None).unwrap();
+//! let opts = export::Opts {
+//!     columns: Some(vec!["ports.*.leader.post.position".to_string()]),
+//!     rollbacks: Some(Rollbacks::ExceptLast),
+//! };
+//! let w = io::BufWriter::new(fs::File::create("game.csv").unwrap());
+//! export::write_csv(w, game.frames, game.start.slippi.version, Some(&opts)).unwrap();
+//! ```
+
+use std::io::Write;
+
+use arrow2::{
+	array::{new_null_array, PrimitiveArray, StructArray},
+	chunk::Chunk,
+	compute::take::take,
+	datatypes::DataType,
+	io::{csv, ndjson},
+};
+
+use crate::{
+	frame::{immutable::Frame, mutable::Frame as MutableFrame, PortOccupancy, Rollbacks},
+	io::{
+		flat::{flatten, patterns, select, Selection},
+		slippi::{Version, MAX_SUPPORTED_VERSION},
+		Result,
+	},
+};
+
+/// Options for exporting frame data.
+#[derive(Clone, Debug, Default)]
+pub struct Opts {
+	/// Only write these columns, as dot-separated paths (e.g.
+	/// `ports.*.leader.post.position`), where `*` matches any one field.
+	/// Selecting a field selects everything under it. If unset, writes all
+	/// columns except `item` (item data), which can only be written to NDJSON.
+	pub columns: Option<Vec<String>>,
+
+	/// Skip frames that were rolled back, keeping only the first or last
+	/// instance of each frame ID (see [`Frame::rollbacks`]).
+	pub rollbacks: Option<Rollbacks>,
+}
+
+/// Flattens `frame` into a struct of the selected columns, with rows for the
+/// selected frames.
+fn columns(frame: Frame, version: Version, opts: &Opts) -> Result<StructArray> {
+	let ports: Vec<_> = frame
+		.ports
+		.iter()
+		.map(|p| PortOccupancy {
+			port: p.port,
+			follower: p.follower.is_some(),
+		})
+		.collect();
+	let len = frame.len();
+	let rows = opts.rollbacks.map(|keep| {
+		let rollbacks = frame.rollbacks(keep);
+		PrimitiveArray::from_vec(
+			(0..len as u32)
+				.filter(|i| !rollbacks[*i as usize])
+				.collect(),
+		)
+	});
+
+	// all fields as of the latest version, so every export has the same columns
+	let latest: Frame = MutableFrame::with_capacity(0, MAX_SUPPORTED_VERSION, &ports).into();
+	let mut fields = vec![];
+	flatten(
+		&latest.into_struct_array(MAX_SUPPORTED_VERSION, &ports),
+		"",
+		None,
+		&mut fields,
+		&mut vec![],
+	);
+
+	let mut actual_fields = vec![];
+	let mut actual_columns = vec![];
+	flatten(
+		&frame.into_struct_array(version, &ports),
+		"",
+		None,
+		&mut actual_fields,
+		&mut actual_columns,
+	);
+
+	let patterns = opts
+		.columns
+		.as_ref()
+		.map(|c| patterns(c.iter().map(String::as_str)));
+	let mut fields: Vec<_> = fields
+		.into_iter()
+		.filter(|f| {
+			let path: Vec<_> = f.name.split('.').collect();
+			match &patterns {
+				Some(p) => select(&path, p) == Selection::Full,
+				None => path[0] != "item",
+			}
+		})
+		.collect();
+	if fields.is_empty() {
+		return Err(err!("no columns selected"));
+	}
+
+	let mut columns = vec![];
+	for field in &mut fields {
+		let column = match actual_fields.iter().position(|f| f.name == field.name) {
+			Some(i) if actual_columns[i].data_type() == &field.data_type => {
+				actual_columns[i].clone()
+			}
+			// item fields vary by version, so we use the game's own
+			Some(i) if matches!(field.data_type, DataType::List(_)) => {
+				field.data_type = actual_columns[i].data_type().clone();
+				actual_columns[i].clone()
+			}
+			Some(i) => {
+				return Err(err!(
+					"type mismatch for {}: expected {:?}, got {:?}",
+					field.name,
+					field.data_type,
+					actual_columns[i].data_type()
+				))
+			}
+			None => new_null_array(field.data_type.clone(), len),
+		};
+		columns.push(match &rows {
+			Some(rows) => take(column.as_ref(), rows)?,
+			None => column,
+		});
+	}
+	Ok(StructArray::new(DataType::Struct(fields), columns, None))
+}
+
+/// Writes frames to `w` as CSV, with a header row.
+pub fn write_csv<W: Write>(
+	mut w: W,
+	frame: Frame,
+	version: Version,
+	opts: Option<&Opts>,
+) -> Result<()> {
+	let array = columns(frame, version, &opts.cloned().unwrap_or_default())?;
+	if let Some(f) = array
+		.fields()
+		.iter()
+		.find(|f| matches!(f.data_type, DataType::List(_)))
+	{
+		return Err(err!("can't write list column to CSV: {}", f.name));
+	}
+	let options = csv::write::SerializeOptions::default();
+	let names: Vec<_> = array.fields().iter().map(|f| &f.name).collect();
+	csv::write::write_header(&mut w, &names, &options)?;
+	csv::write::write_chunk(&mut w, &Chunk::new(array.values().to_vec()), &options)?;
+	Ok(())
+}
+
+/// Writes frames to `w` as NDJSON (one JSON object per line).
+pub fn write_ndjson<W: Write>(
+	w: W,
+	frame: Frame,
+	version: Version,
+	opts: Option<&Opts>,
+) -> Result<()> {
+	let array = columns(frame, version, &opts.cloned().unwrap_or_default())?;
+	let serializer = ndjson::write::Serializer::new(std::iter::once(Ok(array.boxed())), vec![]);
+	for result in ndjson::write::FileWriter::new(w, serializer) {
+		result?;
+	}
+	Ok(())
+}
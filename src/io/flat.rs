@@ -0,0 +1,106 @@
+//! Flattening nested struct arrays into columns, named by their
+//! dot-separated paths (e.g. `ports.P1.leader.post.position.x`), and
+//! selecting fields by those paths.
+
+use arrow2::{
+	array::{Array, StructArray},
+	bitmap::Bitmap,
+	datatypes::Field,
+};
+
+fn and(a: Option<&Bitmap>, b: Option<&Bitmap>) -> Option<Bitmap> {
+	match (a, b) {
+		(Some(a), Some(b)) => Some(a & b),
+		(Some(v), None) | (None, Some(v)) => Some(v.clone()),
+		(None, None) => None,
+	}
+}
+
+/// Appends a column for each non-struct field under `array`, named by its
+/// path prefixed with `prefix`. Nulls are pushed down, so a column is null
+/// wherever any of its ancestors is.
+pub(crate) fn flatten(
+	array: &StructArray,
+	prefix: &str,
+	validity: Option<&Bitmap>,
+	fields: &mut Vec<Field>,
+	columns: &mut Vec<Box<dyn Array>>,
+) {
+	let validity = and(validity, array.validity());
+	for (field, value) in array.fields().iter().zip(array.values()) {
+		let name = format!("{}{}", prefix, field.name);
+		match value.as_any().downcast_ref::<StructArray>() {
+			Some(s) => flatten(s, &format!("{}.", name), validity.as_ref(), fields, columns),
+			None => {
+				let value = match &validity {
+					Some(_) => value.with_validity(and(validity.as_ref(), value.validity())),
+					None => value.clone(),
+				};
+				fields.push(Field::new(name, value.data_type().clone(), true));
+				columns.push(value);
+			}
+		}
+	}
+}
+
+/// Flattens `array` (see [`flatten`]), returning the fields & columns.
+pub(crate) fn columns(array: &StructArray) -> (Vec<Field>, Vec<Box<dyn Array>>) {
+	let mut fields = vec![];
+	let mut columns = vec![];
+	flatten(array, "", None, &mut fields, &mut columns);
+	(fields, columns)
+}
+
+fn downcast(array: &dyn Array) -> &StructArray {
+	array
+		.as_any()
+		.downcast_ref::<StructArray>()
+		.expect("expected a `StructArray`")
+}
+
+/// Each port's frame data in `frame` (as returned by
+/// [`Frame::into_struct_array`](crate::frame::immutable::Frame::into_struct_array)),
+/// with its name (e.g. `P1`).
+pub(crate) fn ports(frame: &StructArray) -> impl Iterator<Item = (&str, &StructArray)> {
+	let i = frame
+		.fields()
+		.iter()
+		.position(|f| f.name == "ports")
+		.expect("missing `ports`");
+	let ports = downcast(frame.values()[i].as_ref());
+	ports
+		.fields()
+		.iter()
+		.zip(ports.values())
+		.map(|(f, v)| (f.name.as_str(), downcast(v.as_ref())))
+}
+
+/// How a field relates to the requested paths.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Selection {
+	/// Not requested.
+	None,
+	/// Some descendants requested.
+	Partial,
+	/// Requested, along with all descendants.
+	Full,
+}
+
+/// Splits dot-separated field paths into patterns for [`select`].
+pub(crate) fn patterns<'a, I: IntoIterator<Item = &'a str>>(paths: I) -> Vec<Vec<&'a str>> {
+	paths.into_iter().map(|p| p.split('.').collect()).collect()
+}
+
+pub(crate) fn select(path: &[&str], patterns: &[Vec<&str>]) -> Selection {
+	let mut selection = Selection::None;
+	for pattern in patterns {
+		let matches = pattern.iter().zip(path).all(|(p, s)| *p == "*" || p == s);
+		if matches {
+			if pattern.len() <= path.len() {
+				return Selection::Full;
+			}
+			selection = Selection::Partial;
+		}
+	}
+	selection
+}
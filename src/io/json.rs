@@ -0,0 +1,294 @@
+//! Whole games as JSON, for editing by hand or with scripts.
+//!
+//! A game is a single JSON object with `start`, `end`, `metadata`, `quirks`,
+//! and `frames` keys. `start` & `end` are as in a `.slpp` file's `start.json`
+//! & `end.json`, and each frame is a [`transpose::Frame`]. Everything else
+//! a `.slp` needs is kept as byte arrays, so nothing is lost converting back:
+//! the raw Game Start & Game End events (`start_raw` & `end_raw`), Gecko
+//! codes, unknown events, and unrecognized trailing bytes of frame events
+//! (`gecko_codes`, `unknown_events`, & `trailing`, omitted if there are none).
+//!
+//! Games read from JSON can be written as `.slp`: [`slippi::ser::write`]
+//! regenerates the raw Game Start & Game End events from their fields, so
+//! edits to `start` & `end` take effect. `start_raw` & `end_raw` are optional,
+//! and only supply the bytes that Peppi doesn't parse.
+//!
+//! ```no_run
+//! use std::{fs, io};
+//! use peppi::io::{json, slippi};
+//!
+//! let r = io::BufReader::new(fs::File::open("game.json").unwrap());
+//! let game = json::read(r).unwrap();
+//! let mut w = io::BufWriter::new(fs::File::create("game.slp").unwrap());
+//! slippi::write(&mut w, &game).unwrap();
+//! ```
+
+use std::io::{Read, Write};
+
+use arrow2::array::FixedSizeBinaryArray;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::{
+	frame::{
+		immutable::{Data, Frame},
+		mutable::Frame as MutableFrame,
+		transpose,
+	},
+	game::{immutable::Game, port_occupancy, Bytes, End, GeckoCodes, Quirks, Start, UnknownEvent},
+	io::{slippi, Result},
+};
+
+/// All frames, transposed one at a time as they're serialized.
+struct Frames<'a>(&'a Game);
+
+impl Serialize for Frames<'_> {
+	fn serialize<S: Serializer>(&self, s: S) -> std::result::Result<S::Ok, S::Error> {
+		let version = self.0.start.slippi.version;
+		let frames = &self.0.frames;
+		s.collect_seq((0..frames.len()).map(|i| {
+			let mut frame = frames.transpose_one(i, version);
+			// missing followers (e.g. when Nana is dead) are null, not zeroed
+			for (x, p) in frame.ports.iter_mut().zip(&frames.ports) {
+				let validity = p.follower.as_ref().and_then(|f| f.validity.as_ref());
+				if validity.is_some_and(|v| !v.get_bit(i)) {
+					x.follower = None;
+				}
+			}
+			frame
+		}))
+	}
+}
+
+/// Unrecognized bytes at the end of each event of a given type (see e.g.
+/// [`Frame::start_trailing`]), with nulls for missing events.
+#[derive(Serialize, Deserialize)]
+struct Trailing {
+	size: usize,
+	values: Vec<Option<Vec<u8>>>,
+}
+
+impl Trailing {
+	fn new(array: Option<&FixedSizeBinaryArray>) -> Option<Self> {
+		array.map(|a| Trailing {
+			size: a.size(),
+			values: a.iter().map(|v| v.map(<[u8]>::to_vec)).collect(),
+		})
+	}
+
+	fn into_array(t: Option<Self>, len: usize) -> Result<Option<FixedSizeBinaryArray>> {
+		t.map(|t| match t.values.len() == len {
+			true => Ok(FixedSizeBinaryArray::try_from_iter(t.values, t.size)?),
+			_ => Err(err!(
+				"expected {} trailing values, got {}",
+				len,
+				t.values.len()
+			)),
+		})
+		.transpose()
+	}
+}
+
+#[derive(Serialize, Deserialize)]
+struct DataTrailing {
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pre: Option<Trailing>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	post: Option<Trailing>,
+}
+
+impl DataTrailing {
+	fn new(data: &Data) -> Self {
+		Self {
+			pre: Trailing::new(data.pre_trailing.as_ref()),
+			post: Trailing::new(data.post_trailing.as_ref()),
+		}
+	}
+
+	fn apply(self, data: &mut Data, len: usize) -> Result<()> {
+		data.pre_trailing = Trailing::into_array(self.pre, len)?;
+		data.post_trailing = Trailing::into_array(self.post, len)?;
+		Ok(())
+	}
+}
+
+#[derive(Serialize, Deserialize)]
+struct PortTrailing {
+	leader: DataTrailing,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	follower: Option<DataTrailing>,
+}
+
+/// Unrecognized trailing bytes of all frame events.
+#[derive(Serialize, Deserialize)]
+struct FrameTrailing {
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	start: Option<Trailing>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	end: Option<Trailing>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	item: Option<Trailing>,
+	ports: Vec<PortTrailing>,
+}
+
+impl FrameTrailing {
+	/// Returns `None` if `frames` has no trailing bytes.
+	fn new(frames: &Frame) -> Option<Self> {
+		let data = frames
+			.ports
+			.iter()
+			.flat_map(|p| std::iter::once(&p.leader).chain(p.follower.as_ref()));
+		let any = [
+			&frames.start_trailing,
+			&frames.end_trailing,
+			&frames.item_trailing,
+		]
+		.into_iter()
+		.chain(data.flat_map(|d| [&d.pre_trailing, &d.post_trailing]))
+		.any(Option::is_some);
+		any.then(|| Self {
+			start: Trailing::new(frames.start_trailing.as_ref()),
+			end: Trailing::new(frames.end_trailing.as_ref()),
+			item: Trailing::new(frames.item_trailing.as_ref()),
+			ports: frames
+				.ports
+				.iter()
+				.map(|p| PortTrailing {
+					leader: DataTrailing::new(&p.leader),
+					follower: p.follower.as_ref().map(DataTrailing::new),
+				})
+				.collect(),
+		})
+	}
+
+	fn apply(self, frames: &mut Frame) -> Result<()> {
+		let len = frames.len();
+		if self.ports.len() != frames.ports.len() {
+			return Err(err!(
+				"expected trailing bytes for {} ports, got {}",
+				frames.ports.len(),
+				self.ports.len()
+			));
+		}
+		frames.start_trailing = Trailing::into_array(self.start, len)?;
+		frames.end_trailing = Trailing::into_array(self.end, len)?;
+		let items = frames.item.as_ref().map_or(0, |i| i.id.len());
+		frames.item_trailing = Trailing::into_array(self.item, items)?;
+		for (p, t) in frames.ports.iter_mut().zip(self.ports) {
+			t.leader.apply(&mut p.leader, len)?;
+			match (&mut p.follower, t.follower) {
+				(Some(f), Some(t)) => t.apply(f, len)?,
+				(None, Some(_)) => return Err(err!("trailing bytes for missing follower")),
+				_ => {}
+			}
+		}
+		Ok(())
+	}
+}
+
+#[derive(Serialize)]
+struct GameRef<'a> {
+	start: &'a Start,
+	end: &'a Option<End>,
+	metadata: &'a Option<Map<String, Value>>,
+	quirks: &'a Option<Quirks>,
+	#[serde(skip_serializing_if = "<[_]>::is_empty")]
+	start_raw: &'a [u8],
+	#[serde(skip_serializing_if = "Option::is_none")]
+	end_raw: Option<&'a [u8]>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	gecko_codes: &'a Option<GeckoCodes>,
+	#[serde(skip_serializing_if = "<[_]>::is_empty")]
+	unknown_events: &'a [UnknownEvent],
+	#[serde(skip_serializing_if = "Option::is_none")]
+	trailing: Option<FrameTrailing>,
+	frames: Frames<'a>,
+}
+
+#[derive(Deserialize)]
+struct GameOwned {
+	start: Start,
+	end: Option<End>,
+	metadata: Option<Map<String, Value>>,
+	quirks: Option<Quirks>,
+	#[serde(default)]
+	start_raw: Vec<u8>,
+	#[serde(default)]
+	end_raw: Option<Vec<u8>>,
+	#[serde(default)]
+	gecko_codes: Option<GeckoCodes>,
+	#[serde(default)]
+	unknown_events: Vec<UnknownEvent>,
+	#[serde(default)]
+	trailing: Option<FrameTrailing>,
+	frames: Vec<transpose::Frame>,
+}
+
+/// Writes `game` to `w` as JSON.
+pub fn write<W: Write>(w: W, game: &Game) -> Result<()> {
+	serde_json::to_writer(
+		w,
+		&GameRef {
+			start: &game.start,
+			end: &game.end,
+			metadata: &game.metadata,
+			quirks: &game.quirks,
+			start_raw: &game.start.bytes.0,
+			end_raw: game.end.as_ref().map(|e| &e.bytes.0[..]),
+			gecko_codes: &game.gecko_codes,
+			unknown_events: &game.unknown_events,
+			trailing: FrameTrailing::new(&game.frames),
+			frames: Frames(game),
+		},
+	)?;
+	Ok(())
+}
+
+/// Reads a game from JSON (as written by [`write`]).
+///
+/// Frames must have the ports given by `start.players`. Fields that a frame
+/// is missing but the game's Slippi version requires are filled with defaults.
+pub fn read<R: Read>(r: R) -> Result<Game> {
+	let GameOwned {
+		mut start,
+		mut end,
+		metadata,
+		quirks,
+		start_raw,
+		end_raw,
+		gecko_codes,
+		unknown_events,
+		trailing,
+		frames: transposed,
+	} = serde_json::from_reader(r)?;
+	let version = start.slippi.version;
+
+	start.bytes = Bytes(start_raw);
+	start.bytes = Bytes(slippi::ser::start_bytes(&start)?);
+	if let Some(end) = &mut end {
+		end.bytes = Bytes(end_raw.unwrap_or_default());
+		end.bytes = Bytes(slippi::ser::end_bytes(end, version)?);
+	}
+
+	let mut frames =
+		MutableFrame::with_capacity(transposed.len(), version, &port_occupancy(&start));
+	for frame in transposed {
+		frames.push_transposed(frame, version)?;
+	}
+	let mut frames: Frame = frames.into();
+	if let Some(trailing) = trailing {
+		trailing.apply(&mut frames)?;
+	}
+
+	Ok(Game {
+		start,
+		end,
+		frames,
+		metadata,
+		gecko_codes,
+		hash: None,
+		quirks,
+		unknown_events,
+		warnings: vec![],
+	})
+}
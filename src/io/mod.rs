@@ -1,6 +1,7 @@
 //! Serialization and deserialization of replays.
 //!
 //! Peppi supports reading and writing both `.slp` (Slippi) and `.slpp` (Peppi) replays.
+//! Use [`read`] if you don't know which format (or compression) a replay uses.
 
 macro_rules! err {
 	($( $arg: expr ),*) => {
@@ -10,11 +11,25 @@ macro_rules! err {
 
 pub(crate) use err;
 
+pub mod archive;
+pub mod batch;
+pub mod dataset;
+pub mod detect;
+pub mod export;
+pub(crate) mod flat;
+pub mod json;
+#[cfg(feature = "parquet")]
+pub mod parquet;
 pub mod peppi;
 pub mod slippi;
 pub(crate) mod ubjson;
 
-use std::io::{Read, Seek, SeekFrom};
+pub use detect::read;
+
+use std::{
+	fmt,
+	io::{Read, Seek, SeekFrom, Write},
+};
 
 use thiserror::Error as ThisError;
 use xxhash_rust::xxh3::Xxh3;
@@ -36,6 +51,163 @@ pub enum Error {
 
 	#[error("invalid UTF8: {0}")]
 	Utf8(#[from] std::string::FromUtf8Error),
+
+	/// A frame event's ID doesn't match the ID of the frame being parsed.
+	#[error("frame ID mismatch: expected {expected}, got {actual}")]
+	FrameIdMismatch { expected: i32, actual: i32 },
+
+	/// A frame event arrived before any frame was started.
+	#[error("missing frame start for frame {id}")]
+	MissingFrameStart { id: i32 },
+
+	/// Follower data for a port whose character has no follower (i.e. isn't ICs).
+	#[error("unexpected follower for port {port}")]
+	UnexpectedFollower { port: u8 },
+
+	/// Frame data for a port that isn't occupied.
+	#[error("invalid port: {port}")]
+	InvalidPort { port: u8 },
+
+	/// An event that can't occur at this point in the replay, or in a replay of this Slippi version.
+	#[error("unexpected event")]
+	UnexpectedEvent,
+
+	/// The Event Payloads event didn't give a size for an event we need (e.g. Game End).
+	#[error("missing payload size for event {code:#04x}")]
+	MissingPayloadSize { code: u8 },
+
+	/// Re-serializing a replay didn't reproduce the original `.slp`, according to its hash.
+	#[error("hash mismatch: expected {expected}, got {actual}")]
+	HashMismatch { expected: String, actual: String },
+
+	/// An error encountered while parsing a specific event.
+	#[error("{source} ({context})")]
+	Parse {
+		context: Context,
+		source: Box<Error>,
+	},
+}
+
+impl Error {
+	/// The kind of error, for bucketing failures without matching on messages.
+	/// Looks through [`Error::Parse`] to the underlying error.
+	pub fn kind(&self) -> ErrorKind {
+		use Error::*;
+		match self {
+			InvalidData(_) => ErrorKind::InvalidData,
+			Io(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => ErrorKind::UnexpectedEof,
+			Io(_) => ErrorKind::Io,
+			Arrow(_) => ErrorKind::Arrow,
+			Json(_) => ErrorKind::Json,
+			Utf8(_) => ErrorKind::Utf8,
+			FrameIdMismatch { .. } => ErrorKind::FrameIdMismatch,
+			MissingFrameStart { .. } => ErrorKind::MissingFrameStart,
+			UnexpectedFollower { .. } => ErrorKind::UnexpectedFollower,
+			InvalidPort { .. } => ErrorKind::InvalidPort,
+			UnexpectedEvent => ErrorKind::UnexpectedEvent,
+			MissingPayloadSize { .. } => ErrorKind::MissingPayloadSize,
+			HashMismatch { .. } => ErrorKind::HashMismatch,
+			Parse { source, .. } => source.kind(),
+		}
+	}
+
+	/// Where in the replay the error occurred, if known.
+	pub fn context(&self) -> Option<&Context> {
+		match self {
+			Error::Parse { context, .. } => Some(context),
+			_ => None,
+		}
+	}
+
+	/// Attaches `context` to this error, unless it already has some.
+	pub(crate) fn with_context(self, context: Context) -> Self {
+		match self {
+			e @ Error::Parse { .. } => e,
+			e => Error::Parse {
+				context,
+				source: Box::new(e),
+			},
+		}
+	}
+}
+
+/// A non-fatal problem encountered while reading a replay.
+#[derive(ThisError, Debug)]
+pub enum Warning {
+	/// Parsing stopped at `error`, and everything after it (including Game End & metadata) was
+	/// dropped. Only occurs when reading leniently (see [`slippi::de::Opts::lenient`]).
+	#[error("stopped early: {error}")]
+	Truncated {
+		error: Error,
+		/// ID of the frame that was being parsed when the error occurred, if it was missing
+		/// any data. Missing data for this frame is null.
+		incomplete_frame: Option<i32>,
+	},
+
+	/// The replay's metadata couldn't be parsed, so it was dropped.
+	#[error("dropped metadata: {error}")]
+	InvalidMetadata { error: Error },
+
+	/// A second Game End event followed the first, and was skipped.
+	/// Recorded as [`crate::game::Quirks::double_game_end`] so it round-trips.
+	#[error("skipped duplicate Game End event")]
+	DuplicateGameEnd,
+
+	/// Unrecognized bytes between Game End and the end of the raw event stream, which were skipped.
+	#[error("extra content after Game End ({len} bytes)")]
+	ExtraContent { len: usize },
+
+	/// Events extended past the end of the raw event stream (as declared in the file header).
+	#[error("consumed more than expected ({len} bytes)")]
+	Overrun { len: usize },
+}
+
+/// Discriminant of an [`Error`], ignoring any payload or context.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+	InvalidData,
+	/// The replay ended unexpectedly (e.g. it was truncated).
+	UnexpectedEof,
+	Io,
+	Arrow,
+	Json,
+	Utf8,
+	FrameIdMismatch,
+	MissingFrameStart,
+	UnexpectedFollower,
+	InvalidPort,
+	UnexpectedEvent,
+	MissingPayloadSize,
+	HashMismatch,
+}
+
+/// Location of a parse error within a replay.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Context {
+	/// Offset of the event in bytes, relative to the start of the raw event stream.
+	pub offset: usize,
+	/// Code of the event being parsed, if it was read.
+	pub code: Option<u8>,
+	/// ID of the frame being parsed, if any.
+	pub frame_id: Option<i32>,
+	/// Slippi version of the replay, if known.
+	pub version: Option<slippi::Version>,
+}
+
+impl fmt::Display for Context {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.code {
+			Some(code) => write!(f, "event {:#04x} @{:#x}", code, self.offset)?,
+			None => write!(f, "@{:#x}", self.offset)?,
+		}
+		if let Some(id) = self.frame_id {
+			write!(f, ", frame {}", id)?;
+		}
+		if let Some(version) = self.version {
+			write!(f, ", version {}", version)?;
+		}
+		Ok(())
+	}
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -76,6 +248,30 @@ impl<R: Read + Seek> Seek for HashingReader<R> {
 	}
 }
 
+/// Writer that hashes (and discards) the bytes written to it.
+struct HashingWriter(Box<Xxh3>);
+
+impl HashingWriter {
+	pub fn new() -> Self {
+		Self(Box::new(Xxh3::new()))
+	}
+
+	pub fn into_digest(self) -> String {
+		format_hash(&self.0)
+	}
+}
+
+impl Write for HashingWriter {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.0.update(buf);
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
+
 fn parse_u8(s: &str) -> Result<u8> {
 	s.parse().map_err(|_| err!("couldn't parse integer: {}", s))
 }
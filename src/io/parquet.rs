@@ -0,0 +1,252 @@
+//! Parquet export of frame data, for querying replays with tools like
+//! DuckDB or Spark.
+//!
+//! Frames are flattened into one column per (non-struct) field, named by its
+//! dot-separated path (e.g. `ports.P1.leader.post.position.x`). Nulls are
+//! pushed down, so a column is null wherever any of its ancestors is (e.g.
+//! every `ports.P1.follower.*` column, on frames without a follower). Item
+//! data stays a single (list) column, `item`.
+//!
+//! Game-level data that isn't per-frame goes in the file's key/value metadata,
+//! as JSON (see [`START_KEY`] etc).
+
+use std::{
+	collections::HashMap,
+	io::{Read, Seek, Write},
+};
+
+use arrow2::{
+	array::{new_empty_array, Array, StructArray},
+	chunk::Chunk,
+	compute::concatenate::concatenate,
+	datatypes::{DataType, Field, Schema},
+	io::parquet::{
+		read,
+		write::{
+			self, transverse, CompressionOptions, Encoding, FileWriter, KeyValue, RowGroupIterator,
+			Version,
+		},
+	},
+};
+
+use crate::{
+	frame::immutable::Frame,
+	game::{immutable::Game, port_occupancy},
+	io::{flat::flatten, slippi, Result},
+};
+
+/// Key for the Slippi version of the game (e.g. `3.16.0`).
+pub const VERSION_KEY: &str = "peppi.slippi_version";
+
+/// Key for the game's start block, as JSON.
+pub const START_KEY: &str = "peppi.start";
+
+/// Key for the game's end block, as JSON (if any).
+pub const END_KEY: &str = "peppi.end";
+
+/// Key for the game's metadata block, as JSON (if any).
+pub const METADATA_KEY: &str = "peppi.metadata";
+
+/// Key for the hash of the original `.slp` (if known).
+pub const HASH_KEY: &str = "peppi.slp_hash";
+
+/// Options for writing Parquet files.
+#[derive(Clone, Debug, Default)]
+pub struct Opts {
+	/// Compression codec for data pages (uncompressed if unset).
+	pub compression: Option<CompressionOptions>,
+
+	/// Maximum number of frames per row group. All frames go in a single row
+	/// group if unset.
+	pub row_group_size: Option<usize>,
+}
+
+/// Inverse of [`flatten`](super::flat::flatten), for the columns in `fields` & `columns` whose
+/// names start with `prefix`. A struct is valid wherever any of its fields
+/// are.
+fn unflatten(
+	prefix: &str,
+	fields: &mut std::iter::Peekable<std::vec::IntoIter<(Field, Box<dyn Array>)>>,
+) -> Result<StructArray> {
+	let mut struct_fields: Vec<Field> = vec![];
+	let mut values: Vec<Box<dyn Array>> = vec![];
+	while let Some((field, _)) = fields.peek() {
+		let rest = match field.name.strip_prefix(prefix) {
+			Some(rest) => rest,
+			None => break,
+		};
+		let (name, value) = match rest.split_once('.') {
+			Some((name, _)) => {
+				let name = name.to_string();
+				let child = unflatten(&format!("{}{}.", prefix, name), fields)?;
+				(name, child.boxed())
+			}
+			None => {
+				let name = rest.to_string();
+				let (_, value) = fields.next().unwrap();
+				(name, value)
+			}
+		};
+		if struct_fields.iter().any(|f| f.name == name) {
+			return Err(err!("non-contiguous columns: {}{}", prefix, name));
+		}
+		struct_fields.push(Field::new(name, value.data_type().clone(), true));
+		values.push(value);
+	}
+
+	if values.is_empty() {
+		return Err(err!("no columns: {}", prefix));
+	}
+	let validity = values
+		.iter()
+		.map(|v| v.validity())
+		.collect::<Option<Vec<_>>>()
+		.map(|bitmaps| {
+			bitmaps[1..]
+				.iter()
+				.fold(bitmaps[0].clone(), |acc, b| &acc | *b)
+		});
+	Ok(StructArray::new(
+		DataType::Struct(struct_fields),
+		values,
+		validity,
+	))
+}
+
+fn key_value(key: &str, value: String) -> KeyValue {
+	KeyValue {
+		key: key.to_string(),
+		value: Some(value),
+	}
+}
+
+/// Writes a game's frames to `w` in Parquet format.
+pub fn write<W: Write>(w: W, game: Game, opts: Option<&Opts>) -> Result<()> {
+	let opts = opts.cloned().unwrap_or_default();
+	if opts.row_group_size == Some(0) {
+		return Err(err!("row group size must be positive"));
+	}
+	let version = game.start.slippi.version;
+
+	let mut key_value_metadata = vec![
+		key_value(VERSION_KEY, version.to_string()),
+		key_value(START_KEY, serde_json::to_string(&game.start)?),
+	];
+	if let Some(end) = &game.end {
+		key_value_metadata.push(key_value(END_KEY, serde_json::to_string(end)?));
+	}
+	if let Some(metadata) = &game.metadata {
+		key_value_metadata.push(key_value(METADATA_KEY, serde_json::to_string(metadata)?));
+	}
+	if let Some(hash) = &game.hash {
+		key_value_metadata.push(key_value(HASH_KEY, hash.clone()));
+	}
+
+	let ports = port_occupancy(&game.start);
+	let len = game.frames.len();
+	let array = game.frames.into_struct_array(version, &ports);
+	let mut fields = vec![];
+	let mut columns = vec![];
+	flatten(&array, "", None, &mut fields, &mut columns);
+	let schema = Schema::from(fields);
+
+	let row_group_size = opts.row_group_size.unwrap_or(len).max(1);
+	let chunks = (0..len).step_by(row_group_size).map(|start| {
+		let end = (start + row_group_size).min(len);
+		Chunk::new(
+			columns
+				.iter()
+				.map(|c| c.sliced(start, end - start))
+				.collect(),
+		)
+	});
+	write_chunks(
+		w,
+		schema,
+		chunks,
+		opts.compression,
+		Some(key_value_metadata),
+	)
+}
+
+/// Writes `chunks` to `w` as a Parquet file, one row group per chunk.
+pub(crate) fn write_chunks<W, I>(
+	w: W,
+	schema: Schema,
+	chunks: I,
+	compression: Option<CompressionOptions>,
+	key_value_metadata: Option<Vec<KeyValue>>,
+) -> Result<()>
+where
+	W: Write,
+	I: IntoIterator<Item = Chunk<Box<dyn Array>>>,
+{
+	let options = write::WriteOptions {
+		write_statistics: true,
+		version: Version::V2,
+		compression: compression.unwrap_or(CompressionOptions::Uncompressed),
+		data_pagesize_limit: None,
+	};
+	let encodings = schema
+		.fields
+		.iter()
+		.map(|f| transverse(&f.data_type, |_| Encoding::Plain))
+		.collect();
+	let row_groups =
+		RowGroupIterator::try_new(chunks.into_iter().map(Ok), &schema, options, encodings)?;
+
+	let mut writer = FileWriter::try_new(w, schema, options)?;
+	for group in row_groups {
+		writer.write(group?)?;
+	}
+	writer.end(key_value_metadata)?;
+	Ok(())
+}
+
+/// Reads the key/value metadata of a Parquet file written by [`write`].
+pub fn read_metadata<R: Read + Seek>(r: &mut R) -> Result<HashMap<String, String>> {
+	let metadata = read::read_metadata(r)?;
+	Ok(metadata
+		.key_value_metadata()
+		.iter()
+		.flatten()
+		.filter_map(|kv| kv.value.clone().map(|v| (kv.key.clone(), v)))
+		.collect())
+}
+
+/// Reads the frames of a Parquet file written by [`write`].
+pub fn read<R: Read + Seek>(mut r: R) -> Result<Frame> {
+	let metadata = read::read_metadata(&mut r)?;
+	let version: slippi::Version = metadata
+		.key_value_metadata()
+		.iter()
+		.flatten()
+		.find(|kv| kv.key == VERSION_KEY)
+		.and_then(|kv| kv.value.as_deref())
+		.ok_or(err!("missing key: {}", VERSION_KEY))?
+		.parse()?;
+	let schema = read::infer_schema(&metadata)?;
+	let fields = schema.fields.clone();
+
+	let reader = read::FileReader::new(r, metadata.row_groups, schema, None, None, None);
+	let mut chunks = vec![];
+	for chunk in reader {
+		chunks.push(chunk?);
+	}
+	let columns = (0..fields.len())
+		.map(|i| match chunks.len() {
+			0 => Ok(new_empty_array(fields[i].data_type.clone())),
+			1 => Ok(chunks[0].arrays()[i].clone()),
+			_ => concatenate(
+				&chunks
+					.iter()
+					.map(|c| c.arrays()[i].as_ref())
+					.collect::<Vec<_>>(),
+			),
+		})
+		.collect::<std::result::Result<Vec<_>, _>>()?;
+
+	let columns: Vec<_> = fields.into_iter().zip(columns).collect();
+	let array = unflatten("", &mut columns.into_iter().peekable())?;
+	Ok(Frame::from_struct_array(array, version))
+}
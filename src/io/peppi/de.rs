@@ -1,16 +1,17 @@
-use std::io::Read;
+use std::{io::Read, ops::Range};
 
 use log::debug;
 
 use arrow2::{
-	array::StructArray,
+	array::{Array, StructArray},
+	compute::concatenate::concatenate,
 	io::ipc::read::{read_stream_metadata, StreamReader, StreamState},
 };
 
 use crate::{
 	frame::{immutable::Frame, mutable::Frame as MutableFrame},
 	game::{self, immutable::Game, port_occupancy},
-	io::{expect_bytes, peppi, slippi, Result},
+	io::{expect_bytes, peppi, slippi, Error, HashingWriter, Result},
 };
 
 type JsMap = serde_json::Map<String, serde_json::Value>;
@@ -21,45 +22,95 @@ pub struct Opts {
 	/// Skip all frame data when parsing a replay for speed
 	/// (when you only need start/end/metadata).
 	pub skip_frames: bool,
+
+	/// Only read these frame fields, as dot-separated paths relative to the
+	/// frame (e.g. `ports.*.leader.post.position`), where `*` matches any one
+	/// field. Requesting a field also requests everything under it. Unrequested
+	/// fields are skipped entirely, and read as nulls. The frame `id` is always
+	/// read. Fails if any path matches no field.
+	pub projection: Option<Vec<String>>,
+
+	/// Only read frames in this range of indexes (not frame IDs). Record
+	/// batches outside the range are skipped entirely (see
+	/// [`ser::Opts::chunk_size`](super::ser::Opts::chunk_size)).
+	pub frame_range: Option<Range<usize>>,
+
+	/// Check that the replay converts back to the original `.slp` exactly
+	/// (see [`verify_hash`]). Can't be combined with options that skip frames.
+	pub verify_hash: bool,
+}
+
+fn into_frame(mut arrays: Vec<Box<dyn Array>>, version: slippi::Version) -> Result<Frame> {
+	let array = match arrays.len() {
+		0 => return Err(err!("no batches")),
+		1 => arrays.pop().unwrap(),
+		_ => concatenate(&arrays.iter().map(|a| a.as_ref()).collect::<Vec<_>>())?,
+	};
+	let array = array
+		.as_any()
+		.downcast_ref::<StructArray>()
+		.ok_or(err!("expected a struct array"))?;
+	Ok(Frame::from_struct_array(array.clone(), version))
+}
+
+fn read_arrow_frames_selected<R: Read>(
+	mut r: R,
+	version: slippi::Version,
+	opts: &Opts,
+) -> Result<Frame> {
+	let mut buf = Vec::new();
+	r.read_to_end(&mut buf)?;
+	let arrays = super::frames::read(buf, opts.projection.as_deref(), opts.frame_range.as_ref())?;
+	into_frame(arrays, version)
 }
 
 fn read_arrow_frames<R: Read>(mut r: R, version: slippi::Version) -> Result<Frame> {
 	// magic number `ARROW1\0\0`
 	expect_bytes(&mut r, &[65, 82, 82, 79, 87, 49, 0, 0])?;
 	let metadata = read_stream_metadata(&mut r)?;
+	let field = match &metadata.schema.fields[..] {
+		[f] => f.clone(),
+		_ => return Err(err!("expected exactly one field")),
+	};
 	let reader = StreamReader::new(r, metadata, None);
-	let mut frame: Option<Frame> = None;
+	let mut arrays = vec![];
 	for result in reader {
 		match result? {
-			StreamState::Some(chunk) => match frame {
-				None => {
-					let f = chunk.arrays()[0]
-						.as_any()
-						.downcast_ref::<StructArray>()
-						.expect("expected a `StructArray`");
-					frame = Some(Frame::from_struct_array(f.clone(), version))
-				}
-				Some(_) => return Err(err!("multiple batches")),
-			},
+			StreamState::Some(chunk) => arrays.push(super::encoding::decode_frames(
+				&field,
+				chunk.arrays()[0].as_ref(),
+			)?),
 			StreamState::Waiting => std::thread::sleep(std::time::Duration::from_millis(1000)),
 		}
 	}
-	match frame {
-		Some(f) => Ok(f),
-		_ => Err(err!("no batches")),
-	}
+	into_frame(arrays, version)
 }
 
-fn read_peppi_start<R: Read>(mut r: R) -> Result<game::Start> {
+/// Reads `start.raw`. If we've already read `start.json`, the raw bytes are
+/// only kept for round-tripping (see [`slippi::ser::start_bytes`]).
+fn read_peppi_start<R: Read>(mut r: R, json: Option<game::Start>) -> Result<game::Start> {
 	let mut buf = Vec::new();
 	r.read_to_end(&mut buf)?;
-	slippi::de::game_start(&mut &buf[..])
+	match json {
+		Some(mut start) => {
+			start.bytes = game::Bytes(buf);
+			Ok(start)
+		}
+		_ => slippi::de::game_start(&mut &buf[..]),
+	}
 }
 
-fn read_peppi_end<R: Read>(mut r: R) -> Result<game::End> {
+/// Reads `end.raw`, like [`read_peppi_start`].
+fn read_peppi_end<R: Read>(mut r: R, json: Option<game::End>) -> Result<game::End> {
 	let mut buf = Vec::new();
 	r.read_to_end(&mut buf)?;
-	slippi::de::game_end(&mut &buf[..])
+	match json {
+		Some(mut end) => {
+			end.bytes = game::Bytes(buf);
+			Ok(end)
+		}
+		_ => slippi::de::game_end(&mut &buf[..]),
+	}
 }
 
 fn read_peppi_metadata<R: Read>(r: R) -> Result<JsMap> {
@@ -81,14 +132,61 @@ fn read_peppi_gecko_codes<R: Read>(mut r: R) -> Result<game::GeckoCodes> {
 	})
 }
 
+fn read_peppi_unknown_events<R: Read>(mut r: R) -> Result<Vec<game::UnknownEvent>> {
+	let mut buf = Vec::new();
+	r.read_to_end(&mut buf)?;
+	let mut r = &buf[..];
+	let mut events = Vec::new();
+	while !r.is_empty() {
+		let mut header = [0; 7];
+		r.read_exact(&mut header)?;
+		let mut bytes = vec![0; u16::from_le_bytes([header[5], header[6]]) as usize];
+		r.read_exact(&mut bytes)?;
+		events.push(game::UnknownEvent {
+			code: header[0],
+			index: u32::from_le_bytes([header[1], header[2], header[3], header[4]]) as usize,
+			bytes,
+		});
+	}
+	Ok(events)
+}
+
+/// Checks that serializing `game` as a Slippi (`.slp`) replay reproduces the
+/// original, by comparing the result's hash against `game.hash`.
+///
+/// Returns [`Error::HashMismatch`] if it doesn't.
+pub fn verify_hash(game: &Game) -> Result<()> {
+	let expected = game.hash.as_ref().ok_or(err!("no hash to verify"))?;
+	let mut w = HashingWriter::new();
+	slippi::ser::write(&mut w, game)?;
+	let actual = w.into_digest();
+	match *expected == actual {
+		true => Ok(()),
+		_ => Err(Error::HashMismatch {
+			expected: expected.clone(),
+			actual,
+		}),
+	}
+}
+
 /// Reads a Peppi (`.slpp`) replay from `r`.
 pub fn read<R: Read>(r: R, opts: Option<&Opts>) -> Result<Game> {
 	let mut start: Option<game::Start> = None;
 	let mut end: Option<game::End> = None;
 	let mut metadata: Option<JsMap> = None;
 	let mut gecko_codes: Option<game::GeckoCodes> = None;
+	let mut unknown_events: Vec<game::UnknownEvent> = Vec::new();
 	let mut frames: Option<Frame> = None;
 	let mut peppi: Option<peppi::Peppi> = None;
+	// JSON errors are only fatal if there's no `.raw` to fall back on
+	let mut start_err: Option<serde_json::Error> = None;
+	let mut end_err: Option<serde_json::Error> = None;
+	let verify = opts.is_some_and(|o| o.verify_hash);
+	if verify
+		&& opts.is_some_and(|o| o.skip_frames || o.projection.is_some() || o.frame_range.is_some())
+	{
+		return Err(err!("can't verify hash without reading all frames"));
+	}
 	for entry in tar::Archive::new(r).entries()? {
 		let file = entry?;
 		let path = file.path()?;
@@ -100,10 +198,29 @@ pub fn read<R: Read>(r: R, opts: Option<&Opts>) -> Result<Game> {
 				super::assert_current_version(p.version)?;
 				peppi = Some(p);
 			}
-			Some("start.raw") => start = Some(read_peppi_start(file)?),
-			Some("end.raw") => end = Some(read_peppi_end(file)?),
+			Some("start.json") => match serde_json::from_reader::<_, game::Start>(file) {
+				Ok(mut json) => {
+					if let Some(raw) = start.take() {
+						json.bytes = raw.bytes;
+					}
+					start = Some(json);
+				}
+				Err(e) => start_err = Some(e),
+			},
+			Some("start.raw") => start = Some(read_peppi_start(file, start.take())?),
+			Some("end.json") => match serde_json::from_reader::<_, game::End>(file) {
+				Ok(mut json) => {
+					if let Some(raw) = end.take() {
+						json.bytes = raw.bytes;
+					}
+					end = Some(json);
+				}
+				Err(e) => end_err = Some(e),
+			},
+			Some("end.raw") => end = Some(read_peppi_end(file, end.take())?),
 			Some("metadata.json") => metadata = Some(read_peppi_metadata(file)?),
 			Some("gecko_codes.raw") => gecko_codes = Some(read_peppi_gecko_codes(file)?),
+			Some("unknown_events.raw") => unknown_events = read_peppi_unknown_events(file)?,
 			Some("frames.arrow") => {
 				let version = start
 					.as_ref()
@@ -115,22 +232,40 @@ pub fn read<R: Read>(r: R, opts: Option<&Opts>) -> Result<Game> {
 						MutableFrame::with_capacity(0, start.slippi.version, &port_occupancy(start))
 							.into()
 					}
-					_ => read_arrow_frames(file, version)?,
+					_ => match opts {
+						Some(o) if o.projection.is_some() || o.frame_range.is_some() => {
+							read_arrow_frames_selected(file, version, o)?
+						}
+						_ => read_arrow_frames(file, version)?,
+					},
 				});
-				break;
 			}
 			_ => debug!("=> skipping"),
 		};
 	}
 
 	let peppi = peppi.ok_or(err!("missing peppi"))?;
-	Ok(Game {
+	let start = match (start, start_err) {
+		(Some(start), _) => start,
+		(None, Some(e)) => return Err(e.into()),
+		(None, None) => return Err(err!("missing start")),
+	};
+	if let (None, Some(e)) = (&end, end_err) {
+		return Err(e.into());
+	}
+	let game = Game {
 		metadata: metadata,
-		start: start.ok_or(err!("missing start"))?,
+		start,
 		end: end,
 		gecko_codes: gecko_codes,
 		frames: frames.ok_or(err!("missing frames"))?,
 		hash: peppi.slp_hash,
 		quirks: peppi.quirks,
-	})
+		unknown_events,
+		warnings: Vec::new(),
+	};
+	if verify {
+		verify_hash(&game)?;
+	}
+	Ok(game)
 }
@@ -0,0 +1,291 @@
+//! Optional encodings for frame fields in `frames.arrow` (see
+//! [`ser::Opts::dictionary`](super::ser::Opts::dictionary) &
+//! [`ser::Opts::delta`](super::ser::Opts::delta)).
+//!
+//! Dictionary-encoded fields use Arrow's own dictionary type. Delta-encoded
+//! fields keep their type, and are marked in their field metadata. Either way,
+//! readers must [`decode_frames`] before converting them.
+
+use std::{collections::HashMap, hash::Hash};
+
+use arrow2::{
+	array::{Array, DictionaryArray, DictionaryKey, PrimitiveArray, StructArray},
+	compute::take::take,
+	datatypes::{DataType, Field, IntegerType, PhysicalType},
+	types::{Index, NativeType},
+};
+
+use crate::io::{
+	flat::{select, Selection},
+	Result,
+};
+
+/// Field metadata key for encodings that Arrow doesn't know about.
+const ENCODING_KEY: &str = "peppi.encoding";
+
+const DELTA: &str = "delta";
+
+/// Calls `$f` with `$array` downcast to its concrete type, if it's an integer
+/// array. Evaluates to `None` otherwise.
+macro_rules! with_integer_array {
+	($array: expr, $f: ident) => {{
+		use arrow2::datatypes::PrimitiveType::*;
+		let array = $array;
+		let any = array.as_any();
+		match array.data_type().to_physical_type() {
+			PhysicalType::Primitive(Int8) => Some($f::<i8>(any.downcast_ref().unwrap())),
+			PhysicalType::Primitive(Int16) => Some($f::<i16>(any.downcast_ref().unwrap())),
+			PhysicalType::Primitive(Int32) => Some($f::<i32>(any.downcast_ref().unwrap())),
+			PhysicalType::Primitive(Int64) => Some($f::<i64>(any.downcast_ref().unwrap())),
+			PhysicalType::Primitive(UInt8) => Some($f::<u8>(any.downcast_ref().unwrap())),
+			PhysicalType::Primitive(UInt16) => Some($f::<u16>(any.downcast_ref().unwrap())),
+			PhysicalType::Primitive(UInt32) => Some($f::<u32>(any.downcast_ref().unwrap())),
+			PhysicalType::Primitive(UInt64) => Some($f::<u64>(any.downcast_ref().unwrap())),
+			_ => None,
+		}
+	}};
+}
+
+/// Integer types we can encode.
+trait Integer: NativeType + Hash + Eq {
+	fn sub(self, rhs: Self) -> Self;
+	fn add(self, rhs: Self) -> Self;
+}
+
+macro_rules! impl_integer {
+	($($t: ty),*) => {
+		$(impl Integer for $t {
+			fn sub(self, rhs: Self) -> Self {
+				self.wrapping_sub(rhs)
+			}
+			fn add(self, rhs: Self) -> Self {
+				self.wrapping_add(rhs)
+			}
+		})*
+	};
+}
+
+impl_integer!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+/// Rebuilds `array`, replacing each non-struct field matched by `patterns`
+/// with the result of `f` (if any). Sets `matched[i]` if `patterns[i]`
+/// matched any field.
+fn map_fields<'a, F>(
+	array: &'a StructArray,
+	patterns: &[Vec<&str>],
+	path: &mut Vec<&'a str>,
+	matched: &mut [bool],
+	f: &mut F,
+) -> Result<StructArray>
+where
+	F: FnMut(&Field, &dyn Array) -> Result<Option<(Field, Box<dyn Array>)>>,
+{
+	let mut fields = vec![];
+	let mut values = vec![];
+	for (field, value) in array.fields().iter().zip(array.values()) {
+		path.push(&field.name);
+		let mapped = match value.as_any().downcast_ref::<StructArray>() {
+			Some(s) => {
+				let s = map_fields(s, patterns, path, matched, f)?;
+				let field = Field {
+					data_type: s.data_type().clone(),
+					..field.clone()
+				};
+				Some((field, s.boxed()))
+			}
+			None if select(path, patterns) == Selection::Full => {
+				for (m, p) in matched.iter_mut().zip(patterns) {
+					*m |= select(path, std::slice::from_ref(p)) == Selection::Full;
+				}
+				f(field, value.as_ref())?
+			}
+			None => None,
+		};
+		path.pop();
+		let (field, value) = mapped.unwrap_or_else(|| (field.clone(), value.clone()));
+		fields.push(field);
+		values.push(value);
+	}
+	Ok(StructArray::new(
+		DataType::Struct(fields),
+		values,
+		array.validity().cloned(),
+	))
+}
+
+/// Like [`map_fields`], but fails if any of `patterns` matches no field.
+fn map_matched_fields<F>(
+	array: &StructArray,
+	patterns: &[Vec<&str>],
+	f: &mut F,
+) -> Result<StructArray>
+where
+	F: FnMut(&Field, &dyn Array) -> Result<Option<(Field, Box<dyn Array>)>>,
+{
+	let mut matched = vec![false; patterns.len()];
+	let array = map_fields(array, patterns, &mut vec![], &mut matched, f)?;
+	match matched.iter().position(|m| !m) {
+		Some(i) => Err(err!("no such field: {}", patterns[i].join("."))),
+		None => Ok(array),
+	}
+}
+
+fn dictionary_keys<K: DictionaryKey>(
+	indexes: Vec<Option<usize>>,
+	values: Box<dyn Array>,
+) -> Result<Box<dyn Array>> {
+	let keys: Vec<Option<K>> = indexes
+		.into_iter()
+		.map(|i| i.map(|i| K::try_from(i).ok().unwrap()))
+		.collect();
+	Ok(DictionaryArray::try_from_keys(PrimitiveArray::from(keys), values)?.boxed())
+}
+
+fn dictionary_encode<T: Integer>(array: &PrimitiveArray<T>) -> Result<Box<dyn Array>> {
+	let mut index = HashMap::new();
+	let mut values = vec![];
+	let indexes: Vec<_> = array
+		.iter()
+		.map(|v| {
+			v.map(|v| {
+				*index.entry(*v).or_insert_with(|| {
+					values.push(*v);
+					values.len() - 1
+				})
+			})
+		})
+		.collect();
+	let values = PrimitiveArray::new(array.data_type().clone(), values.into(), None).boxed();
+	// use the smallest keys that will fit
+	match index.len() {
+		n if n <= 1 << 8 => dictionary_keys::<u8>(indexes, values),
+		n if n <= 1 << 16 => dictionary_keys::<u16>(indexes, values),
+		_ => dictionary_keys::<u32>(indexes, values),
+	}
+}
+
+fn dictionary_decode<K: DictionaryKey + Index>(array: &dyn Array) -> Result<Box<dyn Array>> {
+	let array = array.as_any().downcast_ref::<DictionaryArray<K>>().unwrap();
+	Ok(take(array.values().as_ref(), array.keys())?)
+}
+
+fn delta_encode<T: Integer>(array: &PrimitiveArray<T>) -> Result<Box<dyn Array>> {
+	let mut prev = T::default();
+	let values: Vec<T> = array
+		.values()
+		.iter()
+		.map(|&v| {
+			let d = v.sub(prev);
+			prev = v;
+			d
+		})
+		.collect();
+	Ok(PrimitiveArray::new(
+		array.data_type().clone(),
+		values.into(),
+		array.validity().cloned(),
+	)
+	.boxed())
+}
+
+fn delta_decode<T: Integer>(array: &PrimitiveArray<T>) -> Result<Box<dyn Array>> {
+	let mut prev = T::default();
+	let values: Vec<T> = array
+		.values()
+		.iter()
+		.map(|&d| {
+			prev = prev.add(d);
+			prev
+		})
+		.collect();
+	Ok(PrimitiveArray::new(
+		array.data_type().clone(),
+		values.into(),
+		array.validity().cloned(),
+	)
+	.boxed())
+}
+
+/// Dictionary-encodes the integer fields of `array` matched by `patterns`.
+/// Fails if any pattern matches no field.
+pub(super) fn dictionary(array: &StructArray, patterns: &[Vec<&str>]) -> Result<StructArray> {
+	map_matched_fields(array, patterns, &mut |field, array| {
+		let encoded = with_integer_array!(array, dictionary_encode).transpose()?;
+		Ok(encoded.map(|a| {
+			let field = Field {
+				data_type: a.data_type().clone(),
+				..field.clone()
+			};
+			(field, a)
+		}))
+	})
+}
+
+/// Delta-encodes the integer fields of `array` matched by `patterns`. The
+/// first value of each field is stored as-is, so each batch can be decoded
+/// independently. Fails if any pattern matches no field.
+pub(super) fn delta(array: &StructArray, patterns: &[Vec<&str>]) -> Result<StructArray> {
+	map_matched_fields(array, patterns, &mut |field, array| {
+		let encoded = with_integer_array!(array, delta_encode).transpose()?;
+		Ok(encoded.map(|a| {
+			let mut field = field.clone();
+			field
+				.metadata
+				.insert(ENCODING_KEY.to_string(), DELTA.to_string());
+			(field, a)
+		}))
+	})
+}
+
+/// Undoes any encodings applied by [`dictionary`] or [`delta`] to the frames
+/// struct array `array`, whose (top-level) field is `field`.
+///
+/// Fails on encodings we don't know, rather than returning wrong values.
+pub(super) fn decode_frames(field: &Field, array: &dyn Array) -> Result<Box<dyn Array>> {
+	if let Some(e) = field.metadata.get(ENCODING_KEY) {
+		return Err(err!("unknown encoding for field {}: {}", field.name, e));
+	}
+	match array.as_any().downcast_ref::<StructArray>() {
+		Some(a) => decode(a),
+		None => Err(err!(
+			"expected a struct array, got: {:?}",
+			array.data_type()
+		)),
+	}
+}
+
+fn decode(array: &StructArray) -> Result<Box<dyn Array>> {
+	let mut fields = vec![];
+	let mut values = vec![];
+	for (field, value) in array.fields().iter().zip(array.values()) {
+		let mut value = match value.data_type() {
+			DataType::Dictionary(IntegerType::UInt8, _, _) => {
+				dictionary_decode::<u8>(value.as_ref())?
+			}
+			DataType::Dictionary(IntegerType::UInt16, _, _) => {
+				dictionary_decode::<u16>(value.as_ref())?
+			}
+			DataType::Dictionary(IntegerType::UInt32, _, _) => {
+				dictionary_decode::<u32>(value.as_ref())?
+			}
+			DataType::Dictionary(k, _, _) => {
+				return Err(err!("unsupported dictionary key type: {:?}", k))
+			}
+			DataType::Struct(_) => decode(value.as_any().downcast_ref().unwrap())?,
+			_ => value.clone(),
+		};
+		let mut field = field.clone();
+		match field.metadata.remove(ENCODING_KEY).as_deref() {
+			Some(DELTA) => {
+				value = with_integer_array!(value.as_ref(), delta_decode)
+					.ok_or(err!("can't delta-decode field: {}", field.name))??;
+			}
+			Some(e) => return Err(err!("unknown encoding for field {}: {}", field.name, e)),
+			None => {}
+		}
+		field.data_type = value.data_type().clone();
+		fields.push(field);
+		values.push(value);
+	}
+	Ok(StructArray::new(DataType::Struct(fields), values, array.validity().cloned()).boxed())
+}
@@ -0,0 +1,394 @@
+//! Reading a subset of the frames & columns in `frames.arrow`.
+//!
+//! Arrow's own projection only works on top-level fields, and all of our frame
+//! data lives under a single top-level struct. So we rewrite each record batch
+//! message to drop the nodes & buffers of unrequested fields (whose bytes are
+//! then never read or decompressed), read the result against a pruned schema,
+//! and finally pad it back out to the full schema with nulls.
+//!
+//! Record batches entirely outside the requested range of frames are skipped
+//! without being decoded.
+
+use std::{io::Cursor, ops::Range};
+
+use arrow2::{
+	array::{new_empty_array, new_null_array, Array, ListArray, StructArray},
+	datatypes::{DataType, Field, PhysicalType, Schema},
+	io::ipc::{
+		read::{read_batch, read_file_dictionaries, read_file_metadata, FileMetadata},
+		IpcField,
+	},
+};
+use arrow_format::ipc::{
+	planus::{self, ReadAsRoot},
+	Block, Buffer, FieldNode, Message, MessageHeader, MessageRef,
+};
+
+use super::encoding;
+use crate::io::{
+	flat::{patterns, select, Selection},
+	Result,
+};
+
+/// Fields we always read, regardless of the projection.
+const REQUIRED: [&str; 1] = ["id"];
+
+const CONTINUATION_MARKER: [u8; 4] = [0xff; 4];
+
+/// Number of buffers Arrow's IPC format uses for a node of type `data_type`,
+/// not counting children.
+fn buffer_count(data_type: &DataType) -> Result<usize> {
+	use DataType::*;
+	Ok(match data_type {
+		Null => 0,
+		Struct(_) => 1,
+		// a dictionary's values are in a separate dictionary batch
+		Boolean | List(_) | FixedSizeBinary(_) | Dictionary(..) => 2,
+		Binary | Utf8 => 3,
+		t if matches!(t.to_physical_type(), PhysicalType::Primitive(_)) => 2,
+		t => return Err(err!("unsupported type in projection: {:?}", t)),
+	})
+}
+
+fn children(data_type: &DataType) -> &[Field] {
+	match data_type {
+		DataType::Struct(fields) => fields,
+		DataType::List(field) => std::slice::from_ref(field.as_ref()),
+		_ => &[],
+	}
+}
+
+/// Sets `matched[i]` if `patterns[i]` matches any field under `data_type`.
+fn find_matches<'a>(
+	data_type: &'a DataType,
+	patterns: &[Vec<&str>],
+	path: &mut Vec<&'a str>,
+	matched: &mut [bool],
+) {
+	// list items don't add a path segment
+	let named = matches!(data_type, DataType::Struct(_));
+	for child in children(data_type) {
+		if named {
+			path.push(&child.name);
+			for (m, p) in matched.iter_mut().zip(patterns) {
+				*m |= select(path, std::slice::from_ref(p)) == Selection::Full;
+			}
+		}
+		find_matches(&child.data_type, patterns, path, matched);
+		if named {
+			path.pop();
+		}
+	}
+}
+
+/// Walks the original nodes & buffers in schema order, keeping those of the
+/// selected fields.
+struct Pruner<'a, I, J> {
+	patterns: &'a [Vec<&'a str>],
+	nodes: I,
+	buffers: J,
+	kept_nodes: Vec<FieldNode>,
+	kept_buffers: Vec<Buffer>,
+}
+
+impl<'a, I: Iterator<Item = FieldNode>, J: Iterator<Item = Buffer>> Pruner<'a, I, J> {
+	fn take(&mut self, data_type: &DataType, keep: bool) -> Result<()> {
+		let node = self.nodes.next().ok_or(err!("missing field node"))?;
+		if keep {
+			self.kept_nodes.push(node);
+		}
+		for _ in 0..buffer_count(data_type)? {
+			let buffer = self.buffers.next().ok_or(err!("missing buffer"))?;
+			if keep {
+				self.kept_buffers.push(buffer);
+			}
+		}
+		Ok(())
+	}
+
+	/// Takes the node for `data_type` and all its descendants.
+	fn take_all(&mut self, data_type: &DataType, keep: bool) -> Result<()> {
+		self.take(data_type, keep)?;
+		for child in children(data_type) {
+			self.take_all(&child.data_type, keep)?;
+		}
+		Ok(())
+	}
+
+	/// Returns the pruned type & IPC field (which tells Arrow where to find
+	/// dictionaries), or `None` if nothing under `path` was selected.
+	fn prune<'p>(
+		&mut self,
+		data_type: &'p DataType,
+		ipc_field: &IpcField,
+		path: &mut Vec<&'p str>,
+	) -> Result<Option<(DataType, IpcField)>> {
+		match (select(path, self.patterns), data_type) {
+			(Selection::Full, _) => {
+				self.take_all(data_type, true)?;
+				Ok(Some((data_type.clone(), ipc_field.clone())))
+			}
+			(Selection::Partial, DataType::Struct(fields)) => {
+				let (nodes, buffers) = (self.kept_nodes.len(), self.kept_buffers.len());
+				self.take(data_type, true)?;
+				let mut kept = vec![];
+				let mut kept_ipc = vec![];
+				for (f, ipc) in fields.iter().zip(&ipc_field.fields) {
+					path.push(&f.name);
+					let child = self.prune(&f.data_type, ipc, path)?;
+					path.pop();
+					if let Some((t, ipc)) = child {
+						kept.push(Field::new(f.name.clone(), t, f.is_nullable));
+						kept_ipc.push(ipc);
+					}
+				}
+				if kept.is_empty() {
+					self.kept_nodes.truncate(nodes);
+					self.kept_buffers.truncate(buffers);
+					Ok(None)
+				} else {
+					let ipc_field = IpcField {
+						fields: kept_ipc,
+						dictionary_id: None,
+					};
+					Ok(Some((DataType::Struct(kept), ipc_field)))
+				}
+			}
+			(Selection::Partial, DataType::List(f)) => {
+				let (nodes, buffers) = (self.kept_nodes.len(), self.kept_buffers.len());
+				self.take(data_type, true)?;
+				let ipc = ipc_field.fields.first().ok_or(err!("missing IPC field"))?;
+				// list items don't add a path segment
+				match self.prune(&f.data_type, ipc, path)? {
+					Some((t, ipc)) => {
+						let data_type =
+							DataType::List(Box::new(Field::new(f.name.clone(), t, f.is_nullable)));
+						let ipc_field = IpcField {
+							fields: vec![ipc],
+							dictionary_id: None,
+						};
+						Ok(Some((data_type, ipc_field)))
+					}
+					None => {
+						self.kept_nodes.truncate(nodes);
+						self.kept_buffers.truncate(buffers);
+						Ok(None)
+					}
+				}
+			}
+			_ => {
+				self.take_all(data_type, false)?;
+				Ok(None)
+			}
+		}
+	}
+}
+
+/// Rebuilds `array` (of a pruned type) as `data_type`, filling in the
+/// missing fields with nulls.
+fn unprune(array: Option<&dyn Array>, data_type: &DataType, len: usize) -> Box<dyn Array> {
+	let array = match array {
+		Some(a) => a,
+		None => return new_null_array(data_type.clone(), len),
+	};
+	match data_type {
+		DataType::Struct(fields) => {
+			let array = array.as_any().downcast_ref::<StructArray>().unwrap();
+			let values = fields
+				.iter()
+				.map(|f| {
+					let child = array
+						.fields()
+						.iter()
+						.position(|c| c.name == f.name)
+						.map(|i| array.values()[i].as_ref());
+					unprune(child, &f.data_type, array.len())
+				})
+				.collect();
+			StructArray::new(data_type.clone(), values, array.validity().cloned()).boxed()
+		}
+		DataType::List(f) => {
+			let array = array.as_any().downcast_ref::<ListArray<i32>>().unwrap();
+			let values = unprune(
+				Some(array.values().as_ref()),
+				&f.data_type,
+				array.values().len(),
+			);
+			ListArray::new(
+				data_type.clone(),
+				array.offsets().clone(),
+				values,
+				array.validity().cloned(),
+			)
+			.boxed()
+		}
+		_ => array.to_boxed(),
+	}
+}
+
+/// A record batch message, and where we found it.
+struct BatchMessage {
+	offset: usize,
+	meta_len: usize,
+	message: Message,
+}
+
+impl BatchMessage {
+	fn read(buf: &[u8], block: &Block) -> Result<Self> {
+		let offset = usize::try_from(block.offset).map_err(|_| err!("invalid block offset"))?;
+		let meta_len =
+			usize::try_from(block.meta_data_length).map_err(|_| err!("invalid metadata length"))?;
+		let region = buf
+			.get(offset..offset + meta_len)
+			.ok_or(err!("block out of bounds"))?;
+		let prefix_len = match region.starts_with(&CONTINUATION_MARKER) {
+			true => 8,
+			_ => 4,
+		};
+		let message = region
+			.get(prefix_len..)
+			.ok_or(err!("block out of bounds"))
+			.and_then(|m| {
+				MessageRef::read_as_root(m)
+					.and_then(Message::try_from)
+					.map_err(|e| err!("invalid message: {}", e))
+			})?;
+		match message.header {
+			Some(MessageHeader::RecordBatch(_)) => Ok(Self {
+				offset,
+				meta_len,
+				message,
+			}),
+			_ => Err(err!("expected a record batch")),
+		}
+	}
+
+	fn batch(&mut self) -> &mut arrow_format::ipc::RecordBatch {
+		match self.message.header {
+			Some(MessageHeader::RecordBatch(ref mut b)) => b,
+			_ => unreachable!(),
+		}
+	}
+
+	/// Drops the nodes & buffers of fields not matched by `patterns`, returning
+	/// the pruned type & IPC field.
+	fn prune(
+		&mut self,
+		data_type: &DataType,
+		ipc_field: &IpcField,
+		patterns: &[Vec<&str>],
+	) -> Result<(DataType, IpcField)> {
+		let batch = self.batch();
+		let mut pruner = Pruner {
+			patterns,
+			nodes: batch.nodes.take().unwrap_or_default().into_iter(),
+			buffers: batch.buffers.take().unwrap_or_default().into_iter(),
+			kept_nodes: vec![],
+			kept_buffers: vec![],
+		};
+		let pruned = pruner
+			.prune(data_type, ipc_field, &mut vec![])?
+			.ok_or(err!("nothing selected"))?;
+		batch.nodes = Some(pruner.kept_nodes);
+		batch.buffers = Some(pruner.kept_buffers);
+		Ok(pruned)
+	}
+
+	/// Overwrites the original message with this (smaller, pruned) one. The
+	/// body stays where it is, so the block's offsets remain valid.
+	fn write(&self, buf: &mut [u8]) -> Result<()> {
+		let mut builder = planus::Builder::new();
+		let bytes = builder.finish(&self.message, None);
+		if bytes.len() > self.meta_len.saturating_sub(8) {
+			return Err(err!("pruned message too large"));
+		}
+		let region = &mut buf[self.offset..self.offset + self.meta_len];
+		region[..4].copy_from_slice(&CONTINUATION_MARKER);
+		region[4..8].copy_from_slice(&((self.meta_len - 8) as i32).to_le_bytes());
+		region[8..8 + bytes.len()].copy_from_slice(bytes);
+		region[8 + bytes.len()..].fill(0);
+		Ok(())
+	}
+}
+
+/// Reads the frames struct arrays from the Arrow IPC file `buf`, one per
+/// record batch that overlaps `range` (sliced to fit). Skips the buffers of
+/// any fields not matched by `projection`, and fails if any of its paths
+/// matches no field. The arrays are [decoded](encoding::decode_frames).
+pub(super) fn read(
+	mut buf: Vec<u8>,
+	projection: Option<&[String]>,
+	range: Option<&Range<usize>>,
+) -> Result<Vec<Box<dyn Array>>> {
+	let metadata = read_file_metadata(&mut Cursor::new(&buf))?;
+	let field = match &metadata.schema.fields[..] {
+		[f] => f.clone(),
+		_ => return Err(err!("expected exactly one field")),
+	};
+	let ipc_field = metadata.ipc_schema.fields[0].clone();
+	if let Some(projection) = projection {
+		let patterns = patterns(projection.iter().map(String::as_str));
+		let mut matched = vec![false; patterns.len()];
+		find_matches(&field.data_type, &patterns, &mut vec![], &mut matched);
+		if let Some(i) = matched.iter().position(|m| !m) {
+			return Err(err!("no such field: {}", projection[i]));
+		}
+	}
+	let patterns = projection.map(|p| patterns(p.iter().map(String::as_str).chain(REQUIRED)));
+	let dictionaries = read_file_dictionaries(&mut Cursor::new(&buf), &metadata, &mut vec![])?;
+
+	let mut pruned_metadata: Option<FileMetadata> = None;
+	let mut arrays = vec![];
+	let mut batch_start = 0;
+	for (index, block) in metadata.blocks.iter().enumerate() {
+		let mut message = BatchMessage::read(&buf, block)?;
+		let len =
+			usize::try_from(message.batch().length).map_err(|_| err!("invalid batch length"))?;
+		let (start, end) = match range {
+			Some(r) => (r.start.max(batch_start), r.end.min(batch_start + len)),
+			None => (batch_start, batch_start + len),
+		};
+		let offset = batch_start;
+		batch_start += len;
+		if range.is_some() && start >= end {
+			continue;
+		}
+
+		if let Some(patterns) = &patterns {
+			let (data_type, ipc_field) = message.prune(&field.data_type, &ipc_field, patterns)?;
+			message.write(&mut buf)?;
+			if pruned_metadata.is_none() {
+				let fields = vec![Field::new(field.name.clone(), data_type, field.is_nullable)];
+				let mut m = metadata.clone();
+				m.schema = Schema::from(fields).with_metadata(m.schema.metadata);
+				m.ipc_schema.fields = vec![ipc_field];
+				pruned_metadata = Some(m);
+			}
+		}
+
+		let chunk = read_batch(
+			&mut Cursor::new(&buf),
+			&dictionaries,
+			pruned_metadata.as_ref().unwrap_or(&metadata),
+			None,
+			None,
+			index,
+			&mut vec![],
+			&mut vec![],
+		)?;
+		let array = chunk.arrays()[0].as_ref();
+		let array = match patterns {
+			Some(_) => unprune(Some(array), &field.data_type, array.len()),
+			None => array.to_boxed(),
+		};
+		// must decode whole batches, since delta encoding restarts with each
+		let array = encoding::decode_frames(&field, array.as_ref())?;
+		arrays.push(array.sliced(start - offset, end - start));
+	}
+
+	if arrays.is_empty() {
+		let array = new_empty_array(field.data_type.clone());
+		arrays.push(encoding::decode_frames(&field, array.as_ref())?);
+	}
+	Ok(arrays)
+}
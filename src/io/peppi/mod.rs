@@ -1,6 +1,8 @@
 //! Peppi (`.slpp`) serialization.
 
 pub mod de;
+mod encoding;
+mod frames;
 pub mod ser;
 
 use serde::{Deserialize, Serialize};
@@ -15,7 +17,11 @@ pub use de::read;
 pub use ser::write;
 
 /// Current version of the Peppi format.
-pub const CURRENT_VERSION: Version = Version(2, 0, 0);
+///
+/// Changes since 2.0.0:
+/// - 2.1.0: `frames.arrow` may come before other files (see [`ser::StreamWriter`]),
+///   and its fields may be dictionary- or delta-encoded (see [`ser::Opts`]).
+pub const CURRENT_VERSION: Version = Version(2, 1, 0);
 
 /// Minimum supported version of the Peppi format for reading.
 pub const MIN_VERSION: Version = Version(2, 0, 0);
@@ -1,15 +1,22 @@
-use std::{error::Error, io::Write, path::Path};
+use std::{
+	borrow::Cow,
+	error::Error,
+	io::{Seek, SeekFrom, Write},
+	path::Path,
+};
 
 use arrow2::{
-	array::Array,
+	array::{Array, StructArray},
 	chunk::Chunk,
-	datatypes::{Field, Schema},
+	datatypes::{DataType, Field, Schema},
 	io::ipc::write::{Compression, FileWriter, WriteOptions},
 };
 
+use super::encoding;
 use crate::{
-	game::{immutable::Game, port_occupancy},
-	io::{peppi, slippi},
+	frame::{immutable::Frame, mutable::Frame as MutableFrame, PortOccupancy},
+	game::{self, immutable::Game, port_occupancy, GeckoCodes, UnknownEvent},
+	io::{flat::patterns, peppi, slippi},
 };
 
 /// Options for writing Peppi files.
@@ -20,6 +27,40 @@ pub struct Opts {
 	/// Use this to maximize read speed while saving some disk space (e.g. for machine learning).
 	/// If you just want maximum compression, compress the entire `.slpp` file instead.
 	pub compression: Option<Compression>,
+
+	/// Frame fields to store as dictionaries (values plus indexes into them),
+	/// which saves space for integer fields with few distinct values, like
+	/// `ports.*.leader.post.state` or `ports.*.leader.post.character`. Paths are
+	/// as in [`de::Opts::projection`](super::de::Opts::projection), and writing
+	/// fails if any path matches no field.
+	///
+	/// Not supported by [`StreamWriter`], since Arrow files can't change
+	/// dictionaries between record batches.
+	pub dictionary: Vec<String>,
+
+	/// Frame fields to store as differences between consecutive values, which
+	/// compresses better for integer fields that change steadily, like `id` or
+	/// `start.scene_frame_counter`. Paths are as in
+	/// [`de::Opts::projection`](super::de::Opts::projection), and writing fails
+	/// if any path matches no field.
+	pub delta: Vec<String>,
+
+	/// Maximum number of frames per Arrow record batch. If `None`, all frames
+	/// go in a single batch.
+	///
+	/// Smaller batches let readers skip the frames they don't need (see
+	/// [`de::Opts::frame_range`](super::de::Opts::frame_range)), at some cost in
+	/// size.
+	pub chunk_size: Option<usize>,
+}
+
+fn header<P: AsRef<Path>>(size: usize, path: P) -> Result<tar::Header, Box<dyn Error>> {
+	let mut header = tar::Header::new_gnu();
+	header.set_size(size.try_into()?);
+	header.set_path(path)?;
+	header.set_mode(0o644);
+	header.set_cksum();
+	Ok(header)
 }
 
 fn tar_append<W: Write, P: AsRef<Path>>(
@@ -27,21 +68,108 @@ fn tar_append<W: Write, P: AsRef<Path>>(
 	buf: &[u8],
 	path: P,
 ) -> Result<(), Box<dyn Error>> {
-	let mut header = tar::Header::new_gnu();
-	header.set_size(buf.len().try_into()?);
-	header.set_path(path)?;
-	header.set_mode(0o644);
-	header.set_cksum();
-	builder.append(&header, buf)?;
+	builder.append(&header(buf.len(), path)?, buf)?;
+	Ok(())
+}
+
+/// Size of a tar block. Entries (headers & contents) are padded to a multiple of this.
+const BLOCK_SIZE: usize = 512;
+
+/// Like [`tar_append`], for when we need to know where things are in `w`.
+fn raw_append<W: Write, P: AsRef<Path>>(
+	w: &mut W,
+	buf: &[u8],
+	path: P,
+) -> Result<(), Box<dyn Error>> {
+	w.write_all(header(buf.len(), path)?.as_bytes())?;
+	w.write_all(buf)?;
+	pad(w, buf.len())
+}
+
+fn pad<W: Write>(w: &mut W, size: usize) -> Result<(), Box<dyn Error>> {
+	let remainder = size % BLOCK_SIZE;
+	if remainder > 0 {
+		w.write_all(&[0; BLOCK_SIZE][remainder..])?;
+	}
+	Ok(())
+}
+
+/// Raw Game Start payload, regenerated from `start`'s fields if we don't
+/// have one (e.g. for a game read from JSON).
+fn start_raw(start: &game::Start) -> crate::io::Result<Cow<'_, [u8]>> {
+	match start.bytes.0.is_empty() {
+		true => Ok(Cow::Owned(slippi::ser::start_bytes(start)?)),
+		_ => Ok(Cow::Borrowed(&start.bytes.0)),
+	}
+}
+
+/// Raw Game End payload, like [`start_raw`].
+fn end_raw(end: &game::End, version: slippi::Version) -> crate::io::Result<Cow<'_, [u8]>> {
+	match end.bytes.0.is_empty() {
+		true => Ok(Cow::Owned(slippi::ser::end_bytes(end, version)?)),
+		_ => Ok(Cow::Borrowed(&end.bytes.0)),
+	}
+}
+
+fn gecko_codes_bytes(gecko_codes: &GeckoCodes) -> Vec<u8> {
+	let mut buf = gecko_codes.actual_size.to_le_bytes().to_vec();
+	buf.extend_from_slice(&gecko_codes.bytes);
+	buf
+}
+
+fn unknown_events_bytes(events: &[UnknownEvent]) -> Result<Vec<u8>, Box<dyn Error>> {
+	let mut buf = Vec::new();
+	for e in events {
+		buf.push(e.code);
+		buf.write_all(&u32::try_from(e.index)?.to_le_bytes())?;
+		buf.write_all(&u16::try_from(e.bytes.len())?.to_le_bytes())?;
+		buf.write_all(&e.bytes)?;
+	}
+	Ok(buf)
+}
+
+fn frames_schema(data_type: DataType) -> Schema {
+	Schema::from(vec![Field {
+		name: "frame".to_string(),
+		data_type,
+		is_nullable: false,
+		metadata: Default::default(),
+	}])
+}
+
+/// Delta-encodes the fields of `batch` requested in `opts` (see [`Opts::delta`]).
+fn delta(batch: &StructArray, opts: &Opts) -> Result<StructArray, Box<dyn Error>> {
+	match opts.delta.is_empty() {
+		true => Ok(batch.clone()),
+		_ => Ok(encoding::delta(
+			batch,
+			&patterns(opts.delta.iter().map(String::as_str)),
+		)?),
+	}
+}
+
+/// Writes `batch` as record batches of at most `opts.chunk_size` frames.
+fn write_batches<W: Write>(
+	writer: &mut FileWriter<W>,
+	batch: &StructArray,
+	opts: &Opts,
+) -> Result<(), Box<dyn Error>> {
+	let len = batch.len();
+	let chunk_size = match opts.chunk_size {
+		Some(0) => return Err("chunk size must be positive".into()),
+		Some(n) => n,
+		None => len,
+	};
+	for start in (0..len).step_by(chunk_size.max(1)) {
+		let chunk = batch.clone().sliced(start, chunk_size.min(len - start));
+		// delta-encode each chunk separately, so they can be read separately
+		writer.write(&Chunk::new(vec![delta(&chunk, opts)?.boxed()]), None)?;
+	}
 	Ok(())
 }
 
 /// Writes a replay to `w` in Peppi (`.slpp`) format.
-///
-/// Returns an error if the game's version is higher than `MAX_SUPPORTED_VERSION`.
 pub fn write<W: Write>(w: W, game: Game, opts: Option<&Opts>) -> Result<(), Box<dyn Error>> {
-	slippi::assert_max_version(game.start.slippi.version)?;
-
 	let mut tar = tar::Builder::new(w);
 	tar_append(
 		&mut tar,
@@ -58,41 +186,54 @@ pub fn write<W: Write>(w: W, game: Game, opts: Option<&Opts>) -> Result<(), Box<
 		"metadata.json",
 	)?;
 	tar_append(&mut tar, &serde_json::to_vec(&game.start)?, "start.json")?;
-	tar_append(&mut tar, &game.start.bytes.0, "start.raw")?;
+	tar_append(&mut tar, &start_raw(&game.start)?, "start.raw")?;
 	if let Some(end) = &game.end {
 		tar_append(&mut tar, &serde_json::to_vec(end)?, "end.json")?;
-		tar_append(&mut tar, &end.bytes.0, "end.raw")?;
+		tar_append(
+			&mut tar,
+			&end_raw(end, game.start.slippi.version)?,
+			"end.raw",
+		)?;
 	}
 
 	if let Some(gecko_codes) = &game.gecko_codes {
-		let mut buf = gecko_codes.actual_size.to_le_bytes().to_vec();
-		buf.write_all(&gecko_codes.bytes)?;
-		tar_append(&mut tar, &buf, "gecko_codes.raw")?;
+		tar_append(&mut tar, &gecko_codes_bytes(gecko_codes), "gecko_codes.raw")?;
+	}
+
+	if !game.unknown_events.is_empty() {
+		tar_append(
+			&mut tar,
+			&unknown_events_bytes(&game.unknown_events)?,
+			"unknown_events.raw",
+		)?;
 	}
 
 	if game.frames.id.len() > 0 {
+		let opts = opts.cloned().unwrap_or_default();
 		let ports = port_occupancy(&game.start);
-		let batch = game
+		let mut batch = game
 			.frames
 			.into_struct_array(game.start.slippi.version, &ports);
-		let schema = Schema::from(vec![Field {
-			name: "frame".to_string(),
-			data_type: batch.data_type().clone(),
-			is_nullable: false,
-			metadata: Default::default(),
-		}]);
-
-		let chunk = Chunk::new(vec![Box::new(batch) as Box<dyn Array>]);
+		if !opts.dictionary.is_empty() {
+			// encode all frames at once, so every chunk shares the same dictionaries
+			batch = encoding::dictionary(
+				&batch,
+				&patterns(opts.dictionary.iter().map(String::as_str)),
+			)?;
+		}
+		let data_type = delta(&StructArray::new_empty(batch.data_type().clone()), &opts)?
+			.data_type()
+			.clone();
 		let mut buf = Vec::new();
 		let mut writer = FileWriter::try_new(
 			&mut buf,
-			schema,
+			frames_schema(data_type),
 			None,
 			WriteOptions {
-				compression: opts.map_or(None, |o| o.compression),
+				compression: opts.compression,
 			},
 		)?;
-		writer.write(&chunk, None)?;
+		write_batches(&mut writer, &batch, &opts)?;
 		writer.finish()?;
 		tar_append(&mut tar, &buf, "frames.arrow")?;
 	}
@@ -100,3 +241,196 @@ pub fn write<W: Write>(w: W, game: Game, opts: Option<&Opts>) -> Result<(), Box<
 	tar.into_inner()?.flush()?;
 	Ok(())
 }
+
+/// Space reserved for `peppi.json`, which we can only write once the game is over.
+const PEPPI_JSON_SIZE: usize = BLOCK_SIZE;
+
+enum Sink<W: Write> {
+	/// Before any frames.
+	Tar(W),
+	/// Writing `frames.arrow`, whose frames have type `DataType` (before
+	/// encoding).
+	Arrow(Box<FileWriter<W>>, DataType),
+	/// A previous write failed.
+	Poisoned,
+}
+
+/// Writes a replay in Peppi (`.slpp`) format incrementally, while the game is
+/// still in progress.
+///
+/// Frames are appended to `frames.arrow` as Arrow record batches as they
+/// arrive (see [`StreamWriter::write_frames`]). Everything else that's only
+/// known once the game is over is written by [`StreamWriter::finish`], after
+/// `frames.arrow` (so Peppi versions before 2.1.0 can't read it fully). Since
+/// the size of `frames.arrow` isn't known in advance, `w` must be seekable.
+///
+/// ```no_run
+/// use std::fs::File;
+/// use peppi::io::{peppi::ser::StreamWriter, slippi::parser::{ParsedEvent, Parser}};
+///
+/// let mut parser = Parser::new(None);
+/// parser.drain_frames(600);
+/// let mut writer = None;
+/// # let chunks: Vec<Vec<u8>> = vec![];
+/// for chunk in chunks {
+///     for event in parser.feed(&chunk).unwrap() {
+///         if let ParsedEvent::Start(start) = event {
+///             let f = File::create("game.slpp").unwrap();
+///             writer = Some(StreamWriter::new(f, &start, None).unwrap());
+///         }
+///     }
+///     for frames in parser.take_frames() {
+///         writer.as_mut().unwrap().write_frames(frames).unwrap();
+///     }
+/// }
+/// writer.unwrap().finish(parser.into_game().unwrap()).unwrap();
+/// ```
+pub struct StreamWriter<W: Write + Seek> {
+	sink: Sink<W>,
+	version: slippi::Version,
+	ports: Vec<PortOccupancy>,
+	opts: Opts,
+	/// Position of the contents of `peppi.json`.
+	peppi_pos: u64,
+	/// Position of the header for `frames.arrow`.
+	frames_pos: u64,
+}
+
+impl<W: Write + Seek> StreamWriter<W> {
+	/// Starts writing a replay to `w`.
+	pub fn new(mut w: W, start: &game::Start, opts: Option<&Opts>) -> Result<Self, Box<dyn Error>> {
+		let opts = opts.cloned().unwrap_or_default();
+		if opts.chunk_size == Some(0) {
+			return Err("chunk size must be positive".into());
+		}
+		if !opts.dictionary.is_empty() {
+			return Err("dictionary encoding is not supported when streaming".into());
+		}
+		// `peppi.json` must come first, so reserve space to fill in later
+		// (as JSON, trailing whitespace is harmless)
+		let peppi_pos = w.stream_position()? + BLOCK_SIZE as u64;
+		raw_append(&mut w, &[b' '; PEPPI_JSON_SIZE], "peppi.json")?;
+		raw_append(&mut w, &serde_json::to_vec(start)?, "start.json")?;
+		raw_append(&mut w, &start_raw(start)?, "start.raw")?;
+		// we'll fill in the header for `frames.arrow` once we know its size
+		let frames_pos = w.stream_position()?;
+		w.write_all(&[0; BLOCK_SIZE])?;
+		Ok(Self {
+			sink: Sink::Tar(w),
+			version: start.slippi.version,
+			ports: port_occupancy(start),
+			opts,
+			peppi_pos,
+			frames_pos,
+		})
+	}
+
+	/// Appends `frames` to the replay.
+	///
+	/// See [`crate::io::slippi::parser::Parser::take_frames`],
+	/// [`crate::io::slippi::de::ParseState::take_frames`], &
+	/// [`crate::game::mutable::Game::take_frames`].
+	pub fn write_frames(&mut self, frames: MutableFrame) -> Result<(), Box<dyn Error>> {
+		if frames.len() == 0 {
+			return Ok(());
+		}
+		let frames: Frame = frames.into();
+		self.write_batch(frames.into_struct_array(self.version, &self.ports))
+	}
+
+	fn write_batch(&mut self, batch: StructArray) -> Result<(), Box<dyn Error>> {
+		if let Sink::Tar(_) = self.sink {
+			let w = match std::mem::replace(&mut self.sink, Sink::Poisoned) {
+				Sink::Tar(w) => w,
+				_ => unreachable!(),
+			};
+			let data_type = batch.data_type().clone();
+			let encoded = delta(&StructArray::new_empty(data_type.clone()), &self.opts)?;
+			let writer = FileWriter::try_new(
+				w,
+				frames_schema(encoded.data_type().clone()),
+				None,
+				WriteOptions {
+					compression: self.opts.compression,
+				},
+			)?;
+			self.sink = Sink::Arrow(Box::new(writer), data_type);
+		}
+		match &mut self.sink {
+			Sink::Arrow(writer, data_type) => {
+				if batch.data_type() != data_type {
+					return Err("frame schema changed".into());
+				}
+				if batch.len() == 0 {
+					writer.write(&Chunk::new(vec![delta(&batch, &self.opts)?.boxed()]), None)?;
+				} else {
+					write_batches(writer, &batch, &self.opts)?;
+				}
+				Ok(())
+			}
+			_ => Err("previous write failed".into()),
+		}
+	}
+
+	/// Writes any remaining frames in `game`, plus the rest of the replay
+	/// (Game End, metadata, etc), and returns the underlying writer.
+	pub fn finish(mut self, game: Game) -> Result<W, Box<dyn Error>> {
+		// Readers expect `frames.arrow` to have at least one batch, so we
+		// write an empty one if there were no frames.
+		if game.frames.len() > 0 || matches!(self.sink, Sink::Tar(_)) {
+			self.write_batch(game.frames.into_struct_array(self.version, &self.ports))?;
+		}
+		let mut w = match std::mem::replace(&mut self.sink, Sink::Poisoned) {
+			Sink::Arrow(mut writer, _) => {
+				writer.finish()?;
+				writer.into_inner()
+			}
+			_ => return Err("previous write failed".into()),
+		};
+
+		let pos = w.stream_position()?;
+		let size = usize::try_from(pos - self.frames_pos)? - BLOCK_SIZE;
+		pad(&mut w, size)?;
+		let pos = w.stream_position()?;
+		w.seek(SeekFrom::Start(self.frames_pos))?;
+		w.write_all(header(size, "frames.arrow")?.as_bytes())?;
+		w.seek(SeekFrom::Start(pos))?;
+
+		if let Some(end) = &game.end {
+			raw_append(&mut w, &serde_json::to_vec(end)?, "end.json")?;
+			raw_append(&mut w, &end_raw(end, self.version)?, "end.raw")?;
+		}
+		raw_append(
+			&mut w,
+			&serde_json::to_vec(&game.metadata)?,
+			"metadata.json",
+		)?;
+		if let Some(gecko_codes) = &game.gecko_codes {
+			raw_append(&mut w, &gecko_codes_bytes(gecko_codes), "gecko_codes.raw")?;
+		}
+		if !game.unknown_events.is_empty() {
+			raw_append(
+				&mut w,
+				&unknown_events_bytes(&game.unknown_events)?,
+				"unknown_events.raw",
+			)?;
+		}
+		// end-of-archive marker
+		w.write_all(&[0; 2 * BLOCK_SIZE])?;
+
+		let peppi = serde_json::to_vec(&peppi::Peppi {
+			version: peppi::CURRENT_VERSION,
+			slp_hash: game.hash,
+			quirks: game.quirks,
+		})?;
+		if peppi.len() > PEPPI_JSON_SIZE {
+			return Err("peppi.json too large".into());
+		}
+		let pos = w.stream_position()?;
+		w.seek(SeekFrom::Start(self.peppi_pos))?;
+		w.write_all(&peppi)?;
+		w.seek(SeekFrom::Start(pos))?;
+		w.flush()?;
+		Ok(w)
+	}
+}
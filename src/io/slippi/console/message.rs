@@ -0,0 +1,172 @@
+//! Message framing & encoding for the console protocol.
+//!
+//! Each message is a UBJSON object like `{"type": 2, "payload": {...}}`,
+//! prefixed by its size in bytes (`u32`, big-endian).
+
+use std::io::{self, Read, Write};
+
+use byteorder::ReadBytesExt;
+
+use crate::io::{
+	ubjson::{self, Value},
+	Error, Result,
+};
+
+type BE = byteorder::BigEndian;
+
+/// Largest message we'll accept, to avoid allocating garbage sizes.
+const MAX_SIZE: u32 = 1 << 24;
+
+const HANDSHAKE: i64 = 1;
+const REPLAY: i64 = 2;
+const KEEP_ALIVE: i64 = 3;
+
+/// A message sent by either side of a console connection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) enum Message {
+	/// Sent by the client to request data starting at `cursor`,
+	/// and by the console in reply (with its own `cursor`).
+	Handshake {
+		cursor: u64,
+		client_token: u32,
+		/// Console name (console only).
+		nick: Option<String>,
+		/// Nintendont/Slippi version (console only).
+		version: Option<String>,
+	},
+	/// A chunk of the raw event stream, from `cursor` to `next_cursor`.
+	Replay {
+		cursor: u64,
+		next_cursor: u64,
+		data: Vec<u8>,
+	},
+	KeepAlive,
+}
+
+fn cursor(v: Option<&Value>) -> Result<u64> {
+	let bytes = v
+		.and_then(Value::as_bytes)
+		.ok_or_else(|| err!("missing cursor"))?;
+	let bytes: [u8; 8] = bytes
+		.try_into()
+		.map_err(|b: Vec<u8>| err!("invalid cursor size: {}", b.len()))?;
+	Ok(u64::from_be_bytes(bytes))
+}
+
+impl Message {
+	fn to_value(&self) -> Value {
+		let (r#type, payload) = match self {
+			Message::Handshake {
+				cursor,
+				client_token,
+				nick,
+				version,
+			} => {
+				let mut payload = vec![
+					(
+						"cursor".to_string(),
+						Value::Bytes(cursor.to_be_bytes().to_vec()),
+					),
+					(
+						"clientToken".to_string(),
+						Value::Bytes(client_token.to_be_bytes().to_vec()),
+					),
+				];
+				if let Some(nick) = nick {
+					payload.push(("nick".to_string(), Value::Str(nick.clone())));
+				}
+				if let Some(version) = version {
+					payload.push(("nintendontVersion".to_string(), Value::Str(version.clone())));
+				}
+				(HANDSHAKE, payload)
+			}
+			Message::Replay {
+				cursor,
+				next_cursor,
+				data,
+			} => (
+				REPLAY,
+				vec![
+					(
+						"pos".to_string(),
+						Value::Bytes(cursor.to_be_bytes().to_vec()),
+					),
+					(
+						"nextPos".to_string(),
+						Value::Bytes(next_cursor.to_be_bytes().to_vec()),
+					),
+					("data".to_string(), Value::Bytes(data.clone())),
+				],
+			),
+			Message::KeepAlive => (KEEP_ALIVE, vec![]),
+		};
+		Value::Object(vec![
+			("type".to_string(), Value::Int(r#type)),
+			("payload".to_string(), Value::Object(payload)),
+		])
+	}
+
+	fn from_value(value: &Value) -> Result<Self> {
+		let r#type = value
+			.get("type")
+			.and_then(Value::as_int)
+			.ok_or_else(|| err!("missing message type"))?;
+		let payload = value.get("payload").unwrap_or(&Value::Null);
+		match r#type {
+			HANDSHAKE => Ok(Message::Handshake {
+				// clients send `cursor`, consoles send `pos`
+				cursor: cursor(payload.get("cursor").or_else(|| payload.get("pos")))?,
+				client_token: payload
+					.get("clientToken")
+					.and_then(Value::as_bytes)
+					.and_then(|b| <[u8; 4]>::try_from(b).ok())
+					.map_or(0, u32::from_be_bytes),
+				nick: payload
+					.get("nick")
+					.and_then(Value::as_str)
+					.map(String::from),
+				version: payload
+					.get("nintendontVersion")
+					.and_then(Value::as_str)
+					.map(String::from),
+			}),
+			REPLAY => Ok(Message::Replay {
+				cursor: cursor(payload.get("pos"))?,
+				next_cursor: cursor(payload.get("nextPos"))?,
+				data: payload
+					.get("data")
+					.and_then(Value::as_bytes)
+					.ok_or_else(|| err!("missing replay data"))?,
+			}),
+			KEEP_ALIVE => Ok(Message::KeepAlive),
+			t => Err(err!("unknown message type: {}", t)),
+		}
+	}
+}
+
+/// Writes `message`, with its size prefix.
+pub(super) fn write<W: Write>(w: &mut W, message: &Message) -> Result<()> {
+	// leave room for the size, so we can send it all at once
+	let mut buf = vec![0; 4];
+	ubjson::write_value(&mut buf, &message.to_value())?;
+	let size = u32::try_from(buf.len() - 4).map_err(|_| err!("message too big"))?;
+	buf[..4].copy_from_slice(&size.to_be_bytes());
+	w.write_all(&buf)?;
+	w.flush()?;
+	Ok(())
+}
+
+/// Reads the next message, or `None` if the connection was closed cleanly.
+pub(super) fn read<R: Read>(r: &mut R) -> Result<Option<Message>> {
+	let size = match r.read_u32::<BE>() {
+		Ok(size) => size,
+		Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+		Err(e) => return Err(Error::Io(e)),
+	};
+	if size > MAX_SIZE {
+		return Err(err!("message too big: {} bytes", size));
+	}
+	let mut buf = vec![0; size as usize];
+	r.read_exact(&mut buf)?;
+	Message::from_value(&ubjson::read_value(&mut &buf[..])?).map(Some)
+}
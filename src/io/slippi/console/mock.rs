@@ -0,0 +1,93 @@
+//! A mock console that sends a `.slp` file using the console protocol,
+//! mostly for testing [`super::Client`].
+
+use std::{
+	io::{Read, Write},
+	net::{SocketAddr, TcpListener},
+	thread,
+};
+
+use log::debug;
+
+use super::message::{self, Message};
+use crate::io::{
+	slippi::{de, FILE_SIGNATURE},
+	Result,
+};
+
+/// Nick the mock console reports in its handshake.
+pub const NICK: &str = "peppi-mock";
+
+/// Version the mock console reports in its handshake.
+pub const VERSION: &str = "1.11.0";
+
+/// Sends a keep-alive after every this many replay messages.
+const KEEP_ALIVE_INTERVAL: usize = 16;
+
+/// Serves one client over `stream`: waits for its handshake, then sends the
+/// raw event stream of `slp` (the bytes of a `.slp` file), starting from the
+/// client's cursor, in messages of (at most) `chunk_size` bytes.
+///
+/// Returns once everything has been sent.
+pub fn serve<S: Read + Write>(mut stream: S, slp: &[u8], chunk_size: usize) -> Result<()> {
+	let raw_len = de::parse_header(slp, None)? as usize;
+	let start = FILE_SIGNATURE.len() + 4;
+	let raw = slp
+		.get(start..start + raw_len)
+		.ok_or_else(|| err!("truncated replay"))?;
+
+	let cursor = match message::read(&mut stream)? {
+		Some(Message::Handshake { cursor, .. }) => cursor,
+		Some(m) => return Err(err!("expected handshake, got: {:?}", m)),
+		None => return Err(err!("connection closed during handshake")),
+	};
+	// like a real console, start over if the client asks for data we don't have
+	let mut cursor = match cursor as usize > raw.len() {
+		true => 0,
+		_ => cursor,
+	};
+	message::write(
+		&mut stream,
+		&Message::Handshake {
+			cursor,
+			client_token: 0,
+			nick: Some(NICK.to_string()),
+			version: Some(VERSION.to_string()),
+		},
+	)?;
+
+	for (i, chunk) in raw[cursor as usize..].chunks(chunk_size.max(1)).enumerate() {
+		if i % KEEP_ALIVE_INTERVAL == KEEP_ALIVE_INTERVAL - 1 {
+			message::write(&mut stream, &Message::KeepAlive)?;
+		}
+		let next_cursor = cursor + chunk.len() as u64;
+		message::write(
+			&mut stream,
+			&Message::Replay {
+				cursor,
+				next_cursor,
+				data: chunk.to_vec(),
+			},
+		)?;
+		cursor = next_cursor;
+	}
+	debug!("Mock console done: {} bytes", cursor);
+	Ok(())
+}
+
+/// Listens on an unused local port, and serves the first client to connect
+/// (see [`serve`]) on a new thread.
+///
+/// Returns the address to connect to, and a handle for the server thread.
+pub fn spawn(
+	slp: Vec<u8>,
+	chunk_size: usize,
+) -> Result<(SocketAddr, thread::JoinHandle<Result<()>>)> {
+	let listener = TcpListener::bind(("127.0.0.1", 0))?;
+	let addr = listener.local_addr()?;
+	let handle = thread::spawn(move || {
+		let (stream, _) = listener.accept()?;
+		serve(stream, &slp, chunk_size)
+	});
+	Ok((addr, handle))
+}
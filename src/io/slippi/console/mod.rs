@@ -0,0 +1,197 @@
+//! Live replay data from a Slippi console relay (a Wii running Slippi
+//! Nintendont, or anything that speaks the same protocol, such as the
+//! Slippi Launcher's console mirror).
+//!
+//! The console sends the raw event stream (without the `.slp` UBJSON wrapper
+//! or metadata) over TCP, in size-prefixed UBJSON messages. A [`Client`]
+//! handles the handshake & framing, and feeds the event stream to a
+//! [`Parser`]:
+//!
+//! ```no_run
+//! use peppi::io::slippi::{console::Client, parser::ParsedEvent};
+//!
+//! let mut client = Client::connect(("192.168.1.100", 51441), None).unwrap();
+//! while let Some(events) = client.next_events().unwrap() {
+//!     for event in events {
+//!         if let ParsedEvent::Frame(f) = event {
+//!             println!("frame {}", f.id);
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Dolphin's spectator protocol (used by Slippi Dolphin for netplay mirroring)
+//! runs over ENet instead of TCP, and isn't supported. But since it carries the
+//! same raw event stream, you can feed its payloads to a [`Parser::raw`] yourself.
+//!
+//! See [`mock`] for a local server that sends a `.slp` file using this protocol.
+
+mod message;
+pub mod mock;
+
+use std::{
+	io::{Read, Write},
+	net::{TcpStream, ToSocketAddrs},
+};
+
+use log::{debug, info};
+
+use crate::{
+	game::immutable::Game,
+	io::{
+		slippi::{
+			de::{Opts, ParseState},
+			parser::{ParsedEvent, Parser},
+		},
+		Result,
+	},
+};
+
+use message::Message;
+
+/// Default TCP port for console connections.
+pub const PORT: u16 = 51441;
+
+/// Client for a console relay connection.
+pub struct Client<S> {
+	stream: S,
+	opts: Option<Opts>,
+	nick: Option<String>,
+	version: Option<String>,
+	/// Position in the console's stream of the next bytes we expect.
+	cursor: u64,
+	parser: Parser,
+	/// Bytes left over from the previous game, not yet fed to `parser`.
+	pending: Vec<u8>,
+}
+
+impl Client<TcpStream> {
+	/// Connects to the console at `addr` and performs the handshake.
+	pub fn connect<A: ToSocketAddrs>(addr: A, opts: Option<Opts>) -> Result<Self> {
+		let stream = TcpStream::connect(addr)?;
+		stream.set_nodelay(true)?;
+		Self::new(stream, opts)
+	}
+}
+
+impl<S: Read + Write> Client<S> {
+	/// Performs the handshake over an already-connected `stream`, requesting
+	/// data from the start of the console's stream.
+	pub fn new(stream: S, opts: Option<Opts>) -> Result<Self> {
+		Self::with_cursor(stream, 0, opts)
+	}
+
+	/// Performs the handshake over an already-connected `stream`, requesting
+	/// data from `cursor` onward (e.g. to resume after a disconnect).
+	///
+	/// The console may choose to start elsewhere; see [`Client::cursor`].
+	pub fn with_cursor(mut stream: S, cursor: u64, opts: Option<Opts>) -> Result<Self> {
+		message::write(
+			&mut stream,
+			&Message::Handshake {
+				cursor,
+				client_token: 0,
+				nick: None,
+				version: None,
+			},
+		)?;
+		let (cursor, nick, version) = loop {
+			match message::read(&mut stream)? {
+				Some(Message::Handshake {
+					cursor,
+					nick,
+					version,
+					..
+				}) => break (cursor, nick, version),
+				Some(Message::KeepAlive) => continue,
+				Some(m) => return Err(err!("expected handshake, got: {:?}", m)),
+				None => return Err(err!("connection closed during handshake")),
+			}
+		};
+		info!(
+			"Connected to console: {:?} (version {:?}), cursor: {}",
+			nick, version, cursor
+		);
+		Ok(Self {
+			stream,
+			parser: Parser::raw(opts.clone()),
+			opts,
+			nick,
+			version,
+			cursor,
+			pending: Vec::new(),
+		})
+	}
+
+	/// The console's name, if it sent one.
+	pub fn nick(&self) -> Option<&str> {
+		self.nick.as_deref()
+	}
+
+	/// The console's Nintendont/Slippi version, if it sent one.
+	pub fn version(&self) -> Option<&str> {
+		self.version.as_deref()
+	}
+
+	/// Position in the console's stream of the next bytes we expect.
+	pub fn cursor(&self) -> u64 {
+		self.cursor
+	}
+
+	/// Parse state of the current (or just-finished) game, once its Game
+	/// Start has been received.
+	pub fn state(&self) -> Option<&ParseState> {
+		self.parser.state()
+	}
+
+	/// Whether the current game has ended.
+	pub fn is_game_done(&self) -> bool {
+		self.parser.is_done()
+	}
+
+	/// Returns the current game (if we've gotten to its Game Start), and
+	/// starts parsing a new one.
+	///
+	/// If the current game hasn't ended, any missing data for its last frame is null.
+	pub fn take_game(&mut self) -> Option<Game> {
+		let parser = std::mem::replace(&mut self.parser, Parser::raw(self.opts.clone()));
+		self.pending.extend_from_slice(parser.remaining());
+		parser.into_game()
+	}
+
+	/// Waits for the next replay data from the console, and returns whatever
+	/// it completed. Returns `None` once the console closes the connection.
+	///
+	/// After a game ends, the next call starts parsing a new one (discarding
+	/// the finished game, unless you retrieve it first via [`Client::take_game`]).
+	pub fn next_events(&mut self) -> Result<Option<Vec<ParsedEvent>>> {
+		loop {
+			match message::read(&mut self.stream)? {
+				Some(Message::Replay {
+					cursor,
+					next_cursor,
+					data,
+				}) => {
+					if cursor != self.cursor {
+						return Err(err!(
+							"cursor mismatch: expected {}, got {}",
+							self.cursor,
+							cursor
+						));
+					}
+					debug!("Replay data: {} bytes @{}", data.len(), cursor);
+					self.cursor = next_cursor;
+					if self.parser.is_done() {
+						self.take_game();
+					}
+					let mut buf = std::mem::take(&mut self.pending);
+					buf.extend_from_slice(&data);
+					return self.parser.feed(&buf).map(Some);
+				}
+				Some(Message::KeepAlive) => continue,
+				Some(m) => return Err(err!("unexpected message: {:?}", m)),
+				None => return Ok(None),
+			}
+		}
+	}
+}
@@ -7,22 +7,25 @@ use std::{
 	path::PathBuf,
 };
 
-use arrow2::array::MutableArray;
+use arrow2::array::{MutableArray, MutableFixedSizeBinaryArray};
 use byteorder::ReadBytesExt;
-use log::{debug, info, trace, warn};
+use log::{debug, info, trace};
+use xxhash_rust::xxh3::Xxh3;
 
 type BE = byteorder::BigEndian;
 
 use crate::{
-	frame::{self, mutable::Frame as MutableFrame, transpose},
+	frame::{self, mutable::Frame as MutableFrame, transpose, PortOccupancy},
 	game::{
 		self, immutable::Game, port_occupancy, shift_jis::MeleeString, Match, Netplay, Player,
-		PlayerType, Port, Quirks, MAX_PLAYERS, NUM_PORTS,
+		PlayerType, Port, Quirks, UnknownEvent, UnknownPayload, MAX_PLAYERS, NUM_PORTS,
+	},
+	io::{
+		expect_bytes, format_hash, slippi, ubjson, Context, Error, HashingReader, Result, Warning,
 	},
-	io::{expect_bytes, slippi, ubjson, HashingReader, Result},
 };
 
-type PayloadSizes = [Option<NonZeroU16>; 256];
+pub(super) type PayloadSizes = [Option<NonZeroU16>; 256];
 
 #[derive(Clone, Debug)]
 pub struct Debug {
@@ -40,6 +43,10 @@ pub struct Opts {
 	pub compute_hash: bool,
 	/// Debug options.
 	pub debug: Option<Debug>,
+	/// Recover from corrupt or truncated replays by returning the data parsed before the
+	/// problem, rather than an error. See [`Game::warnings`] for what was dropped.
+	/// Errors before the first frame (in the header or Game Start) are still fatal.
+	pub lenient: bool,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, num_enum::TryFromPrimitive)]
@@ -60,7 +67,34 @@ pub enum Event {
 #[derive(Debug, Default)]
 struct SplitAccumulator {
 	raw: Vec<u8>,
-	actual_size: u32,
+	/// `actual_size` of each Message Splitter event so far.
+	actual_sizes: Vec<u16>,
+}
+
+impl SplitAccumulator {
+	fn actual_size(&self) -> u32 {
+		self.actual_sizes.iter().map(|&s| s as u32).sum()
+	}
+
+	/// The original Message Splitter events, for a wrapped event we can't parse.
+	fn unknown_events(&self, code: u8, index: usize) -> Vec<UnknownEvent> {
+		let last = self.actual_sizes.len() - 1;
+		self.raw
+			.chunks(512)
+			.zip(&self.actual_sizes)
+			.enumerate()
+			.map(|(i, (raw, actual_size))| {
+				let mut bytes = raw.to_vec();
+				bytes.extend_from_slice(&actual_size.to_be_bytes());
+				bytes.extend_from_slice(&[code, u8::from(i == last)]);
+				UnknownEvent {
+					code: Event::MessageSplitter as u8,
+					index,
+					bytes,
+				}
+			})
+			.collect()
+	}
 }
 
 pub struct PartialGame {
@@ -71,6 +105,8 @@ pub struct PartialGame {
 	pub gecko_codes: Option<game::GeckoCodes>,
 	pub hash: Option<String>,
 	pub quirks: Option<Quirks>,
+	pub unknown_events: Vec<UnknownEvent>,
+	pub warnings: Vec<Warning>,
 }
 
 impl From<PartialGame> for Game {
@@ -83,17 +119,21 @@ impl From<PartialGame> for Game {
 			gecko_codes: game.gecko_codes,
 			hash: game.hash,
 			quirks: game.quirks,
+			unknown_events: game.unknown_events,
+			warnings: game.warnings,
 		}
 	}
 }
 
 pub struct ParseState {
-	payload_sizes: PayloadSizes,
-	bytes_read: usize,
+	pub(super) payload_sizes: PayloadSizes,
+	pub(super) bytes_read: usize,
 	event_counts: HashMap<u8, usize>,
 	split_accumulator: SplitAccumulator,
-	port_indexes: [usize; 4],
-	game: PartialGame,
+	port_indexes: [Option<usize>; NUM_PORTS],
+	/// ID of the last frame removed by [`ParseState::take_frames`].
+	taken_id: Option<i32>,
+	pub(super) game: PartialGame,
 }
 
 impl game::Game for ParseState {
@@ -133,29 +173,217 @@ impl ParseState {
 		self.bytes_read
 	}
 
+	/// Removes & returns the frames parsed so far, so that they don't
+	/// accumulate indefinitely (e.g. when parsing a long live game).
+	///
+	/// Returns `None` if there aren't any, or if we're in the middle of a
+	/// frame. Frames parsed afterwards are indexed from zero again.
+	pub fn take_frames(&mut self) -> Option<MutableFrame> {
+		let len = self.game.frames.len();
+		if len == 0 || self.complete_len() < len {
+			return None;
+		}
+		// can't fail, since the frame is complete
+		self.frame_close().ok()?;
+		self.taken_id = self.last_id();
+		let frames = MutableFrame::with_capacity(
+			0,
+			self.game.start.slippi.version,
+			&port_occupancy(&self.game.start),
+		);
+		Some(std::mem::replace(&mut self.game.frames, frames))
+	}
+
 	fn last_id(&self) -> Option<i32> {
-		self.game.frames.id.values().last().map(|id| *id)
+		self.game
+			.frames
+			.id
+			.values()
+			.last()
+			.copied()
+			.or(self.taken_id)
+	}
+
+	/// Checks that a frame event with ID `id` belongs to the frame being parsed.
+	fn expect_frame_id(&self, id: i32) -> Result<()> {
+		match self.last_id() {
+			Some(last_id) if last_id == id => Ok(()),
+			Some(last_id) => Err(Error::FrameIdMismatch {
+				expected: last_id,
+				actual: id,
+			}),
+			None => Err(Error::MissingFrameStart { id }),
+		}
+	}
+
+	fn port_data(&mut self, port: u8, is_follower: bool) -> Result<&mut frame::mutable::Data> {
+		let port_index = self
+			.port_indexes
+			.get(port as usize)
+			.copied()
+			.flatten()
+			.ok_or(Error::InvalidPort { port })?;
+		let port_data = &mut self.game.frames.ports[port_index];
+		match is_follower {
+			true => port_data
+				.follower
+				.as_mut()
+				.ok_or(Error::UnexpectedFollower { port }),
+			_ => Ok(&mut port_data.leader),
+		}
+	}
+
+	/// Where we are in the replay, for error reporting.
+	pub(super) fn context(&self, code: Option<u8>) -> Context {
+		Context {
+			offset: self.bytes_read,
+			code,
+			frame_id: self.last_id(),
+			version: Some(self.game.start.slippi.version),
+		}
 	}
 
 	fn frame_open(&mut self, id: i32) {
 		self.game.frames.id.push(Some(id));
 	}
 
-	fn frame_close(&mut self) {
+	/// Fills in nulls for any data missing from the current frame.
+	///
+	/// Returns `true` if anything was missing.
+	pub(super) fn frame_close(&mut self) -> Result<bool> {
+		let version = self.game.start.slippi.version;
 		let len = self.game.frames.len();
+		let mut incomplete = false;
 		for p in &mut self.game.frames.ports {
-			while p.leader.len() < len {
-				p.leader.push_null(self.game.start.slippi.version);
-			}
+			incomplete |= close_data(&mut p.leader, len, version);
 			if let Some(f) = &mut p.follower {
-				while f.len() < len {
-					f.push_null(self.game.start.slippi.version);
-				}
+				incomplete |= close_data(f, len, version);
+			}
+		}
+		// The remaining fields are only incomplete if we stopped mid-frame (see `Opts::lenient`).
+		let frames = &mut self.game.frames;
+		if let Some(start) = &mut frames.start {
+			while start.len() < len {
+				start.push_null(version);
+				incomplete = true;
+			}
+			pad_trailing(&mut frames.start_trailing, len);
+		}
+		if let Some(end) = &mut frames.end {
+			while end.len() < len {
+				end.push_null(version);
+				incomplete = true;
+			}
+			pad_trailing(&mut frames.end_trailing, len);
+		}
+		if let (Some(item_offset), Some(item)) = (&mut frames.item_offset, &frames.item) {
+			while item_offset.len_proxy() < len {
+				let item_len = i32::try_from(item.len())
+					.map_err(|_| err!("too many items: {}", item.len()))?;
+				item_offset
+					.try_push(item_len - *item_offset.last())
+					.map_err(|e| err!("invalid item offset: {}", e))?;
+				incomplete = true;
+			}
+		}
+		Ok(incomplete)
+	}
+
+	/// Number of frames for which every array has data, i.e. that are safe to
+	/// transpose. Usually all but the frame being parsed.
+	pub(super) fn complete_len(&self) -> usize {
+		let frames = &self.game.frames;
+		let mut len = frames.len();
+		for p in &frames.ports {
+			for d in std::iter::once(&p.leader).chain(p.follower.as_ref()) {
+				len = len.min(d.len()).min(d.post.len());
+			}
+		}
+		if let Some(start) = &frames.start {
+			len = len.min(start.len());
+		}
+		if let Some(end) = &frames.end {
+			len = len.min(end.len());
+		}
+		if let Some(item_offset) = &frames.item_offset {
+			len = len.min(item_offset.len_proxy());
+		}
+		len
+	}
+
+	/// Stops parsing because of `error`, closing the current frame so that
+	/// the data parsed so far is usable.
+	pub(super) fn truncate(&mut self, error: Error) {
+		let incomplete_frame = match self.frame_close() {
+			Ok(true) => self.last_id(),
+			_ => None,
+		};
+		self.game.warnings.push(Warning::Truncated {
+			error,
+			incomplete_frame,
+		});
+	}
+
+	/// Skips ahead to Game End, which we assume is the last event in the stream!
+	///
+	/// Returns the number of bytes to skip.
+	pub(super) fn skip_to_end(&mut self, raw_len: usize) -> Result<usize> {
+		let code = Event::GameEnd as u8;
+		let end_offset = 1 + self.payload_sizes[code as usize]
+			.ok_or(Error::MissingPayloadSize { code })?
+			.get() as usize;
+		if raw_len == 0 || raw_len.saturating_sub(self.bytes_read) < end_offset {
+			return Err(err!(
+				"Cannot skip to game end. Replay in-progress or corrupted."
+			));
+		}
+		let skip = raw_len - self.bytes_read - end_offset;
+		info!("Jumping to GameEnd (skipping {} bytes)", skip);
+		self.bytes_read += skip;
+		Ok(skip)
+	}
+
+	/// Checks `extra`, the bytes between Game End and the end of the raw
+	/// event stream (which is `raw_len` bytes long).
+	pub(super) fn check_extra_content(&mut self, raw_len: usize, extra: &[u8]) {
+		// Some replays have duplicated Game End events, which are safe to ignore.
+		if !extra.is_empty() {
+			let len = extra.len();
+			if len == 1 + game::End::size(self.game.start.slippi.version)
+				&& extra[0] == Event::GameEnd as u8
+			{
+				self.game.warnings.push(Warning::DuplicateGameEnd);
+				self.game
+					.quirks
+					.get_or_insert(Quirks::default())
+					.double_game_end = true;
+			} else {
+				self.game.warnings.push(Warning::ExtraContent { len });
 			}
+		} else if raw_len > 0 && self.bytes_read > raw_len {
+			self.game.warnings.push(Warning::Overrun {
+				len: self.bytes_read - raw_len,
+			});
 		}
 	}
 }
 
+/// Pads `data` with nulls up to `len`, returning `true` if any were needed.
+fn close_data(data: &mut frame::mutable::Data, len: usize, version: slippi::Version) -> bool {
+	let mut incomplete = false;
+	while data.len() < len {
+		data.push_null(version);
+		incomplete = true;
+	}
+	// a Frame Pre without its Frame Post
+	while data.post.len() < len {
+		data.post.push_null(version);
+		incomplete = true;
+	}
+	pad_trailing(&mut data.post_trailing, len);
+	incomplete
+}
+
 fn if_more<F, T>(r: &mut &[u8], f: F) -> Result<Option<T>>
 where
 	F: FnOnce(&mut &[u8]) -> Result<T>,
@@ -462,16 +690,23 @@ pub(crate) fn game_end(r: &mut &[u8]) -> Result<game::End> {
 }
 
 fn handle_splitter_event(buf: &[u8], accumulator: &mut SplitAccumulator) -> Result<Option<u8>> {
-	assert_eq!(buf.len(), 516);
+	if buf.len() != 516 {
+		return Err(err!("invalid message splitter size: {}", buf.len()));
+	}
 	let actual_size = (&buf[512..514]).read_u16::<BE>()?;
-	assert!(actual_size <= 512);
+	if actual_size > 512 {
+		return Err(err!(
+			"invalid message splitter actual size: {}",
+			actual_size
+		));
+	}
 	let wrapped_event = buf[514];
 	let is_final = buf[515] != 0;
 
 	// bytes beyond `actual_size` are meaningless,
 	// but save them anyway for lossless round-tripping
 	accumulator.raw.extend_from_slice(&buf[0..512]);
-	accumulator.actual_size += actual_size as u32;
+	accumulator.actual_sizes.push(actual_size);
 
 	Ok(match is_final {
 		true => Some(wrapped_event),
@@ -498,10 +733,15 @@ fn debug_write_event(
 /// Parses an Event Payloads event from `r`, which must come first in the raw
 /// stream and tells us the sizes for all other events to follow.
 ///
-/// Returns the number of bytes read, and a map of event codes to payload sizes.
-/// This map uses raw event codes as keys (as opposed to `Event` enum values)
-/// for forwards compatibility, to allow skipping unknown events.
-fn parse_payloads<R: Read>(mut r: R, opts: Option<&Opts>) -> Result<(usize, PayloadSizes)> {
+/// Returns the number of bytes read, a map of event codes to payload sizes,
+/// and the entries for event types we don't recognize (in their original
+/// order, for round-tripping). The map uses raw event codes as keys (as
+/// opposed to `Event` enum values) for forwards compatibility, to allow
+/// skipping unknown events.
+pub(super) fn parse_payloads<R: Read>(
+	mut r: R,
+	opts: Option<&Opts>,
+) -> Result<(usize, PayloadSizes, Vec<UnknownPayload>)> {
 	let code = r.read_u8()?;
 	if code != Event::Payloads as u8 {
 		return Err(err!("expected event payloads, but got: {:#02x}", code));
@@ -524,16 +764,20 @@ fn parse_payloads<R: Read>(mut r: R, opts: Option<&Opts>) -> Result<(usize, Payl
 	}
 
 	let mut sizes: PayloadSizes = [None; 256];
-	for _ in (0..size - 1).step_by(3) {
+	let mut unknown = Vec::new();
+	for index in 0..(size - 1) as usize / 3 {
 		let code = buf.read_u8()?;
 		let size = buf.read_u16::<BE>()?;
 		sizes[code as usize] =
 			Some(NonZeroU16::new(size).ok_or_else(|| err!("zero-size event payload"))?);
+		if Event::try_from(code).is_err() {
+			unknown.push(UnknownPayload { index, code, size });
+		}
 	}
 
-	sizes[Event::GameStart as usize].ok_or_else(|| err!("missing Game Start in payload sizes"))?;
-
-	sizes[Event::GameEnd as usize].ok_or_else(|| err!("missing Game End in payload sizes"))?;
+	for code in [Event::GameStart as u8, Event::GameEnd as u8] {
+		sizes[code as usize].ok_or(Error::MissingPayloadSize { code })?;
+	}
 
 	debug!(
 		"Event payload sizes: {{{}}}",
@@ -545,7 +789,7 @@ fn parse_payloads<R: Read>(mut r: R, opts: Option<&Opts>) -> Result<(usize, Payl
 			.join(", ")
 	);
 
-	Ok((1 + size as usize, sizes)) // +1 byte for the event code
+	Ok((1 + size as usize, sizes, unknown)) // +1 byte for the event code
 }
 
 /// Parses a Game Start event from `r`, which must come immediately after the
@@ -579,6 +823,128 @@ fn parse_game_start<R: Read>(
 	}
 }
 
+/// Size of the part of a frame event's payload that we know how to parse,
+/// or `None` for other events.
+fn known_payload_size(event: Event, version: slippi::Version) -> Option<usize> {
+	use frame::immutable::{End, Item, Post, Pre, Start};
+	const FRAME_ID: usize = std::mem::size_of::<i32>();
+	const PORT: usize = 2 * std::mem::size_of::<u8>(); // port number + is_follower
+
+	match event {
+		Event::FramePre => Some(FRAME_ID + PORT + Pre::size(version)),
+		Event::FramePost => Some(FRAME_ID + PORT + Post::size(version)),
+		Event::FrameStart => Some(FRAME_ID + Start::size(version)),
+		Event::FrameEnd => Some(FRAME_ID + End::size(version)),
+		Event::Item => Some(FRAME_ID + Item::size(version)),
+		_ => None,
+	}
+}
+
+/// Checks that frame event payloads are big enough for `version`, so that
+/// we never end up with a partially-parsed event.
+fn check_payload_sizes(payload_sizes: &PayloadSizes, version: slippi::Version) -> Result<()> {
+	use Event::*;
+	for event in [FramePre, FramePost, FrameStart, FrameEnd, Item] {
+		let min_size = known_payload_size(event, version).unwrap();
+		match payload_sizes[event as usize] {
+			Some(size) if (size.get() as usize) < min_size => {
+				return Err(err!(
+					"payload size for {:?} too small for v{}: {} < {}",
+					event,
+					version,
+					size,
+					min_size
+				))
+			}
+			_ => {}
+		}
+	}
+
+	Ok(())
+}
+
+/// Makes room for any bytes at the end of frame events beyond what we know
+/// how to parse (i.e. from newer Slippi versions), so we can round-trip them.
+///
+/// Assumes the payload sizes have passed `check_payload_sizes`.
+fn init_trailing(
+	frames: &mut MutableFrame,
+	payload_sizes: &PayloadSizes,
+	version: slippi::Version,
+	capacity: usize,
+) {
+	let trailing = |event: Event| {
+		let known_size = known_payload_size(event, version).unwrap();
+		payload_sizes[event as usize]
+			.map(|size| size.get() as usize - known_size)
+			.filter(|&size| size > 0)
+			.map(|size| MutableFixedSizeBinaryArray::with_capacity(size, capacity))
+	};
+	for p in &mut frames.ports {
+		for d in std::iter::once(&mut p.leader).chain(p.follower.as_mut()) {
+			d.pre_trailing = trailing(Event::FramePre);
+			d.post_trailing = trailing(Event::FramePost);
+		}
+	}
+	if frames.start.is_some() {
+		frames.start_trailing = trailing(Event::FrameStart);
+	}
+	if frames.end.is_some() {
+		frames.end_trailing = trailing(Event::FrameEnd);
+		frames.item_trailing = trailing(Event::Item);
+	}
+}
+
+/// Upper bound on the number of frames in a raw event stream of `raw_len`
+/// bytes, assuming each frame has all of its events (and no items).
+fn frame_capacity(
+	payload_sizes: &PayloadSizes,
+	ports: &[PortOccupancy],
+	version: slippi::Version,
+	raw_len: usize,
+) -> usize {
+	let size =
+		|event: Event| payload_sizes[event as usize].map_or(0, |size| 1 + size.get() as usize);
+	let per_port = size(Event::FramePre) + size(Event::FramePost);
+	let mut per_frame: usize = ports
+		.iter()
+		.map(|p| per_port * (1 + p.follower as usize))
+		.sum();
+	if version.gte(2, 2) {
+		per_frame += size(Event::FrameStart);
+	}
+	if version.gte(3, 0) {
+		per_frame += size(Event::FrameEnd);
+	}
+	match per_frame {
+		0 => 1024,
+		_ => raw_len / per_frame,
+	}
+}
+
+/// Saves the bytes left over after parsing an event, if we're expecting any.
+fn push_trailing(trailing: &mut Option<MutableFixedSizeBinaryArray>, r: &[u8]) -> Result<()> {
+	if let Some(t) = trailing {
+		if r.len() != t.size() {
+			return Err(err!(
+				"unexpected trailing bytes: expected {}, got {}",
+				t.size(),
+				r.len()
+			));
+		}
+		t.push(Some(r));
+	}
+	Ok(())
+}
+
+fn pad_trailing(trailing: &mut Option<MutableFixedSizeBinaryArray>, len: usize) {
+	if let Some(t) = trailing {
+		while t.len() < len {
+			t.push_null();
+		}
+	}
+}
+
 pub fn parse_header<R: Read>(mut r: R, _opts: Option<&Opts>) -> Result<u32> {
 	// For speed, assume the `raw` element comes first and handle it manually.
 	// The official JS parser does this too, so it should be reliable.
@@ -587,30 +953,54 @@ pub fn parse_header<R: Read>(mut r: R, _opts: Option<&Opts>) -> Result<u32> {
 	Ok(r.read_u32::<BE>()?)
 }
 
-pub fn parse_start<R: Read>(mut r: R, opts: Option<&Opts>) -> Result<ParseState> {
-	let (bytes_read, payload_sizes) = parse_payloads(&mut r, opts)?;
-	let (bytes_read, start) = parse_game_start(&mut r, &payload_sizes, bytes_read, opts)?;
+pub fn parse_start<R: Read>(r: R, opts: Option<&Opts>) -> Result<ParseState> {
+	parse_start_sized(r, opts, 0)
+}
+
+/// Like [`parse_start`], but uses `raw_len` (the size of the raw event stream,
+/// if known) to pre-size the frame arrays.
+fn parse_start_sized<R: Read>(mut r: R, opts: Option<&Opts>, raw_len: usize) -> Result<ParseState> {
+	let context = |offset| Context {
+		offset,
+		code: None,
+		frame_id: None,
+		version: None,
+	};
+	let (bytes_read, payload_sizes, unknown_payloads) =
+		parse_payloads(&mut r, opts).map_err(|e| e.with_context(context(0)))?;
+	let (bytes_read, start) = parse_game_start(&mut r, &payload_sizes, bytes_read, opts)
+		.map_err(|e| e.with_context(context(bytes_read)))?;
+	check_payload_sizes(&payload_sizes, start.slippi.version)
+		.map_err(|e| e.with_context(context(0)))?;
 
 	let ports = port_occupancy(&start);
 	let version = start.slippi.version;
 	let capacity = match opts.map_or(false, |o| o.skip_frames) {
 		true => 0,
+		false if raw_len > 0 => frame_capacity(&payload_sizes, &ports, version, raw_len),
 		false => 1024,
 	};
+	let mut frames = MutableFrame::with_capacity(capacity, version, &ports);
+	init_trailing(&mut frames, &payload_sizes, version, capacity);
 	let game = PartialGame {
 		start: start.clone(),
 		end: None,
-		frames: MutableFrame::with_capacity(capacity, version, &ports),
+		frames,
 		metadata: None,
 		gecko_codes: None,
 		hash: None,
-		quirks: None,
+		quirks: (!unknown_payloads.is_empty()).then(|| Quirks {
+			unknown_payloads,
+			..Default::default()
+		}),
+		unknown_events: Vec::new(),
+		warnings: Vec::new(),
 	};
 
 	let port_indexes = {
-		let mut result = [0, 0, 0, 0];
+		let mut result = [None; NUM_PORTS];
 		for (i, p) in ports.into_iter().enumerate() {
-			result[p.port as usize] = i;
+			result[p.port as usize] = Some(i);
 		}
 		result
 	};
@@ -623,33 +1013,88 @@ pub fn parse_start<R: Read>(mut r: R, opts: Option<&Opts>) -> Result<ParseState>
 		event_counts,
 		game,
 		port_indexes,
+		taken_id: None,
 		split_accumulator: Default::default(),
 	})
 }
 
 /// Parses a single event from `r`.
 ///
-/// Returns the event code that was parsed.
+/// Returns the event code that was parsed. Errors carry a [`Context`]
+/// describing where in the replay they occurred.
 pub fn parse_event<R: Read>(mut r: R, state: &mut ParseState, opts: Option<&Opts>) -> Result<u8> {
-	let mut code = r.read_u8()?;
+	let code = r
+		.read_u8()
+		.map_err(|e| Error::from(e).with_context(state.context(None)))?;
+	parse_event_payload(r, code, state, opts).map_err(|e| e.with_context(state.context(Some(code))))
+}
+
+fn parse_event_payload<R: Read>(
+	mut r: R,
+	code: u8,
+	state: &mut ParseState,
+	opts: Option<&Opts>,
+) -> Result<u8> {
 	debug!("Event {:#02x} @{:#x}", code, state.bytes_read);
 
-	let size = state.payload_sizes[code as usize]
-		.ok_or_else(|| err!("unknown event: {:#02x}", code))?
-		.get() as usize;
+	let size = payload_size(state, code)?;
 	let mut buf = vec![0; size];
 	r.read_exact(&mut buf)?;
+	handle_event(&buf, code, state, opts)
+}
+
+/// Like [`parse_event`], but borrows the event's payload from `r` instead of
+/// copying it, and advances `r` past the event.
+fn parse_event_slice(r: &mut &[u8], state: &mut ParseState, opts: Option<&Opts>) -> Result<u8> {
+	let code = r
+		.read_u8()
+		.map_err(|e| Error::from(e).with_context(state.context(None)))?;
+	parse_event_payload_slice(r, code, state, opts)
+		.map_err(|e| e.with_context(state.context(Some(code))))
+}
+
+fn parse_event_payload_slice(
+	r: &mut &[u8],
+	code: u8,
+	state: &mut ParseState,
+	opts: Option<&Opts>,
+) -> Result<u8> {
+	debug!("Event {:#02x} @{:#x}", code, state.bytes_read);
+
+	let size = payload_size(state, code)?;
+	if r.len() < size {
+		return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+	}
+	let (buf, rest) = r.split_at(size);
+	*r = rest;
+	handle_event(buf, code, state, opts)
+}
+
+fn payload_size(state: &ParseState, code: u8) -> Result<usize> {
+	Ok(state.payload_sizes[code as usize]
+		.ok_or_else(|| err!("unknown event: {:#02x}", code))?
+		.get() as usize)
+}
 
+/// Handles the payload of a single event, which is all of `buf`.
+fn handle_event(
+	buf: &[u8],
+	mut code: u8,
+	state: &mut ParseState,
+	opts: Option<&Opts>,
+) -> Result<u8> {
+	let size = buf.len();
+	let mut wrapped = None;
 	if code == Event::MessageSplitter as u8 {
-		if let Some(wrapped_event) = handle_splitter_event(&buf, &mut state.split_accumulator)? {
+		if let Some(wrapped_event) = handle_splitter_event(buf, &mut state.split_accumulator)? {
 			code = wrapped_event;
-			buf.clear();
-			buf.append(&mut state.split_accumulator.raw);
+			wrapped = Some(std::mem::take(&mut state.split_accumulator));
 		}
 	};
+	let buf = wrapped.as_ref().map_or(buf, |w| &w.raw[..]);
 
 	if let Some(ref d) = opts.as_ref().and_then(|o| o.debug.as_ref()) {
-		debug_write_event(&buf, code, Some(state), d)?;
+		debug_write_event(buf, code, Some(state), d)?;
 	}
 
 	*state.event_counts.entry(code).or_default() += 1;
@@ -657,13 +1102,18 @@ pub fn parse_event<R: Read>(mut r: R, state: &mut ParseState, opts: Option<&Opts
 	let event = Event::try_from(code).ok();
 	if let Some(event) = event {
 		use Event::*;
+		// Only Gecko Codes are split across Message Splitter events, and we
+		// can't write anything else that way.
+		if wrapped.is_some() && event != GeckoCodes {
+			return Err(Error::UnexpectedEvent);
+		}
 		match event {
 			Payloads => return Err(err!("Duplicate payloads event")),
 			MessageSplitter => {}
 			GeckoCodes => {
 				state.game.gecko_codes = Some(game::GeckoCodes {
 					bytes: buf.to_vec(),
-					actual_size: state.split_accumulator.actual_size,
+					actual_size: wrapped.as_ref().map_or(0, |w| w.actual_size()),
 				})
 			}
 			GameStart => return Err(err!("Duplicate start event")),
@@ -671,11 +1121,14 @@ pub fn parse_event<R: Read>(mut r: R, state: &mut ParseState, opts: Option<&Opts
 			FrameStart => {
 				// no FrameEnd events before v3.0, so simulate it
 				if state.game.start.slippi.version.lt(3, 0) {
-					state.frame_close();
+					state.frame_close()?;
 				}
 				let r = &mut &*buf;
 				let id = r.read_i32::<BE>()?;
 				trace!("Frame start: {}", id);
+				if state.game.frames.start.is_none() {
+					return Err(Error::UnexpectedEvent);
+				}
 				state.frame_open(id);
 				state
 					.game
@@ -684,6 +1137,7 @@ pub fn parse_event<R: Read>(mut r: R, state: &mut ParseState, opts: Option<&Opts
 					.as_mut()
 					.unwrap()
 					.read_push(r, state.game.start.slippi.version)?;
+				push_trailing(&mut state.game.frames.start_trailing, r)?;
 			}
 			FramePre => {
 				let r = &mut &*buf;
@@ -692,42 +1146,29 @@ pub fn parse_event<R: Read>(mut r: R, state: &mut ParseState, opts: Option<&Opts
 				let is_follower = r.read_u8()? != 0;
 				trace!("Frame pre: {}:{}", id, port);
 				if state.game.start.slippi.version.gte(2, 2) {
-					assert_eq!(id, state.last_id().unwrap());
+					state.expect_frame_id(id)?;
 				} else {
 					// no Frame Start events before v2.2, but also no rollbacks
 					let last_id = state.last_id().unwrap_or(frame::FIRST_INDEX - 1);
-					if last_id + 1 == id {
+					if last_id.checked_add(1) == Some(id) {
 						state.frame_open(id);
-					} else {
-						assert_eq!(id, last_id);
+					} else if last_id != id {
+						return Err(Error::FrameIdMismatch {
+							expected: last_id,
+							actual: id,
+						});
 					}
 				}
-				let port_index = state.port_indexes[port as usize];
-				if is_follower {
-					state.game.frames.ports[port_index]
-						.follower
-						.as_mut()
-						.unwrap()
-						.validity
-						.as_mut()
-						.map(|v| v.push(true));
-					state.game.frames.ports[port_index]
-						.follower
-						.as_mut()
-						.unwrap()
-						.pre
-						.read_push(r, state.game.start.slippi.version)?;
-				} else {
-					state.game.frames.ports[port_index]
-						.leader
-						.validity
-						.as_mut()
-						.map(|v| v.push(true));
-					state.game.frames.ports[port_index]
-						.leader
-						.pre
-						.read_push(r, state.game.start.slippi.version)?;
+				let version = state.game.start.slippi.version;
+				let len = state.game.frames.len();
+				let data = state.port_data(port, is_follower)?;
+				if data.len() >= len {
+					// duplicate Frame Pre
+					return Err(Error::UnexpectedEvent);
 				}
+				data.validity.as_mut().map(|v| v.push(true));
+				data.pre.read_push(r, version)?;
+				push_trailing(&mut data.pre_trailing, r)?;
 			}
 			FramePost => {
 				let r = &mut &*buf;
@@ -735,44 +1176,38 @@ pub fn parse_event<R: Read>(mut r: R, state: &mut ParseState, opts: Option<&Opts
 				let port = r.read_u8()?;
 				let is_follower = r.read_u8()? != 0;
 				trace!("Frame post: {}:{}", id, port);
-				assert_eq!(id, state.last_id().unwrap());
-				match is_follower {
-					true => state.game.frames.ports[state.port_indexes[port as usize]]
-						.follower
-						.as_mut()
-						.unwrap()
-						.post
-						.read_push(r, state.game.start.slippi.version)?,
-					_ => state.game.frames.ports[state.port_indexes[port as usize]]
-						.leader
-						.post
-						.read_push(r, state.game.start.slippi.version)?,
-				};
+				state.expect_frame_id(id)?;
+				let version = state.game.start.slippi.version;
+				let data = state.port_data(port, is_follower)?;
+				if data.post.len() >= data.len() {
+					// Frame Post without a matching Frame Pre
+					return Err(Error::UnexpectedEvent);
+				}
+				data.post.read_push(r, version)?;
+				push_trailing(&mut data.post_trailing, r)?;
 			}
 			FrameEnd => {
 				let r = &mut &*buf;
 				let id = r.read_i32::<BE>()?;
 				trace!("Frame end: {}", id);
-				assert_eq!(id, state.last_id().unwrap());
+				state.expect_frame_id(id)?;
+				// `end`, `item` & `item_offset` all exist since v3.0
+				match &state.game.frames.end {
+					Some(end) if end.len() < state.game.frames.len() => {}
+					_ => return Err(Error::UnexpectedEvent),
+				}
 				let old_len = *state.game.frames.item_offset.as_ref().unwrap().last();
-				let new_len: i32 = state
-					.game
-					.frames
-					.item
-					.as_ref()
-					.unwrap()
-					.r#type
-					.len()
-					.try_into()
-					.unwrap();
+				let new_len = state.game.frames.item.as_ref().unwrap().r#type.len();
+				let new_len =
+					i32::try_from(new_len).map_err(|_| err!("too many items: {}", new_len))?;
 				state
 					.game
 					.frames
 					.item_offset
 					.as_mut()
 					.unwrap()
-					.try_push(new_len.checked_sub(old_len).unwrap())
-					.unwrap();
+					.try_push(new_len - old_len)
+					.map_err(|e| err!("invalid item offset: {}", e))?;
 				state
 					.game
 					.frames
@@ -780,22 +1215,50 @@ pub fn parse_event<R: Read>(mut r: R, state: &mut ParseState, opts: Option<&Opts
 					.as_mut()
 					.unwrap()
 					.read_push(r, state.game.start.slippi.version)?;
-				state.frame_close();
+				push_trailing(&mut state.game.frames.end_trailing, r)?;
+				state.frame_close()?;
 			}
 			Item => {
 				let r = &mut &*buf;
 				let id = r.read_i32::<BE>()?;
 				trace!("Frame item: {}", id);
-				assert_eq!(id, state.last_id().unwrap());
-				state
-					.game
-					.frames
-					.item
-					.as_mut()
-					.unwrap()
-					.read_push(r, state.game.start.slippi.version)?;
+				state.expect_frame_id(id)?;
+				let version = state.game.start.slippi.version;
+				match state.game.frames.item.as_mut() {
+					Some(item) => item.read_push(r, version)?,
+					None => return Err(Error::UnexpectedEvent),
+				}
+				push_trailing(&mut state.game.frames.item_trailing, r)?;
 			}
 		};
+	} else {
+		if let Some(w) = &wrapped {
+			// The preceding Message Splitter events were counted as known, but
+			// we'll keep them as unknown events along with this one.
+			let splitter = Event::MessageSplitter as u8;
+			if let Some(n) = state.event_counts.get_mut(&splitter) {
+				*n -= w.actual_sizes.len() - 1;
+			}
+		}
+		// Keep the event's position relative to the events we know how to
+		// write, so that it can be re-emitted in the same place.
+		let index = state
+			.event_counts
+			.iter()
+			.filter(|(&c, _)| c != Event::Payloads as u8 && Event::try_from(c).is_ok())
+			.map(|(_, n)| n)
+			.sum();
+		match &wrapped {
+			Some(w) => {
+				let events = w.unknown_events(code, index);
+				state.game.unknown_events.extend(events);
+			}
+			None => state.game.unknown_events.push(UnknownEvent {
+				code,
+				index,
+				bytes: buf.to_vec(),
+			}),
+		}
 	}
 
 	state.bytes_read += size + 1; // +1 byte for the event code
@@ -822,6 +1285,26 @@ pub fn parse_metadata<R: Read>(
 	Ok(())
 }
 
+/// Parses everything after the raw event stream: the (optional) metadata,
+/// and the closing brace of the top-level UBJSON map.
+pub(super) fn parse_trailer<R: Read>(
+	mut r: R,
+	state: &mut ParseState,
+	opts: Option<&Opts>,
+) -> Result<()> {
+	// Some replays have no `metadata` (e.g. Fizzi's anonymized Ranked dataset),
+	// in which case the next char should be the final UBSJON `}`.
+	match r.read_u8()? {
+		0x55 => {
+			parse_metadata(r.by_ref(), state, opts)?;
+			expect_bytes(&mut r, &[0x7d])?;
+		}
+		0x7d => {} // top-level closing brace ("}")
+		x => return Err(err!("expected: 0x55 or 0x7d, got: {:#02x}", x)),
+	};
+	Ok(())
+}
+
 /// Reads a Slippi (`.slp`) replay from `r`.
 pub fn read<R: Read + Seek>(r: R, opts: Option<&Opts>) -> Result<Game> {
 	let hash = opts.map_or(false, |o| o.compute_hash);
@@ -835,73 +1318,131 @@ pub fn read<R: Read + Seek>(r: R, opts: Option<&Opts>) -> Result<Game> {
 	let mut state = parse_start(&mut r, opts)?;
 
 	if opts.map_or(false, |o| o.skip_frames) {
-		// Skip to GameEnd, which we assume is the last event in the stream!
-		let end_offset = 1 + state.payload_sizes[Event::GameEnd as usize].unwrap().get() as usize;
-		if raw_len == 0 || raw_len - state.bytes_read < end_offset {
-			return Err(err!(
-				"Cannot skip to game end. Replay in-progress or corrupted."
-			));
-		}
-		let skip = raw_len - state.bytes_read - end_offset;
-		info!("Jumping to GameEnd (skipping {} bytes)", skip);
+		let skip = state.skip_to_end(raw_len)?;
 		if hash {
 			io::copy(&mut r.by_ref().take(skip as u64), &mut io::sink())?;
 		} else {
 			r.seek(SeekFrom::Current(skip.try_into().map_err(invalid_data)?))?;
 		}
-		state.bytes_read += skip;
 	}
 
+	let lenient = opts.map_or(false, |o| o.lenient);
+
 	// Main event loop. `raw_len` will be 0 for an in-progress replay.
 	while raw_len == 0 || state.bytes_read < raw_len {
-		if parse_event(r.by_ref(), &mut state, opts)? == Event::GameEnd as u8 {
-			break;
+		match parse_event(r.by_ref(), &mut state, opts) {
+			Ok(code) if code == Event::GameEnd as u8 => break,
+			Ok(_) => {}
+			Err(e) if lenient => {
+				state.truncate(e);
+				return Ok(Game::from(state.game));
+			}
+			Err(e) => return Err(e),
 		}
 	}
 
 	// FrameEnd doesn't exist until v3.0, so we simulate it in FrameStart/FramePre.
 	// But that means there can be a "dangling" frame that we need to close here.
-	if state.game.start.slippi.version.lt(3, 0) {
-		state.frame_close();
-	}
+	// (Corrupt replays can also have a Game End in the middle of a frame.)
+	state.frame_close()?;
 
 	info!("Frames: {}", state.game.frames.len());
 
-	// Some replays have duplicated Game End events, which are safe to ignore.
-	if state.bytes_read < raw_len {
-		let len = raw_len - state.bytes_read;
-		let mut buf = vec![0; len];
-		r.read_exact(&mut buf)?;
-		if len == 1 + game::End::size(state.game.start.slippi.version)
-			&& buf[0] == Event::GameEnd as u8
-		{
-			info!("Skipping duplicate Game End event");
-			state
-				.game
-				.quirks
-				.get_or_insert(Quirks::default())
-				.double_game_end = true;
-		} else {
-			warn!("Extra content after Game End ({} bytes)", len);
+	let mut extra = vec![0; raw_len.saturating_sub(state.bytes_read)];
+	match r.read_exact(&mut extra) {
+		Err(e) if lenient => {
+			state.truncate(e.into());
+			return Ok(Game::from(state.game));
 		}
-	} else if raw_len > 0 && state.bytes_read > raw_len {
-		warn!(
-			"Consumed more than expected ({} bytes)",
-			state.bytes_read - raw_len
-		);
-	}
+		result => result?,
+	};
+	state.check_extra_content(raw_len, &extra);
 
-	// Some replays have no `metadata` (e.g. Fizzi's anonymized Ranked dataset),
-	// in which case the next char should be the final UBSJON `}`.
-	match r.read_u8()? {
-		0x55 => {
-			parse_metadata(r.by_ref(), &mut state, opts)?;
-			expect_bytes(&mut r, &[0x7d])?;
+	match parse_trailer(r.by_ref(), &mut state, opts) {
+		Err(error) if lenient => {
+			state.game.metadata = None;
+			state.game.warnings.push(Warning::InvalidMetadata { error });
 		}
-		0x7d => {} // top-level closing brace ("}")
-		x => return Err(err!("expected: 0x55 or 0x7d, got: {:#02x}", x)),
+		result => result?,
 	};
 
 	state.game.hash = r.into_digest();
 	Ok(Game::from(state.game))
 }
+
+/// Reads a Slippi (`.slp`) replay from `bytes`.
+///
+/// This is faster than [`read`], since event payloads are parsed in place
+/// rather than copied, and the frame arrays are pre-sized to fit. It's a good
+/// fit for memory-mapped files (e.g. via the `memmap2` crate).
+pub fn read_slice(bytes: &[u8], opts: Option<&Opts>) -> Result<Game> {
+	let hash = opts.is_some_and(|o| o.compute_hash);
+	let mut r = bytes;
+
+	// Handle Event Payloads and Game Start
+	let raw_len = parse_header(&mut r, opts)? as usize;
+	info!("Raw length: {} bytes", raw_len);
+
+	// don't trust `raw_len` to size our allocations if it's impossibly large
+	let capacity_len = raw_len.min(r.len());
+	let mut state = parse_start_sized(&mut r, opts, capacity_len)?;
+
+	if opts.is_some_and(|o| o.skip_frames) {
+		let skip = state.skip_to_end(raw_len)?;
+		if r.len() < skip {
+			return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+		}
+		r = &r[skip..];
+	}
+
+	let lenient = opts.is_some_and(|o| o.lenient);
+
+	// Main event loop. `raw_len` will be 0 for an in-progress replay.
+	while raw_len == 0 || state.bytes_read < raw_len {
+		match parse_event_slice(&mut r, &mut state, opts) {
+			Ok(code) if code == Event::GameEnd as u8 => break,
+			Ok(_) => {}
+			Err(e) if lenient => {
+				state.truncate(e);
+				return Ok(Game::from(state.game));
+			}
+			Err(e) => return Err(e),
+		}
+	}
+
+	// see `read`
+	state.frame_close()?;
+
+	info!("Frames: {}", state.game.frames.len());
+
+	let extra_len = raw_len.saturating_sub(state.bytes_read);
+	if r.len() < extra_len {
+		let e = io::Error::from(io::ErrorKind::UnexpectedEof);
+		match lenient {
+			true => {
+				state.truncate(e.into());
+				return Ok(Game::from(state.game));
+			}
+			_ => return Err(e.into()),
+		}
+	}
+	let (extra, rest) = r.split_at(extra_len);
+	r = rest;
+	state.check_extra_content(raw_len, extra);
+
+	match parse_trailer(&mut r, &mut state, opts) {
+		Err(error) if lenient => {
+			state.game.metadata = None;
+			state.game.warnings.push(Warning::InvalidMetadata { error });
+		}
+		result => result?,
+	};
+
+	state.game.hash = hash.then(|| {
+		// like `read`, hash only the bytes we consumed
+		let mut hasher = Xxh3::new();
+		hasher.update(&bytes[..bytes.len() - r.len()]);
+		format_hash(&hasher)
+	});
+	Ok(Game::from(state.game))
+}
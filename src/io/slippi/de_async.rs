@@ -0,0 +1,231 @@
+//! Asynchronous counterparts of the functions in [`super::de`], for parsing
+//! replays from a [`tokio::io::AsyncRead`] (e.g. an upload) as they arrive.
+//!
+//! These read each event into memory and then hand it off to the blocking
+//! parser, so they never block on I/O. Requires the `tokio` feature.
+
+use std::{
+	io,
+	pin::Pin,
+	task::{self, Poll},
+};
+
+use log::info;
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::{
+	game::immutable::Game,
+	io::{
+		format_hash,
+		slippi::{
+			de::{self, Event, Opts, ParseState},
+			FILE_SIGNATURE,
+		},
+		Error, Result, Warning,
+	},
+};
+
+/// Most bytes we'll read after the raw event stream. Real metadata is only a
+/// few kilobytes, but the trailer isn't length-prefixed, so we need a limit.
+const MAX_TRAILER_LEN: usize = 1 << 20;
+
+/// Reader that hashes the bytes it reads.
+struct HashingReader<R: AsyncRead + Unpin> {
+	reader: R,
+	hasher: Option<Box<Xxh3>>,
+}
+
+impl<R: AsyncRead + Unpin> HashingReader<R> {
+	fn new(reader: R, hash: bool) -> Self {
+		Self {
+			reader,
+			hasher: hash.then(|| Box::new(Xxh3::new())),
+		}
+	}
+
+	fn into_digest(self) -> Option<String> {
+		self.hasher.as_deref().map(format_hash)
+	}
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for HashingReader<R> {
+	fn poll_read(
+		mut self: Pin<&mut Self>,
+		cx: &mut task::Context<'_>,
+		buf: &mut ReadBuf<'_>,
+	) -> Poll<io::Result<()>> {
+		let start = buf.filled().len();
+		let this = &mut *self;
+		let result = Pin::new(&mut this.reader).poll_read(cx, buf);
+		if let Some(h) = this.hasher.as_mut() {
+			h.update(&buf.filled()[start..]);
+		}
+		result
+	}
+}
+
+/// Appends up to `len` bytes from `r` to `buf`, stopping early at EOF.
+///
+/// We leave it to the blocking parser to detect truncation, since it
+/// knows how to report it (with context).
+async fn read_up_to<R: AsyncRead + Unpin>(
+	r: &mut R,
+	buf: &mut Vec<u8>,
+	len: usize,
+) -> io::Result<()> {
+	(&mut *r).take(len as u64).read_to_end(buf).await?;
+	Ok(())
+}
+
+/// Async counterpart of [`de::parse_header`].
+pub async fn parse_header<R: AsyncRead + Unpin>(mut r: R, opts: Option<&Opts>) -> Result<u32> {
+	// signature, then `raw` content size (u32)
+	let mut buf = Vec::new();
+	read_up_to(&mut r, &mut buf, FILE_SIGNATURE.len() + 4).await?;
+	de::parse_header(&buf[..], opts)
+}
+
+/// Async counterpart of [`de::parse_start`].
+pub async fn parse_start<R: AsyncRead + Unpin>(
+	mut r: R,
+	opts: Option<&Opts>,
+) -> Result<ParseState> {
+	// Event Payloads: event code, size, then `size - 1` bytes of payload sizes
+	let mut buf = Vec::new();
+	read_up_to(&mut r, &mut buf, 2).await?;
+	if let Some(&size) = buf.get(1) {
+		read_up_to(&mut r, &mut buf, (size as usize).saturating_sub(1)).await?;
+	}
+
+	// We need the payload sizes to know how much of Game Start to read.
+	// Don't pass `opts` here, or we'd output debug info twice.
+	let payload_sizes = match de::parse_payloads(&buf[..], None) {
+		Ok((_, payload_sizes, _)) => payload_sizes,
+		Err(_) => return de::parse_start(&buf[..], opts),
+	};
+
+	let code_offset = buf.len();
+	read_up_to(&mut r, &mut buf, 1).await?;
+	if let Some(size) = buf
+		.get(code_offset)
+		.and_then(|&code| payload_sizes[code as usize])
+	{
+		read_up_to(&mut r, &mut buf, size.get() as usize).await?;
+	}
+
+	de::parse_start(&buf[..], opts)
+}
+
+/// Async counterpart of [`de::parse_event`].
+pub async fn parse_event<R: AsyncRead + Unpin>(
+	mut r: R,
+	state: &mut ParseState,
+	opts: Option<&Opts>,
+) -> Result<u8> {
+	let mut buf = Vec::new();
+	read_up_to(&mut r, &mut buf, 1)
+		.await
+		.map_err(|e| Error::from(e).with_context(state.context(None)))?;
+	if let Some(&code) = buf.first() {
+		if let Some(size) = state.payload_sizes[code as usize] {
+			read_up_to(&mut r, &mut buf, size.get() as usize)
+				.await
+				.map_err(|e| Error::from(e).with_context(state.context(Some(code))))?;
+		}
+	}
+	de::parse_event(&buf[..], state, opts)
+}
+
+/// Async counterpart of [`de::parse_metadata`].
+///
+/// Metadata isn't length-prefixed, so this reads `r` to the end.
+pub async fn parse_metadata<R: AsyncRead + Unpin>(
+	mut r: R,
+	state: &mut ParseState,
+	opts: Option<&Opts>,
+) -> Result<()> {
+	let mut buf = Vec::new();
+	r.read_to_end(&mut buf).await?;
+	de::parse_metadata(&buf[..], state, opts)
+}
+
+/// Reads a Slippi (`.slp`) replay from `r`. Async counterpart of [`de::read`].
+///
+/// Since `r` can't seek, [`Opts::skip_frames`] still reads the frame events
+/// (but doesn't parse them). Metadata larger than 1 MiB is treated as
+/// invalid.
+pub async fn read<R: AsyncRead + Unpin>(r: R, opts: Option<&Opts>) -> Result<Game> {
+	let hash = opts.is_some_and(|o| o.compute_hash);
+	// Wrap so we can hash all the bytes we've read at the end.
+	let mut r = HashingReader::new(r, hash);
+
+	// Handle Event Payloads and Game Start
+	let raw_len = parse_header(&mut r, opts).await? as usize;
+	info!("Raw length: {} bytes", raw_len);
+
+	let mut state = parse_start(&mut r, opts).await?;
+
+	if opts.is_some_and(|o| o.skip_frames) {
+		let skip = state.skip_to_end(raw_len)?;
+		tokio::io::copy(&mut (&mut r).take(skip as u64), &mut tokio::io::sink()).await?;
+	}
+
+	let lenient = opts.is_some_and(|o| o.lenient);
+
+	// Main event loop. `raw_len` will be 0 for an in-progress replay.
+	while raw_len == 0 || state.bytes_read < raw_len {
+		match parse_event(&mut r, &mut state, opts).await {
+			Ok(code) if code == Event::GameEnd as u8 => break,
+			Ok(_) => {}
+			Err(e) if lenient => {
+				state.truncate(e);
+				return Ok(Game::from(state.game));
+			}
+			Err(e) => return Err(e),
+		}
+	}
+
+	// Close any "dangling" frame (see `de::read`).
+	state.frame_close()?;
+
+	info!("Frames: {}", state.game.frames.len());
+
+	let mut extra = vec![0; raw_len.saturating_sub(state.bytes_read)];
+	match r.read_exact(&mut extra).await {
+		Err(e) if lenient => {
+			state.truncate(e.into());
+			return Ok(Game::from(state.game));
+		}
+		result => result?,
+	};
+	state.check_extra_content(raw_len, &extra);
+
+	// The trailer isn't length-prefixed, so read as much as we allow and
+	// then hash only what the parser consumed (as the blocking reader would).
+	// Read errors are handled like parse errors, also as the blocking reader
+	// would.
+	let mut trailer = Vec::new();
+	let read = read_up_to(&mut r.reader, &mut trailer, MAX_TRAILER_LEN).await;
+	let mut rest = &trailer[..];
+	let result = match read {
+		Ok(()) => de::parse_trailer(&mut rest, &mut state, opts),
+		Err(e) => {
+			rest = &[];
+			Err(e.into())
+		}
+	};
+	if let Some(h) = r.hasher.as_mut() {
+		h.update(&trailer[..trailer.len() - rest.len()]);
+	}
+	match result {
+		Err(error) if lenient => {
+			state.game.metadata = None;
+			state.game.warnings.push(Warning::InvalidMetadata { error });
+		}
+		result => result?,
+	};
+
+	state.game.hash = r.into_digest();
+	Ok(Game::from(state.game))
+}
@@ -0,0 +1,163 @@
+//! Following a `.slp` file that's still being written (e.g. by Dolphin).
+//!
+//! While a game is in progress, Dolphin writes events to the replay file as
+//! they happen, with a raw event stream size of 0 in the header. A
+//! [`Follower`] repeatedly reads whatever's been appended since last time,
+//! and feeds it to a [`Parser`]:
+//!
+//! ```no_run
+//! use peppi::io::slippi::{follow::Follower, parser::ParsedEvent};
+//!
+//! let follower = Follower::open("Game_20240101T120000.slp", None).unwrap();
+//! let game = follower
+//!     .run(|event, _| {
+//!         if let ParsedEvent::Frame(f) = event {
+//!             println!("frame {}", f.id);
+//!         }
+//!     })
+//!     .unwrap();
+//! ```
+
+use std::{
+	fs::File,
+	io::{ErrorKind, Read},
+	path::Path,
+	thread,
+	time::{Duration, Instant},
+};
+
+use log::debug;
+
+use crate::{
+	game::immutable::Game,
+	io::{
+		slippi::{
+			de::{self, ParseState},
+			parser::{ParsedEvent, Parser},
+		},
+		Result,
+	},
+};
+
+/// How often to check for new bytes, by default.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Options for following a file.
+#[derive(Clone, Debug)]
+pub struct Opts {
+	/// Options for parsing the replay itself.
+	pub parse: Option<de::Opts>,
+	/// How long to wait between checks for new bytes.
+	pub poll_interval: Duration,
+	/// Give up if nothing new has been written for this long (e.g. because
+	/// Dolphin crashed). By default, wait forever.
+	pub idle_timeout: Option<Duration>,
+}
+
+impl Default for Opts {
+	fn default() -> Self {
+		Self {
+			parse: None,
+			poll_interval: DEFAULT_POLL_INTERVAL,
+			idle_timeout: None,
+		}
+	}
+}
+
+/// Parses a replay file as it grows.
+pub struct Follower<R> {
+	r: R,
+	opts: Opts,
+	parser: Parser,
+	buf: Vec<u8>,
+}
+
+impl Follower<File> {
+	/// Opens the replay at `path` for following.
+	pub fn open<P: AsRef<Path>>(path: P, opts: Option<Opts>) -> Result<Self> {
+		Ok(Self::new(File::open(path)?, opts))
+	}
+}
+
+impl<R: Read> Follower<R> {
+	/// Follows `r`, which should return any newly-written bytes from each call
+	/// to `read` (and `Ok(0)` when there aren't any yet), like a `File` does.
+	pub fn new(r: R, opts: Option<Opts>) -> Self {
+		let opts = opts.unwrap_or_default();
+		Self {
+			r,
+			parser: Parser::new(opts.parse.clone()),
+			opts,
+			buf: vec![0; 64 * 1024],
+		}
+	}
+
+	/// Parse state so far, once Game Start has been written.
+	pub fn state(&self) -> Option<&ParseState> {
+		self.parser.state()
+	}
+
+	/// Whether we've reached the end of the replay.
+	pub fn is_done(&self) -> bool {
+		self.parser.is_done()
+	}
+
+	/// The game parsed so far, or `None` if we haven't gotten to Game Start.
+	///
+	/// If we're not done, any missing data for the current frame is null.
+	pub fn into_game(self) -> Option<Game> {
+		self.parser.into_game()
+	}
+
+	/// Reads & parses everything that's been written since the last call,
+	/// without waiting. Returns `None` if nothing new was written.
+	pub fn poll(&mut self) -> Result<Option<Vec<ParsedEvent>>> {
+		let mut events = Vec::new();
+		let mut any = false;
+		while !self.parser.is_done() {
+			let n = match self.r.read(&mut self.buf) {
+				Ok(0) => break,
+				Ok(n) => n,
+				Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e.into()),
+			};
+			any = true;
+			events.extend(self.parser.feed(&self.buf[..n])?);
+		}
+		Ok(any.then_some(events))
+	}
+
+	/// Waits for the next bytes to be written, and returns whatever they
+	/// completed. Returns `None` once we're done, or if we've waited longer
+	/// than [`Opts::idle_timeout`].
+	pub fn next_events(&mut self) -> Result<Option<Vec<ParsedEvent>>> {
+		let start = Instant::now();
+		while !self.parser.is_done() {
+			if let Some(events) = self.poll()? {
+				return Ok(Some(events));
+			}
+			if self.opts.idle_timeout.is_some_and(|t| start.elapsed() >= t) {
+				debug!("Timed out waiting for new bytes");
+				break;
+			}
+			thread::sleep(self.opts.poll_interval);
+		}
+		Ok(None)
+	}
+
+	/// Follows the file until the end of the replay (or until we time out),
+	/// calling `f` for each event as it's completed, along with the current
+	/// parse state.
+	///
+	/// Returns the game (possibly incomplete, if we timed out), or `None` if
+	/// we never got to Game Start.
+	pub fn run<F: FnMut(ParsedEvent, &ParseState)>(mut self, mut f: F) -> Result<Option<Game>> {
+		while let Some(events) = self.next_events()? {
+			for event in events {
+				// all events come after Game Start, so we must have state
+				f(event, self.parser.state().unwrap());
+			}
+		}
+		Ok(self.into_game())
+	}
+}
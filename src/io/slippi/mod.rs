@@ -1,6 +1,11 @@
 //! Slippi (`.slp`) serialization.
 
+pub mod console;
 pub mod de;
+#[cfg(feature = "tokio")]
+pub mod de_async;
+pub mod follow;
+pub mod parser;
 pub mod ser;
 
 use serde::{Deserialize, Serialize};
@@ -8,12 +13,14 @@ use std::{fmt, str};
 
 use crate::io::{parse_u8, Error, Result};
 
-pub use de::read;
+pub use de::{read, read_slice};
 pub use ser::write;
 
-/// Peppi can read replays with higher versions than this, but that discards information.
-/// So we refuse to re-serialze such replays, to avoid inadvertent information loss.
-/// This restriction may be removed in the future.
+/// Newest Slippi version that Peppi fully understands.
+///
+/// Replays with higher versions can still be read & re-written losslessly, but any
+/// data added to frame events since this version is kept only as opaque trailing
+/// bytes (see e.g. [`crate::frame::immutable::Data::pre_trailing`]).
 pub const MAX_SUPPORTED_VERSION: Version = Version(3, 16, 0);
 
 /// Every `.slp` file starts with a UBJSON opening brace, "raw" key & type (`{U\x03raw[$U#l`).
@@ -57,19 +64,7 @@ impl fmt::Display for Version {
 }
 
 /// Slippi format options.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Slippi {
 	pub version: Version,
 }
-
-pub(crate) fn assert_max_version(version: Version) -> Result<()> {
-	if version <= MAX_SUPPORTED_VERSION {
-		Ok(())
-	} else {
-		Err(err!(
-			"unsupported version ({} > {})",
-			version,
-			MAX_SUPPORTED_VERSION
-		))
-	}
-}
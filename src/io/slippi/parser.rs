@@ -0,0 +1,359 @@
+//! Push-based parsing of Slippi replays, for sources that deliver bytes in
+//! arbitrary chunks (e.g. a socket, or a file that's still being written).
+//!
+//! Unlike [`super::de::parse_event`], which needs a `Read` that can supply a
+//! whole event on demand, a [`Parser`] buffers incomplete events itself:
+//!
+//! ```
+//! use peppi::io::slippi::parser::{ParsedEvent, Parser};
+//!
+//! let bytes = std::fs::read("tests/data/game.slp").unwrap();
+//! let mut parser = Parser::new(None);
+//! let mut frames = 0;
+//! for chunk in bytes.chunks(1000) {
+//!     for event in parser.feed(chunk).unwrap() {
+//!         if let ParsedEvent::Frame(_) = event {
+//!             frames += 1;
+//!         }
+//!     }
+//! }
+//! assert!(parser.is_done());
+//! assert_eq!(frames, 5209);
+//! ```
+
+use log::info;
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::{
+	frame::{mutable::Frame as MutableFrame, transpose},
+	game::{self, immutable::Game, Game as _},
+	io::{
+		format_hash,
+		slippi::{
+			de::{self, Event, Opts, ParseState},
+			FILE_SIGNATURE,
+		},
+		ErrorKind, Result, Warning,
+	},
+};
+
+/// Something of interest that was completed by [`Parser::feed`].
+#[derive(Debug)]
+pub enum ParsedEvent {
+	/// Event Payloads & Game Start. After this, [`Parser::state`] is available.
+	Start(game::Start),
+	/// All events for a single frame (including any rollbacks, which are
+	/// separate frames in this sense).
+	Frame(transpose::Frame),
+	/// Game End.
+	End(game::End),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Phase {
+	/// UBJSON header, up to the size of the raw event stream.
+	Header,
+	/// Event Payloads & Game Start.
+	Start,
+	/// Frame data we've been asked to skip (see [`Opts::skip_frames`]).
+	Skip(usize),
+	/// All other events, up to & including Game End.
+	Events,
+	/// Anything after Game End in the raw event stream.
+	Extra,
+	/// Metadata & the closing UBJSON brace.
+	Trailer,
+	Done,
+}
+
+/// Incremental parser that accepts bytes in chunks of any size.
+///
+/// Any errors are fatal, and the parser shouldn't be used after one is returned.
+/// (Or with [`Opts::lenient`], parsing stops as if at the end of the replay,
+/// and the problem is recorded in [`Game::warnings`].)
+pub struct Parser {
+	opts: Option<Opts>,
+	phase: Phase,
+	/// Whether there's a UBJSON wrapper around the raw event stream.
+	has_header: bool,
+	/// Bytes not yet parsed. We only buffer one event's worth at a time,
+	/// except for the trailer (which has no size prefix).
+	buf: Vec<u8>,
+	raw_len: usize,
+	state: Option<ParseState>,
+	/// How many frames we've yielded as [`ParsedEvent::Frame`].
+	frames_done: usize,
+	hasher: Option<Box<Xxh3>>,
+	/// Minimum number of frames to drain at once (see [`Parser::drain_frames`]).
+	drain: Option<usize>,
+	drained: Vec<MutableFrame>,
+}
+
+impl Parser {
+	/// Creates a parser for a `.slp` file.
+	pub fn new(opts: Option<Opts>) -> Self {
+		Self::with_phase(opts, Phase::Header, true)
+	}
+
+	/// Creates a parser for a bare raw event stream (i.e. without the UBJSON
+	/// wrapper & metadata), as sent by the spectator protocol.
+	pub fn raw(opts: Option<Opts>) -> Self {
+		Self::with_phase(opts, Phase::Start, false)
+	}
+
+	fn with_phase(opts: Option<Opts>, phase: Phase, has_header: bool) -> Self {
+		let hash = opts.as_ref().is_some_and(|o| o.compute_hash);
+		Self {
+			opts,
+			phase,
+			has_header,
+			buf: Vec::new(),
+			raw_len: 0,
+			state: None,
+			frames_done: 0,
+			hasher: hash.then(|| Box::new(Xxh3::new())),
+			drain: None,
+			drained: Vec::new(),
+		}
+	}
+
+	/// Hands off frames in batches of (at least) `n` via
+	/// [`Parser::take_frames`], rather than accumulating them all.
+	///
+	/// Frames that have been taken won't be in [`Parser::into_game`].
+	pub fn drain_frames(&mut self, n: usize) {
+		self.drain = Some(n.max(1));
+	}
+
+	/// Frames drained since the last call (see [`Parser::drain_frames`]).
+	/// Once we reach the end of the game, this includes any stragglers.
+	pub fn take_frames(&mut self) -> Vec<MutableFrame> {
+		std::mem::take(&mut self.drained)
+	}
+
+	/// Parse state so far, once Game Start has been parsed.
+	pub fn state(&self) -> Option<&ParseState> {
+		self.state.as_ref()
+	}
+
+	/// Whether we've reached the end of the replay.
+	pub fn is_done(&self) -> bool {
+		self.phase == Phase::Done
+	}
+
+	/// Bytes that were fed but not yet parsed, such as anything after the
+	/// end of the replay.
+	pub fn remaining(&self) -> &[u8] {
+		&self.buf
+	}
+
+	/// The game parsed so far, or `None` if we haven't gotten to Game Start
+	/// (or the current frame can't be closed, e.g. because it has too many items).
+	///
+	/// If we're not done, any missing data for the current frame is null.
+	pub fn into_game(self) -> Option<Game> {
+		self.state.and_then(|mut state| {
+			state.frame_close().ok()?;
+			state.game.hash = self.hasher.as_deref().map(format_hash);
+			Some(Game::from(state.game))
+		})
+	}
+
+	/// Parses as much as possible of `chunk` (plus anything left over from
+	/// previous calls), and returns whatever was completed.
+	pub fn feed(&mut self, chunk: &[u8]) -> Result<Vec<ParsedEvent>> {
+		let mut buf = std::mem::take(&mut self.buf);
+		buf.extend_from_slice(chunk);
+		let mut events = Vec::new();
+		let mut pos = 0;
+		let result = loop {
+			match self.step(&buf[pos..], &mut events) {
+				Ok(Some(n)) => {
+					if let Some(h) = self.hasher.as_mut() {
+						h.update(&buf[pos..pos + n]);
+					}
+					pos += n;
+				}
+				Ok(None) => break Ok(events),
+				Err(e) => break Err(e),
+			}
+		};
+		buf.drain(..pos);
+		self.buf = buf;
+		result
+	}
+
+	/// Parses the next unit of `buf`, if it's all there.
+	///
+	/// Returns the number of bytes consumed, or `None` if we need more.
+	fn step(&mut self, buf: &[u8], events: &mut Vec<ParsedEvent>) -> Result<Option<usize>> {
+		let opts = self.opts.as_ref();
+		let lenient = opts.is_some_and(|o| o.lenient);
+		match self.phase {
+			Phase::Header => {
+				// signature, then `raw` content size (u32)
+				let len = FILE_SIGNATURE.len() + 4;
+				if buf.len() < len {
+					return Ok(None);
+				}
+				self.raw_len = de::parse_header(&buf[..len], opts)? as usize;
+				info!("Raw length: {} bytes", self.raw_len);
+				self.phase = Phase::Start;
+				Ok(Some(len))
+			}
+			Phase::Start => {
+				let len = match start_len(buf) {
+					Some(len) => len,
+					None => return Ok(None),
+				};
+				let mut state = de::parse_start(&buf[..len], opts)?;
+				events.push(ParsedEvent::Start(state.game.start.clone()));
+				self.phase = match opts.is_some_and(|o| o.skip_frames) {
+					true => Phase::Skip(state.skip_to_end(self.raw_len)?),
+					_ => Phase::Events,
+				};
+				self.state = Some(state);
+				Ok(Some(len))
+			}
+			Phase::Skip(remaining) => {
+				if remaining > 0 && buf.is_empty() {
+					return Ok(None);
+				}
+				let len = remaining.min(buf.len());
+				self.phase = match len == remaining {
+					true => Phase::Events,
+					_ => Phase::Skip(remaining - len),
+				};
+				Ok(Some(len))
+			}
+			Phase::Events => {
+				let state = self.state.as_mut().unwrap();
+				if self.raw_len > 0 && state.bytes_read >= self.raw_len {
+					self.end_events(events)?;
+					return Ok(Some(0));
+				}
+				let len = match buf.first() {
+					Some(&code) => match state.payload_sizes[code as usize] {
+						Some(size) => 1 + size.get() as usize,
+						// unknown event, which `parse_event` will reject
+						None => 1,
+					},
+					None => return Ok(None),
+				};
+				if buf.len() < len {
+					return Ok(None);
+				}
+				match de::parse_event(&buf[..len], state, opts) {
+					Ok(code) if code == Event::GameEnd as u8 => {
+						self.end_events(events)?;
+					}
+					Ok(_) => {
+						let len = state.complete_len();
+						self.yield_frames(len, events);
+						self.drain(false);
+					}
+					Err(e) if lenient => {
+						state.truncate(e);
+						let len = state.game.frames.len();
+						self.yield_frames(len, events);
+						self.drain(true);
+						self.phase = Phase::Done;
+					}
+					Err(e) => return Err(e),
+				}
+				Ok(Some(len))
+			}
+			Phase::Extra => {
+				let state = self.state.as_mut().unwrap();
+				let len = self.raw_len.saturating_sub(state.bytes_read);
+				if buf.len() < len {
+					return Ok(None);
+				}
+				state.check_extra_content(self.raw_len, &buf[..len]);
+				self.phase = Phase::Trailer;
+				Ok(Some(len))
+			}
+			Phase::Trailer => {
+				let state = self.state.as_mut().unwrap();
+				let mut r = buf;
+				match de::parse_trailer(&mut r, state, opts) {
+					// we'll try again once there's more
+					Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+					Err(error) if lenient => {
+						state.game.metadata = None;
+						state.game.warnings.push(Warning::InvalidMetadata { error });
+					}
+					result => result?,
+				};
+				self.phase = Phase::Done;
+				Ok(Some(buf.len() - r.len()))
+			}
+			Phase::Done => Ok(None),
+		}
+	}
+
+	/// Finishes the raw event stream, after Game End (or once we've read as
+	/// many bytes as the header said there would be).
+	fn end_events(&mut self, events: &mut Vec<ParsedEvent>) -> Result<()> {
+		let state = self.state.as_mut().unwrap();
+		// close any "dangling" frame (see `de::read`)
+		state.frame_close()?;
+		info!("Frames: {}", state.game.frames.len());
+		let len = state.game.frames.len();
+		let end = state.game.end.clone();
+		self.yield_frames(len, events);
+		self.drain(true);
+		events.extend(end.map(ParsedEvent::End));
+		self.phase = match self.has_header {
+			true => Phase::Extra,
+			_ => Phase::Done,
+		};
+		Ok(())
+	}
+
+	/// Moves the parsed frames to `drained`, if we're draining and there are
+	/// enough of them (or if `all`).
+	fn drain(&mut self, all: bool) {
+		let state = self.state.as_mut().unwrap();
+		match self.drain {
+			Some(n) if all || state.game.frames.len() >= n => {
+				if let Some(frames) = state.take_frames() {
+					self.frames_done = 0;
+					self.drained.push(frames);
+				}
+			}
+			_ => {}
+		}
+	}
+
+	/// Yields all frames before `len` that we haven't yielded yet.
+	fn yield_frames(&mut self, len: usize, events: &mut Vec<ParsedEvent>) {
+		let state = self.state.as_ref().unwrap();
+		events.extend((self.frames_done..len).map(|i| ParsedEvent::Frame(state.frame(i))));
+		self.frames_done = self.frames_done.max(len);
+	}
+}
+
+/// Size of the Event Payloads & Game Start events at the start of `buf`,
+/// or `None` if we don't have enough bytes to tell.
+///
+/// If the events are invalid, returns just enough for `parse_start` to
+/// find the problem (and report it with context).
+fn start_len(buf: &[u8]) -> Option<usize> {
+	// Event Payloads: event code, size, then `size - 1` bytes of payload sizes
+	let payloads_len = 1 + *buf.get(1)? as usize;
+	if buf.len() < payloads_len {
+		return None;
+	}
+	// Don't pass `opts` here, or we'd output debug info twice.
+	let payload_sizes = match de::parse_payloads(&buf[..payloads_len], None) {
+		Ok((_, payload_sizes, _)) => payload_sizes,
+		Err(_) => return Some(payloads_len),
+	};
+	let code = *buf.get(payloads_len)?;
+	let len = match payload_sizes[code as usize] {
+		Some(size) => payloads_len + 1 + size.get() as usize,
+		None => payloads_len + 1,
+	};
+	(buf.len() >= len).then_some(len)
+}
@@ -1,18 +1,28 @@
-use std::io::Write;
+use std::{collections::HashMap, io::Write};
 
+use arrow2::array::FixedSizeBinaryArray;
 use byteorder::WriteBytesExt;
 
 use crate::{
 	frame::immutable::{End, Frame, Item, Post, Pre, Start},
-	game::{self, immutable::Game, GeckoCodes},
+	game::{self, immutable::Game, GeckoCodes, UnknownEvent, MAX_PLAYERS, NUM_PORTS},
 	io::{
-		slippi::{self, de::Event},
+		slippi::{
+			self,
+			de::{self, Event},
+		},
 		ubjson, Result,
 	},
 };
 
 type BE = byteorder::BigEndian;
 
+/// Game Start & Game End payloads, regenerated from the game's fields.
+struct RawEvents {
+	start: Vec<u8>,
+	end: Option<Vec<u8>>,
+}
+
 struct PayloadSizes {
 	/// Order matters for round-tripping, hence Vec rather than HashMap.
 	sizes: Vec<(u8, u16)>,
@@ -23,20 +33,22 @@ impl PayloadSizes {
 		Self { sizes: Vec::new() }
 	}
 
-	fn push(&mut self, event: Event, size: usize) {
-		self.sizes.push((event as u8, size.try_into().unwrap()))
+	fn push(&mut self, event: Event, size: usize) -> Result<()> {
+		let size =
+			u16::try_from(size).map_err(|_| err!("{:?} event too large: {} bytes", event, size))?;
+		self.sizes.push((event as u8, size));
+		Ok(())
 	}
 
 	fn raw_size(&self, game: &Game) -> u32 {
 		use Event::*;
 
 		let counts = frame_counts(&game.frames);
-		let sizes: std::collections::HashMap<u8, u16> =
-			self.sizes.iter().map(|(k, v)| (*k, *v)).collect();
+		let sizes: HashMap<u8, u16> = self.sizes.iter().map(|(k, v)| (*k, *v)).collect();
 		1 + 1 + (3 * self.sizes.len() as u32) // Payload sizes
 			+ 1 + sizes[&(GameStart as u8)] as u32 // GameStart
 			+ 1 + sizes[&(GameEnd as u8)] as u32 // GameEnd
-			+ match game.quirks.map_or(false, |q| q.double_game_end) {
+			+ match game.quirks.as_ref().map_or(false, |q| q.double_game_end) {
 				true => 1 + sizes[&(GameEnd as u8)] as u32, // ...and another for good measure
 				_ => 0u32,
 			}
@@ -46,44 +58,102 @@ impl PayloadSizes {
 			+ sizes.get(&(FrameEnd as u8)).map_or(0, |s| counts.frames * (1 + *s as u32)) // FrameEnd
 			+ sizes.get(&(Item as u8)).map_or(0, |s| counts.items * (1 + *s as u32)) // Item
 			+ game.gecko_codes.as_ref().map_or(0, gecko_codes_size)
+			+ game
+				.unknown_events
+				.iter()
+				.map(|e| 1 + e.bytes.len() as u32)
+				.sum::<u32>()
 	}
 }
 
-fn payload_sizes(game: &Game) -> PayloadSizes {
+fn payload_sizes(game: &Game, raw: &RawEvents) -> Result<PayloadSizes> {
 	let mut sizes = PayloadSizes::new();
 	let ver = game.start.slippi.version.clone();
 
 	const FRAME_NUMBER: usize = std::mem::size_of::<i32>();
 	const PORT: usize = 2 * std::mem::size_of::<u8>(); // port number + is_follower
 
-	sizes.push(Event::GameStart, game.start.bytes.0.len());
-	sizes.push(Event::FramePre, FRAME_NUMBER + PORT + Pre::size(ver));
-	sizes.push(Event::FramePost, FRAME_NUMBER + PORT + Post::size(ver));
+	let frames = &game.frames;
+	let leader = frames.ports.first().map(|p| &p.leader);
+
+	sizes.push(Event::GameStart, raw.start.len())?;
+	sizes.push(
+		Event::FramePre,
+		FRAME_NUMBER
+			+ PORT + Pre::size(ver)
+			+ trailing_size(leader.and_then(|l| l.pre_trailing.as_ref())),
+	)?;
+	sizes.push(
+		Event::FramePost,
+		FRAME_NUMBER
+			+ PORT + Post::size(ver)
+			+ trailing_size(leader.and_then(|l| l.post_trailing.as_ref())),
+	)?;
 	sizes.push(
 		Event::GameEnd,
-		game.end
-			.as_ref()
-			.map_or(game::End::size(ver), |e| e.bytes.0.len()),
-	);
+		raw.end.as_ref().map_or(game::End::size(ver), |e| e.len()),
+	)?;
 
 	if ver.gte(2, 2) {
-		sizes.push(Event::FrameStart, FRAME_NUMBER + Start::size(ver));
+		sizes.push(
+			Event::FrameStart,
+			FRAME_NUMBER + Start::size(ver) + trailing_size(frames.start_trailing.as_ref()),
+		)?;
 		if ver.gte(3, 0) {
-			sizes.push(Event::Item, FRAME_NUMBER + Item::size(ver));
+			sizes.push(
+				Event::Item,
+				FRAME_NUMBER + Item::size(ver) + trailing_size(frames.item_trailing.as_ref()),
+			)?;
 			if ver.gte(3, 0) {
-				sizes.push(Event::FrameEnd, FRAME_NUMBER + End::size(ver));
+				sizes.push(
+					Event::FrameEnd,
+					FRAME_NUMBER + End::size(ver) + trailing_size(frames.end_trailing.as_ref()),
+				)?;
 				if ver.gte(3, 3) {
 					if let Some(codes) = &game.gecko_codes {
 						// discard higher-order bits of actual_size, matching Slippi's behavior
-						sizes.push(Event::GeckoCodes, codes.actual_size as u16 as usize);
-						sizes.push(Event::MessageSplitter, 516);
+						sizes.push(Event::GeckoCodes, codes.actual_size as u16 as usize)?;
+						sizes.push(Event::MessageSplitter, 516)?;
 					}
 				}
 			}
 		}
 	}
 
-	sizes
+	// restore unknown event types to their original positions
+	if let Some(quirks) = &game.quirks {
+		for p in &quirks.unknown_payloads {
+			if sizes.sizes.iter().any(|(code, _)| *code == p.code) {
+				return Err(err!("duplicate payload size for event {:#04x}", p.code));
+			}
+			let index = p.index.min(sizes.sizes.len());
+			sizes.sizes.insert(index, (p.code, p.size));
+		}
+	}
+
+	for e in &game.unknown_events {
+		let size = u16::try_from(e.bytes.len())
+			.map_err(|_| err!("unknown event too large: {} bytes", e.bytes.len()))?;
+		match sizes.sizes.iter().find(|(code, _)| *code == e.code) {
+			None => sizes.sizes.push((e.code, size)),
+			Some((_, s)) if *s != size => {
+				return Err(err!(
+					"payload size mismatch for event {:#04x}: {} vs {}",
+					e.code,
+					s,
+					size
+				))
+			}
+			_ => {}
+		}
+	}
+
+	Ok(sizes)
+}
+
+/// Size of the unrecognized bytes at the end of each event of a given type, if any.
+fn trailing_size(trailing: Option<&FixedSizeBinaryArray>) -> usize {
+	trailing.map_or(0, |t| t.size())
 }
 
 fn gecko_codes<W: Write>(w: &mut W, codes: &GeckoCodes) -> Result<()> {
@@ -100,15 +170,221 @@ fn gecko_codes<W: Write>(w: &mut W, codes: &GeckoCodes) -> Result<()> {
 	Ok(())
 }
 
-fn game_start<W: Write>(w: &mut W, s: &game::Start, ver: slippi::Version) -> Result<()> {
-	assert_eq!(ver, s.slippi.version);
+fn put(buf: &mut [u8], offset: usize, bytes: &[u8]) {
+	buf[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+/// Writes a string into a fixed-size field, null-padded. `max` is the
+/// longest string the field can hold (some fields need a null terminator).
+fn put_str(buf: &mut [u8], offset: usize, size: usize, max: usize, s: &[u8]) -> Result<()> {
+	if s.len() > max {
+		return Err(err!("string too long: {} bytes (max: {})", s.len(), max));
+	}
+	buf[offset..offset + size].fill(0);
+	put(buf, offset, s);
+	Ok(())
+}
+
+fn player_start_bytes(buf: &mut [u8], n: usize, p: Option<&game::Player>) -> Result<()> {
+	let offset = 0x64 + 36 * n;
+	let p = match p {
+		Some(p) => p,
+		None => {
+			// mark the slot as empty, unless it's already unparseable
+			if game::PlayerType::try_from(buf[offset + 1]).is_ok() {
+				buf[offset + 1] = 3;
+			}
+			return Ok(());
+		}
+	};
+
+	buf[offset] = p.character;
+	buf[offset + 1] = p.r#type as u8;
+	buf[offset + 2] = p.stocks;
+	buf[offset + 3] = p.costume;
+	if let Some(team) = p.team {
+		buf[offset + 7] = team.shade;
+		buf[offset + 9] = team.color;
+	}
+	buf[offset + 8] = p.handicap;
+	buf[offset + 12] = p.bitfield;
+	if let Some(cpu_level) = p.cpu_level {
+		buf[offset + 15] = cpu_level;
+	}
+	put(buf, offset + 24, &p.offense_ratio.to_be_bytes());
+	put(buf, offset + 28, &p.defense_ratio.to_be_bytes());
+	put(buf, offset + 32, &p.model_scale.to_be_bytes());
+
+	// v1.0
+	if let Some(ucf) = p.ucf.filter(|_| buf.len() >= 352) {
+		put(
+			buf,
+			320 + 8 * n,
+			&ucf.dash_back.map_or(0, |x| x as u32).to_be_bytes(),
+		);
+		put(
+			buf,
+			324 + 8 * n,
+			&ucf.shield_drop.map_or(0, |x| x as u32).to_be_bytes(),
+		);
+	}
+
+	// v1.3
+	if let Some(name_tag) = p.name_tag.as_ref().filter(|_| buf.len() >= 416) {
+		put_str(buf, 352 + 16 * n, 16, 16, &name_tag.to_bytes()?)?;
+	}
+
+	// v3.9
+	if let Some(netplay) = p.netplay.as_ref().filter(|_| buf.len() >= 584) {
+		put_str(buf, 420 + 31 * n, 31, 31, &netplay.name.to_bytes()?)?;
+		put_str(buf, 544 + 10 * n, 10, 10, &netplay.code.to_bytes()?)?;
+		// v3.11
+		if let Some(suid) = netplay.suid.as_ref().filter(|_| buf.len() >= 700) {
+			put_str(buf, 584 + 29 * n, 29, 28, suid.as_bytes())?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Regenerates the raw Game Start payload from `start`'s fields.
+///
+/// Bytes that Peppi doesn't parse are copied from `start.bytes`, or zeroed if
+/// that's empty (e.g. for a `Start` deserialized from JSON). If `start` is
+/// unchanged since it was parsed, returns `start.bytes` as-is.
+pub fn start_bytes(start: &game::Start) -> Result<Vec<u8>> {
+	let mut buf = start.bytes.0.clone();
+	if !buf.is_empty() && de::game_start(&mut &buf[..]).is_ok_and(|s| s == *start) {
+		return Ok(buf);
+	}
+	if buf.is_empty() {
+		buf = vec![0; game::Start::size(start.slippi.version)];
+		for n in 0..MAX_PLAYERS {
+			buf[0x64 + 36 * n + 1] = 3; // empty slot
+		}
+	}
+	if buf.len() < game::Start::size(slippi::Version(0, 1, 0)) {
+		return Err(err!("Game Start too short: {} bytes", buf.len()));
+	}
+
+	let ver = start.slippi.version;
+	put(&mut buf, 0, &[ver.0, ver.1, ver.2]);
+	put(&mut buf, 4, &start.bitfield);
+	buf[0xa] = start.is_raining_bombs as u8;
+	buf[0xc] = start.is_teams as u8;
+	buf[0xf] = start.item_spawn_frequency as u8;
+	buf[0x10] = start.self_destruct_score as u8;
+	put(&mut buf, 0x12, &start.stage.to_be_bytes());
+	put(&mut buf, 0x14, &start.timer.to_be_bytes());
+	put(&mut buf, 0x27, &start.item_spawn_bitfield);
+	put(&mut buf, 0x34, &start.damage_ratio.to_be_bytes());
+	put(&mut buf, 0x13c, &start.random_seed.to_be_bytes());
+
+	for n in 0..NUM_PORTS {
+		let mut players = start.players.iter().filter(|p| p.port as usize == n);
+		let player = players.next();
+		if players.next().is_some() {
+			return Err(err!("multiple players on port {}", player.unwrap().port));
+		}
+		player_start_bytes(&mut buf, n, player)?;
+	}
+
+	// v1.5
+	if let Some(is_pal) = start.is_pal.filter(|_| buf.len() >= 417) {
+		buf[416] = is_pal as u8;
+	}
+	// v2.0
+	if let Some(is_frozen_ps) = start.is_frozen_ps.filter(|_| buf.len() >= 418) {
+		buf[417] = is_frozen_ps as u8;
+	}
+	// v3.7
+	if let Some(scene) = start.scene.filter(|_| buf.len() >= 420) {
+		buf[418] = scene.minor;
+		buf[419] = scene.major;
+	}
+	// v3.12
+	if let Some(language) = start.language.filter(|_| buf.len() >= 701) {
+		buf[700] = language as u8;
+	}
+	// v3.14
+	if let Some(m) = start.r#match.as_ref().filter(|_| buf.len() >= 760) {
+		put_str(&mut buf, 701, 51, 50, m.id.as_bytes())?;
+		put(&mut buf, 752, &m.game.to_be_bytes());
+		put(&mut buf, 756, &m.tiebreaker.to_be_bytes());
+	}
+
+	Ok(buf)
+}
+
+/// Regenerates the raw Game End payload from `end`'s fields, like
+/// [`start_bytes`].
+pub fn end_bytes(end: &game::End, version: slippi::Version) -> Result<Vec<u8>> {
+	let mut buf = end.bytes.0.clone();
+	if !buf.is_empty() && de::game_end(&mut &buf[..]).is_ok_and(|e| e == *end) {
+		return Ok(buf);
+	}
+	if buf.is_empty() {
+		buf = vec![0xff; game::End::size(version)];
+	}
+
+	buf[0] = end.method as u8;
+	// v2.0
+	if let Some(lras_initiator) = end.lras_initiator.filter(|_| buf.len() >= 2) {
+		buf[1] = lras_initiator.map_or(255, u8::from);
+	}
+	// v3.13
+	if let Some(players) = end.players.as_ref().filter(|_| buf.len() >= 6) {
+		buf[2..6].fill(0xff);
+		for p in players {
+			buf[2 + p.port as usize] = p.placement;
+		}
+	}
+
+	Ok(buf)
+}
+
+fn game_start<W: Write>(w: &mut W, bytes: &[u8]) -> Result<()> {
 	w.write_u8(Event::GameStart as u8)?;
-	Ok(w.write_all(&s.bytes.0)?)
+	Ok(w.write_all(bytes)?)
 }
 
-fn game_end<W: Write>(w: &mut W, e: &game::End, _ver: slippi::Version) -> Result<()> {
+fn game_end<W: Write>(w: &mut W, bytes: &[u8]) -> Result<()> {
 	w.write_u8(Event::GameEnd as u8)?;
-	Ok(w.write_all(&e.bytes.0)?)
+	Ok(w.write_all(bytes)?)
+}
+
+fn unknown_event<W: Write>(w: &mut W, e: &UnknownEvent) -> Result<()> {
+	w.write_u8(e.code)?;
+	Ok(w.write_all(&e.bytes)?)
+}
+
+/// Writes the raw event stream in `buf`, with `unknown` events spliced back
+/// in at their original positions.
+fn interleave_unknown_events<W: Write>(
+	w: &mut W,
+	buf: &[u8],
+	payload_sizes: &PayloadSizes,
+	unknown: &[UnknownEvent],
+) -> Result<()> {
+	let sizes: HashMap<u8, u16> = payload_sizes.sizes.iter().copied().collect();
+	let mut unknown = unknown.iter().peekable();
+	let (mut pos, mut index) = (0, 0);
+	while pos < buf.len() {
+		while let Some(e) = unknown.next_if(|e| e.index <= index) {
+			unknown_event(w, e)?;
+		}
+		let size = sizes
+			.get(&buf[pos])
+			.ok_or_else(|| err!("missing payload size for event {:#04x}", buf[pos]))?;
+		let len = 1 + *size as usize;
+		w.write_all(&buf[pos..pos + len])?;
+		pos += len;
+		index += 1;
+	}
+	for e in unknown {
+		unknown_event(w, e)?;
+	}
+	Ok(())
 }
 
 #[derive(Debug)]
@@ -144,27 +420,10 @@ fn gecko_codes_size(gecko_codes: &GeckoCodes) -> u32 {
 	num_blocks * (512 + 5)
 }
 
-/// Writes a replay to `w` in Slippi (`.slp`) format.
-///
-/// Returns an error if the game's version is higher than `MAX_SUPPORTED_VERSION`.
-pub fn write<W: Write>(w: &mut W, game: &Game) -> Result<()> {
-	slippi::assert_max_version(game.start.slippi.version)?;
-
-	let payload_sizes = payload_sizes(game);
-
-	w.write_all(&slippi::FILE_SIGNATURE)?;
-	w.write_u32::<BE>(payload_sizes.raw_size(game))?;
-
-	w.write_u8(Event::Payloads as u8)?;
-	// see "off-by-one" note in `de::parse_payloads`
-	w.write_u8((payload_sizes.sizes.len() * 3 + 1).try_into().unwrap())?;
-	for (event, size) in payload_sizes.sizes {
-		w.write_u8(event)?;
-		w.write_u16::<BE>(size)?;
-	}
-
+/// Writes all events after Event Payloads, except for unknown events.
+fn events<W: Write>(w: &mut W, game: &Game, raw: &RawEvents) -> Result<()> {
 	let ver = game.start.slippi.version;
-	game_start(w, &game.start, ver)?;
+	game_start(w, &raw.start)?;
 
 	if let Some(codes) = &game.gecko_codes {
 		gecko_codes(w, codes)?;
@@ -172,13 +431,49 @@ pub fn write<W: Write>(w: &mut W, game: &Game) -> Result<()> {
 
 	game.frames.write(w, ver)?;
 
-	if let Some(end) = &game.end {
-		game_end(w, end, ver)?;
-		if game.quirks.map_or(false, |q| q.double_game_end) {
-			game_end(w, end, ver)?;
+	if let Some(end) = &raw.end {
+		game_end(w, end)?;
+		if game.quirks.as_ref().map_or(false, |q| q.double_game_end) {
+			game_end(w, end)?;
 		}
 	}
 
+	Ok(())
+}
+
+/// Writes a replay to `w` in Slippi (`.slp`) format.
+pub fn write<W: Write>(w: &mut W, game: &Game) -> Result<()> {
+	let raw = RawEvents {
+		start: start_bytes(&game.start)?,
+		end: game
+			.end
+			.as_ref()
+			.map(|e| end_bytes(e, game.start.slippi.version))
+			.transpose()?,
+	};
+	let payload_sizes = payload_sizes(game, &raw)?;
+	// see "off-by-one" note in `de::parse_payloads`
+	let payloads_size = u8::try_from(payload_sizes.sizes.len() * 3 + 1)
+		.map_err(|_| err!("too many event types: {}", payload_sizes.sizes.len()))?;
+
+	w.write_all(&slippi::FILE_SIGNATURE)?;
+	w.write_u32::<BE>(payload_sizes.raw_size(game))?;
+
+	w.write_u8(Event::Payloads as u8)?;
+	w.write_u8(payloads_size)?;
+	for (event, size) in &payload_sizes.sizes {
+		w.write_u8(*event)?;
+		w.write_u16::<BE>(*size)?;
+	}
+
+	if game.unknown_events.is_empty() {
+		events(w, game, &raw)?;
+	} else {
+		let mut buf = Vec::new();
+		events(&mut buf, game, &raw)?;
+		interleave_unknown_events(w, &buf, &payload_sizes, &game.unknown_events)?;
+	}
+
 	if let Some(metadata) = &game.metadata {
 		w.write_all(&[
 			0x55, 0x08, 0x6d, 0x65, 0x74, 0x61, 0x64, 0x61, 0x74, 0x61, 0x7b,
@@ -3,7 +3,10 @@ use std::io::Read;
 use byteorder::{BigEndian, ReadBytesExt};
 use serde_json::{Map, Value};
 
-use crate::io::Result;
+use crate::io::{ubjson, Result};
+
+/// How deeply containers may nest in a [`read_value`] value.
+const MAX_DEPTH: usize = 32;
 
 fn to_utf8<R: Read>(r: &mut R) -> Result<String> {
 	let length = r.read_u8()?;
@@ -48,3 +51,146 @@ pub(crate) fn read_map<R: Read>(r: &mut R) -> Result<Map<String, Value>> {
 	} {}
 	Ok(m)
 }
+
+fn read_int(r: &mut &[u8], marker: u8) -> Result<i64> {
+	Ok(match marker {
+		b'i' => r.read_i8()? as i64,
+		b'U' => r.read_u8()? as i64,
+		b'I' => r.read_i16::<BigEndian>()? as i64,
+		b'l' => r.read_i32::<BigEndian>()? as i64,
+		b'L' => r.read_i64::<BigEndian>()?,
+		m => return Err(err!("expected UBJSON integer, got: {:#02x}", m)),
+	})
+}
+
+/// Reads a length or count, which can't be more than the bytes remaining
+/// (since each byte/element/entry takes at least one).
+fn read_len(r: &mut &[u8]) -> Result<usize> {
+	let marker = r.read_u8()?;
+	let len = read_int(r, marker)?;
+	match usize::try_from(len) {
+		Ok(len) if len <= r.len() => Ok(len),
+		_ => Err(err!("invalid UBJSON length: {}", len)),
+	}
+}
+
+fn read_bytes(r: &mut &[u8], len: usize) -> Result<Vec<u8>> {
+	let mut buf = vec![0; len];
+	r.read_exact(&mut buf)?;
+	Ok(buf)
+}
+
+fn read_string(r: &mut &[u8]) -> Result<String> {
+	let len = read_len(r)?;
+	Ok(String::from_utf8(read_bytes(r, len)?)?)
+}
+
+/// Reads a general UBJSON value, as opposed to the subset used by replay
+/// metadata (see [`read_map`]).
+pub(crate) fn read_value(r: &mut &[u8]) -> Result<ubjson::Value> {
+	let marker = r.read_u8()?;
+	value(r, marker, 0)
+}
+
+/// Reads a value whose type marker is `marker`, nested `depth` containers deep.
+fn value(r: &mut &[u8], marker: u8, depth: usize) -> Result<ubjson::Value> {
+	use ubjson::Value::*;
+	Ok(match marker {
+		b'Z' => Null,
+		b'T' => Bool(true),
+		b'F' => Bool(false),
+		b'i' | b'U' | b'I' | b'l' | b'L' => Int(read_int(r, marker)?),
+		b'd' => Float(r.read_f32::<BigEndian>()? as f64),
+		b'D' => Float(r.read_f64::<BigEndian>()?),
+		b'C' => Str((r.read_u8()? as char).to_string()),
+		b'S' => Str(read_string(r)?),
+		b'[' | b'{' if depth >= MAX_DEPTH => {
+			return Err(err!("UBJSON nested too deeply"));
+		}
+		b'[' => array(r, depth + 1)?,
+		b'{' => object(r, depth + 1)?,
+		m => return Err(err!("unexpected UBJSON value type: {:#02x}", m)),
+	})
+}
+
+/// Reads the optional type (`$`) & count (`#`) of a container,
+/// returning them along with the next marker (if we didn't consume it).
+fn container_header(r: &mut &[u8]) -> Result<(Option<u8>, Option<usize>, Option<u8>)> {
+	let mut marker = r.read_u8()?;
+	let mut r#type = None;
+	if marker == b'$' {
+		r#type = match r.read_u8()? {
+			// elements of these types take no bytes, so their count is unbounded
+			t @ (b'Z' | b'T' | b'F' | b'N') => {
+				return Err(err!("unsupported UBJSON container type: {:#02x}", t))
+			}
+			t => Some(t),
+		};
+		marker = r.read_u8()?;
+		if marker != b'#' {
+			return Err(err!("UBJSON container type without count"));
+		}
+	}
+	match marker {
+		b'#' => Ok((r#type, Some(read_len(r)?), None)),
+		m => Ok((r#type, None, Some(m))),
+	}
+}
+
+fn array(r: &mut &[u8], depth: usize) -> Result<ubjson::Value> {
+	use ubjson::Value::*;
+	match container_header(r)? {
+		(Some(b'U') | Some(b'i'), Some(count), _) => Ok(Bytes(read_bytes(r, count)?)),
+		(Some(t), Some(count), _) => (0..count)
+			.map(|_| value(r, t, depth))
+			.collect::<Result<_>>()
+			.map(Array),
+		(None, Some(count), _) => (0..count)
+			.map(|_| {
+				let m = r.read_u8()?;
+				value(r, m, depth)
+			})
+			.collect::<Result<_>>()
+			.map(Array),
+		(_, None, Some(mut m)) => {
+			let mut values = Vec::new();
+			while m != b']' {
+				values.push(value(r, m, depth)?);
+				m = r.read_u8()?;
+			}
+			Ok(Array(values))
+		}
+		_ => unreachable!(),
+	}
+}
+
+fn object(r: &mut &[u8], depth: usize) -> Result<ubjson::Value> {
+	let mut entries = Vec::new();
+	match container_header(r)? {
+		(Some(_), _, _) => return Err(err!("strongly-typed UBJSON objects are unsupported")),
+		(None, Some(count), _) => {
+			for _ in 0..count {
+				let key = read_string(r)?;
+				let m = r.read_u8()?;
+				entries.push((key, value(r, m, depth)?));
+			}
+		}
+		(None, None, Some(mut m)) => {
+			// keys have no type marker, so what we thought was a marker is
+			// actually the key length's marker (or the end of the object)
+			while m != b'}' {
+				let len = read_int(r, m)?;
+				let len = match usize::try_from(len) {
+					Ok(len) if len <= r.len() => len,
+					_ => return Err(err!("invalid UBJSON key length: {}", len)),
+				};
+				let key = String::from_utf8(read_bytes(r, len)?)?;
+				let m2 = r.read_u8()?;
+				entries.push((key, value(r, m2, depth)?));
+				m = r.read_u8()?;
+			}
+		}
+		_ => unreachable!(),
+	}
+	Ok(ubjson::Value::Object(entries))
+}
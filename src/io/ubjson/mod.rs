@@ -1,5 +1,54 @@
 pub(crate) mod de;
 pub(crate) mod ser;
 
-pub(crate) use de::read_map;
-pub(crate) use ser::write_map;
+pub(crate) use de::{read_map, read_value};
+pub(crate) use ser::{write_map, write_value};
+
+/// A general UBJSON value, for when we need more than the subset that
+/// replay metadata uses (e.g. for the console protocol).
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Value {
+	Null,
+	Bool(bool),
+	Int(i64),
+	Float(f64),
+	Str(String),
+	/// Strongly-typed array of `u8`/`i8`.
+	Bytes(Vec<u8>),
+	Array(Vec<Value>),
+	Object(Vec<(String, Value)>),
+}
+
+impl Value {
+	pub(crate) fn get(&self, key: &str) -> Option<&Value> {
+		match self {
+			Value::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+			_ => None,
+		}
+	}
+
+	pub(crate) fn as_int(&self) -> Option<i64> {
+		match self {
+			Value::Int(i) => Some(*i),
+			_ => None,
+		}
+	}
+
+	pub(crate) fn as_str(&self) -> Option<&str> {
+		match self {
+			Value::Str(s) => Some(s),
+			_ => None,
+		}
+	}
+
+	pub(crate) fn as_bytes(&self) -> Option<Vec<u8>> {
+		match self {
+			Value::Bytes(b) => Some(b.clone()),
+			Value::Array(a) => a
+				.iter()
+				.map(|v| v.as_int().and_then(|i| u8::try_from(i).ok()))
+				.collect(),
+			_ => None,
+		}
+	}
+}
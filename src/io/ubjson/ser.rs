@@ -3,6 +3,8 @@ use std::io::{Result, Write};
 use byteorder::{BigEndian, WriteBytesExt};
 use serde_json::{Map, Value};
 
+use crate::io::ubjson;
+
 fn write_utf8<W: Write>(w: &mut W, s: &str) -> Result<()> {
 	write!(w, "U")?;
 	w.write_u8(s.len().try_into().unwrap())?;
@@ -32,3 +34,63 @@ pub(crate) fn write_map<W: Write>(w: &mut W, map: &Map<String, Value>) -> Result
 	}
 	Ok(())
 }
+
+fn write_int<W: Write>(w: &mut W, i: i64) -> Result<()> {
+	if let Ok(i) = u8::try_from(i) {
+		w.write_u8(b'U')?;
+		w.write_u8(i)
+	} else if let Ok(i) = i16::try_from(i) {
+		w.write_u8(b'I')?;
+		w.write_i16::<BigEndian>(i)
+	} else if let Ok(i) = i32::try_from(i) {
+		w.write_u8(b'l')?;
+		w.write_i32::<BigEndian>(i)
+	} else {
+		w.write_u8(b'L')?;
+		w.write_i64::<BigEndian>(i)
+	}
+}
+
+fn write_string<W: Write>(w: &mut W, s: &str) -> Result<()> {
+	write_int(w, s.len() as i64)?;
+	w.write_all(s.as_bytes())
+}
+
+/// Writes a general UBJSON value (see [`super::read_value`]).
+pub(crate) fn write_value<W: Write>(w: &mut W, value: &ubjson::Value) -> Result<()> {
+	use ubjson::Value::*;
+	match value {
+		Null => w.write_u8(b'Z'),
+		Bool(true) => w.write_u8(b'T'),
+		Bool(false) => w.write_u8(b'F'),
+		Int(i) => write_int(w, *i),
+		Float(f) => {
+			w.write_u8(b'D')?;
+			w.write_f64::<BigEndian>(*f)
+		}
+		Str(s) => {
+			w.write_u8(b'S')?;
+			write_string(w, s)
+		}
+		Bytes(b) => {
+			w.write_all(b"[$U#")?;
+			write_int(w, b.len() as i64)?;
+			w.write_all(b)
+		}
+		Array(values) => {
+			w.write_u8(b'[')?;
+			for v in values {
+				write_value(w, v)?;
+			}
+			w.write_u8(b']')
+		}
+		Object(entries) => {
+			w.write_u8(b'{')?;
+			for (k, v) in entries {
+				write_string(w, k)?;
+				write_value(w, v)?;
+			}
+			w.write_u8(b'}')
+		}
+	}
+}
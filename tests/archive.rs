@@ -0,0 +1,140 @@
+use std::{
+	fs,
+	io::{Cursor, Write},
+};
+
+use peppi::io::{archive, ErrorKind};
+
+mod common;
+use common::get_path;
+
+const ENTRIES: [(&str, &str); 4] = [
+	("replays/v3.12.slp", "v3.12"),
+	("replays/corrupt.slp", "corrupt"),
+	("replays/README.txt", "v3.13"),
+	("replays/Game.SLP", "v3.16"),
+];
+
+/// Frame counts (or error kinds) we expect for the replays in `ENTRIES`.
+fn expected() -> Vec<(String, Result<usize, ErrorKind>)> {
+	vec![
+		("replays/v3.12.slp".to_string(), Ok(124)),
+		(
+			"replays/corrupt.slp".to_string(),
+			Err(ErrorKind::UnexpectedEof),
+		),
+		("replays/Game.SLP".to_string(), Ok(315)),
+	]
+}
+
+fn tar() -> Vec<u8> {
+	let mut b = tar::Builder::new(Vec::new());
+	let mut header = tar::Header::new_gnu();
+	header.set_entry_type(tar::EntryType::Directory);
+	header.set_size(0);
+	b.append_data(&mut header, "replays/", &[][..]).unwrap();
+	for (name, file) in ENTRIES {
+		b.append_path_with_name(get_path(file), name).unwrap();
+	}
+	b.into_inner().unwrap()
+}
+
+fn collect(r: &[u8]) -> Vec<(String, Result<usize, ErrorKind>)> {
+	let mut results = vec![];
+	archive::read(Cursor::new(r), None, |name, result| {
+		results.push((
+			name.to_string(),
+			result.map(|g| g.frames.len()).map_err(|e| e.kind()),
+		))
+	})
+	.unwrap();
+	results
+}
+
+#[test]
+fn tar_archive() {
+	let bytes = tar();
+	assert_eq!(collect(&bytes), expected());
+
+	let mut e = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+	e.write_all(&bytes).unwrap();
+	assert_eq!(collect(&e.finish().unwrap()), expected());
+
+	assert_eq!(
+		collect(&zstd::encode_all(&bytes[..], 0).unwrap()),
+		expected()
+	);
+}
+
+#[test]
+fn tar_stream() {
+	// `read_tar` only needs `Read`, so it works on (non-seekable) slices
+	let mut e = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+	e.write_all(&tar()).unwrap();
+	let bytes = e.finish().unwrap();
+	let mut results = vec![];
+	archive::read_tar(&bytes[..], None, |name, result| {
+		results.push((
+			name.to_string(),
+			result.map(|g| g.frames.len()).map_err(|e| e.kind()),
+		))
+	})
+	.unwrap();
+	assert_eq!(results, expected());
+}
+
+#[cfg(not(feature = "xz"))]
+#[test]
+fn xz_without_feature() {
+	let mut bytes = vec![0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00];
+	bytes.resize(1024, 0);
+	let err = archive::read(Cursor::new(&bytes), None, |_, _| ()).unwrap_err();
+	assert_eq!(err.kind(), ErrorKind::InvalidData);
+	assert!(err.to_string().contains("`xz` feature"), "{}", err);
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn zip_archive() {
+	let mut w = zip::ZipWriter::new(Cursor::new(Vec::new()));
+	w.add_directory("replays/", zip::write::SimpleFileOptions::default())
+		.unwrap();
+	for (i, (name, file)) in ENTRIES.into_iter().enumerate() {
+		let method = match i % 2 {
+			0 => zip::CompressionMethod::Stored,
+			_ => zip::CompressionMethod::Deflated,
+		};
+		let opts = zip::write::SimpleFileOptions::default().compression_method(method);
+		w.start_file(name, opts).unwrap();
+		w.write_all(&fs::read(get_path(file)).unwrap()).unwrap();
+	}
+	let bytes = w.finish().unwrap().into_inner();
+	assert_eq!(collect(&bytes), expected());
+}
+
+#[test]
+fn not_an_archive() {
+	let bytes = fs::read(get_path("v3.12")).unwrap();
+	let err = archive::read(Cursor::new(&bytes), None, |_, _| ()).unwrap_err();
+	assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn oversized_entry() {
+	// header claims far more data than there is
+	let mut header = tar::Header::new_gnu();
+	header.set_path("huge.slp").unwrap();
+	header.set_size(1 << 60);
+	header.set_cksum();
+	let mut bytes = header.as_bytes().to_vec();
+	bytes.extend(fs::read(get_path("v3.12")).unwrap());
+
+	let mut results = vec![];
+	let err = archive::read(Cursor::new(&bytes), None, |name, result| {
+		results.push((name.to_string(), result.map(|g| g.frames.len())))
+	})
+	.unwrap_err();
+	// the entry is parsed from whatever data there is, then the archive ends early
+	assert!(matches!(results.as_slice(), [(name, Ok(124))] if name == "huge.slp"));
+	assert_eq!(err.kind(), ErrorKind::Io);
+}
@@ -1,12 +1,18 @@
-use arrow2::io::json::write as json_write;
+use arrow2::{
+	array::{Array, BooleanArray, PrimitiveArray, StructArray},
+	io::json::write as json_write,
+};
 use pretty_assertions::assert_eq;
 use serde_json::json;
-use std::io::BufWriter;
+use std::{
+	fs,
+	io::{BufWriter, Cursor},
+};
 
-use peppi::{frame::PortOccupancy, game::Port};
+use peppi::{frame::PortOccupancy, game::Port, io::slippi};
 
 mod common;
-use common::game;
+use common::{game, get_path};
 
 #[test]
 fn into_struct_array() {
@@ -347,3 +353,90 @@ fn into_struct_array() {
 		);
 	}
 }
+
+fn tidy_column<'a, T: 'static>(tidy: &'a StructArray, name: &str) -> &'a T {
+	let i = tidy.fields().iter().position(|f| f.name == name).unwrap();
+	tidy.values()[i].as_any().downcast_ref::<T>().unwrap()
+}
+
+#[test]
+fn tidy() {
+	let expected = game("ics2");
+	let version = expected.start.slippi.version;
+	let len = expected.frames.len();
+	let p1 = &expected.frames.ports[0];
+	let follower = p1.follower.as_ref().unwrap();
+	let follower_valid = follower.validity.as_ref().unwrap();
+	assert!(follower_valid.unset_bits() > 0);
+
+	let tidy = game("ics2").frames.into_tidy(version);
+	assert_eq!(
+		tidy.fields()[..6]
+			.iter()
+			.map(|f| f.name.as_str())
+			.collect::<Vec<_>>(),
+		[
+			"frame_id",
+			"port",
+			"is_follower",
+			"character",
+			"pre.random_seed",
+			"pre.state"
+		],
+	);
+	assert!(tidy.fields()[3].is_nullable);
+	assert!(!tidy.fields().iter().any(|f| f.name == "post.character"));
+	// rows for invalid followers are skipped
+	assert_eq!(
+		tidy.len(),
+		len * expected.frames.ports.len() + len - follower_valid.unset_bits()
+	);
+
+	let frame_ids = tidy_column::<PrimitiveArray<i32>>(&tidy, "frame_id");
+	let ports = tidy_column::<PrimitiveArray<u8>>(&tidy, "port");
+	let is_follower = tidy_column::<BooleanArray>(&tidy, "is_follower");
+	let characters = tidy_column::<PrimitiveArray<u8>>(&tidy, "character");
+	let x = tidy_column::<PrimitiveArray<f32>>(&tidy, "post.position.x");
+
+	// ordered by frame, then port, then leader/follower
+	let mut row = 0;
+	for i in 0..len {
+		for port in &expected.frames.ports {
+			let mut data = vec![(false, &port.leader)];
+			if let Some(f) = port.follower.as_ref() {
+				if f.validity.as_ref().unwrap().get_bit(i) {
+					data.push((true, f));
+				}
+			}
+			for (follower, data) in data {
+				assert_eq!(frame_ids.value(row), expected.frames.id.value(i));
+				assert_eq!(ports.value(row), u8::from(port.port));
+				assert_eq!(is_follower.value(row), follower);
+				assert_eq!(characters.value(row), data.post.character.value(i));
+				assert_eq!(x.value(row), data.post.position.x.value(i));
+				assert!(x.is_valid(row));
+				row += 1;
+			}
+		}
+	}
+	assert_eq!(row, tidy.len());
+}
+
+#[test]
+fn tidy_incomplete_frame() {
+	// cut off in the middle of the first Frame Post
+	let bytes = fs::read(get_path("v3.12")).unwrap();
+	let opts = slippi::de::Opts {
+		lenient: true,
+		..Default::default()
+	};
+	let game = slippi::read(Cursor::new(&bytes[..47940]), Some(&opts)).unwrap();
+	assert_eq!(game.frames.len(), 1);
+
+	let tidy = game.frames.into_tidy(game.start.slippi.version);
+	assert_eq!(tidy.len(), 2);
+	let characters = tidy_column::<PrimitiveArray<u8>>(&tidy, "character");
+	assert_eq!(characters.null_count(), 2);
+	let x = tidy_column::<PrimitiveArray<f32>>(&tidy, "pre.position.x");
+	assert_eq!(x.null_count(), 0);
+}
@@ -0,0 +1,136 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use peppi::io::{batch, peppi as io_peppi, ErrorKind};
+
+mod common;
+use common::{game, get_path};
+
+#[test]
+fn read_dir() {
+	let expected: HashMap<_, _> = fs::read_dir("tests/data")
+		.unwrap()
+		.map(|e| e.unwrap().path())
+		.map(|p| {
+			let frames = peppi::io::slippi::read(fs::File::open(&p).unwrap(), None)
+				.map(|g| g.frames.len())
+				.map_err(|e| e.kind());
+			(p, frames)
+		})
+		.collect();
+
+	for workers in [0, 1, 4] {
+		let opts = batch::Opts {
+			workers,
+			..Default::default()
+		};
+		let actual: HashMap<_, _> = batch::read_dir("tests/data", opts)
+			.unwrap()
+			.map(|(p, r)| (p, r.map(|g| g.frames.len()).map_err(|e| e.kind())))
+			.collect();
+		assert_eq!(actual, expected);
+	}
+}
+
+#[test]
+fn read_glob() {
+	let mut paths: Vec<_> = batch::read_glob("tests/data/v3.1*.slp", Default::default())
+		.unwrap()
+		.map(|(p, r)| {
+			r.unwrap();
+			p
+		})
+		.collect();
+	paths.sort();
+	assert_eq!(
+		paths,
+		[get_path("v3.12"), get_path("v3.13"), get_path("v3.16")]
+	);
+
+	assert_eq!(
+		batch::read_glob("tests/data/[", Default::default())
+			.err()
+			.map(|e| e.kind()),
+		Some(ErrorKind::InvalidData)
+	);
+}
+
+#[cfg(unix)]
+#[test]
+fn read_glob_unreadable() {
+	use std::os::unix::fs::PermissionsExt;
+
+	let dir = std::env::temp_dir().join(format!("peppi-glob-{}", std::process::id()));
+	let locked = dir.join("locked");
+	fs::create_dir_all(&locked).unwrap();
+	fs::copy(get_path("v3.12"), locked.join("game.slp")).unwrap();
+	fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+	// if we can read it anyway (e.g. as root), there's nothing to test
+	if fs::read_dir(&locked).is_err() {
+		let results: Vec<_> =
+			batch::read_glob(&format!("{}/**/*.slp", dir.display()), Default::default())
+				.unwrap()
+				.map(|(p, r)| (p, r.map(|_| ()).map_err(|e| e.kind())))
+				.collect();
+		assert_eq!(results, [(locked.clone(), Err(ErrorKind::Io))]);
+	}
+
+	fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+	fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn formats() {
+	let dir = std::env::temp_dir().join(format!("peppi-batch-{}", std::process::id()));
+	fs::create_dir_all(&dir).unwrap();
+	let slpp = dir.join("game.slpp");
+	let mut buf = Vec::new();
+	io_peppi::write(&mut buf, game("v3.12"), Default::default()).unwrap();
+	fs::write(&slpp, buf).unwrap();
+	fs::copy(get_path("v3.12"), dir.join("game.slp")).unwrap();
+	fs::write(dir.join("notes.txt"), "not a replay").unwrap();
+
+	let opts = batch::Opts {
+		slippi: Some(peppi::io::slippi::de::Opts {
+			skip_frames: true,
+			..Default::default()
+		}),
+		..Default::default()
+	};
+	let mut results: Vec<_> = batch::read_dir(&dir, opts)
+		.unwrap()
+		.map(|(p, r)| (p, r.unwrap().frames.len()))
+		.collect();
+	results.sort();
+	fs::remove_dir_all(&dir).unwrap();
+	assert_eq!(
+		results,
+		[(dir.join("game.slp"), 0), (dir.join("game.slpp"), 124)]
+	);
+}
+
+#[test]
+fn abort_on_error() {
+	let paths: Vec<PathBuf> = ["v3.12", "corrupt", "v3.13", "v3.16"]
+		.into_iter()
+		.map(get_path)
+		.collect();
+
+	let opts = batch::Opts {
+		workers: 1,
+		abort_on_error: true,
+		..Default::default()
+	};
+	let results: Vec<_> = batch::read_all(paths.clone(), opts).collect();
+	assert_eq!(results.len(), 2);
+	assert!(results[0].1.is_ok());
+	assert_eq!(results[1].0, get_path("corrupt"));
+	assert!(results[1].1.is_err());
+
+	// without aborting, we get everything
+	let opts = batch::Opts {
+		workers: 2,
+		..Default::default()
+	};
+	assert_eq!(batch::read_all(paths, opts).count(), 4);
+}
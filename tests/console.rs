@@ -0,0 +1,176 @@
+use std::{
+	fs,
+	io::{self, Read, Write},
+};
+
+use peppi::io::{
+	slippi::{
+		console::{mock, Client},
+		parser::ParsedEvent,
+	},
+	ErrorKind,
+};
+
+mod common;
+use common::{game, get_path};
+
+/// Receives everything from a mock console sending `name`, in chunks of `chunk_size`.
+fn receive(
+	name: &str,
+	chunk_size: usize,
+	cursor: u64,
+) -> (Client<std::net::TcpStream>, Vec<ParsedEvent>) {
+	let bytes = fs::read(get_path(name)).unwrap();
+	let (addr, server) = mock::spawn(bytes, chunk_size).unwrap();
+	let stream = std::net::TcpStream::connect(addr).unwrap();
+	let mut client = Client::with_cursor(stream, cursor, None).unwrap();
+	let mut events = vec![];
+	while let Some(evs) = client.next_events().unwrap() {
+		events.extend(evs);
+	}
+	server.join().unwrap().unwrap();
+	(client, events)
+}
+
+#[test]
+fn same_as_read() {
+	for name in ["v0.1", "v3.12", "unknown_event"] {
+		let expected = game(name);
+		let version = expected.start.slippi.version;
+		for chunk_size in [7, 100, 4096, 1 << 20] {
+			let (mut client, events) = receive(name, chunk_size, 0);
+			assert_eq!(client.nick(), Some(mock::NICK));
+			assert_eq!(client.version(), Some(mock::VERSION));
+			assert!(client.is_game_done());
+
+			let frames: Vec<_> = events
+				.iter()
+				.filter_map(|e| match e {
+					ParsedEvent::Frame(f) => Some(f),
+					_ => None,
+				})
+				.collect();
+			assert_eq!(frames.len(), expected.frames.len());
+			for (i, f) in frames.into_iter().enumerate() {
+				assert_eq!(f, &expected.frames.transpose_one(i, version));
+			}
+			assert_eq!(
+				client.state().unwrap().frames().len(),
+				expected.frames.len()
+			);
+
+			let game = client.take_game().unwrap();
+			assert_eq!(game.start, expected.start);
+			assert_eq!(game.end, expected.end);
+			assert_eq!(game.metadata, None);
+			assert!(client.state().is_none());
+		}
+	}
+}
+
+#[test]
+fn connect() {
+	let bytes = fs::read(get_path("v3.12")).unwrap();
+	let (addr, server) = mock::spawn(bytes, 1000).unwrap();
+	let mut client = Client::connect(addr, None).unwrap();
+	while client.next_events().unwrap().is_some() {}
+	server.join().unwrap().unwrap();
+	assert_eq!(client.take_game().unwrap().frames.len(), 124);
+}
+
+#[test]
+fn invalid_cursor() {
+	// the console starts over if we ask for data it doesn't have
+	let (mut client, events) = receive("v3.12", 1000, u64::MAX >> 1);
+	assert!(matches!(events.first(), Some(ParsedEvent::Start(_))));
+	assert_eq!(client.take_game().unwrap().frames.len(), 124);
+	let bytes = fs::read(get_path("v3.12")).unwrap();
+	let raw_len = u32::from_be_bytes(bytes[11..15].try_into().unwrap());
+	assert_eq!(client.cursor(), raw_len as u64);
+}
+
+/// A fake connection, that discards writes and reads from a canned response.
+struct Canned(io::Cursor<Vec<u8>>);
+
+impl Read for Canned {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		self.0.read(buf)
+	}
+}
+
+impl Write for Canned {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+/// Encodes a `{type, payload}` message with the given cursor fields.
+fn message(r#type: u8, cursors: &[(&str, u64)], data: Option<&[u8]>) -> Vec<u8> {
+	let mut m = b"{U\x04typeU".to_vec();
+	m.push(r#type);
+	m.extend(b"U\x07payload{");
+	for (k, v) in cursors {
+		m.extend([b'U', k.len() as u8]);
+		m.extend(k.as_bytes());
+		m.extend(b"[$U#U\x08");
+		m.extend(v.to_be_bytes());
+	}
+	if let Some(data) = data {
+		m.extend(b"U\x04data[$U#l");
+		m.extend((data.len() as u32).to_be_bytes());
+		m.extend(data);
+	}
+	m.extend(b"}}");
+	framed(&m)
+}
+
+/// Prefixes `m` with its size.
+fn framed(m: &[u8]) -> Vec<u8> {
+	let mut framed = (m.len() as u32).to_be_bytes().to_vec();
+	framed.extend(m);
+	framed
+}
+
+#[test]
+fn cursor_mismatch() {
+	let mut response = message(1, &[("pos", 10)], None);
+	response.extend(message(3, &[], None));
+	response.extend(message(2, &[("pos", 10), ("nextPos", 11)], Some(&[0x35])));
+	response.extend(message(2, &[("pos", 12), ("nextPos", 13)], Some(&[0x10])));
+	let mut client = Client::new(Canned(io::Cursor::new(response)), None).unwrap();
+	assert_eq!(client.cursor(), 10);
+	assert_eq!(client.nick(), None);
+	assert!(client.next_events().unwrap().unwrap().is_empty());
+	assert_eq!(client.cursor(), 11);
+	let err = client.next_events().unwrap_err();
+	assert_eq!(err.kind(), ErrorKind::InvalidData);
+	assert!(err.to_string().contains("cursor mismatch"));
+}
+
+#[test]
+fn malformed_messages() {
+	let mut deep = b"{U\x04typeU\x01U\x07payload".to_vec();
+	deep.extend([b'['; 10000]);
+	for m in [
+		// cursor with a count far beyond the message
+		b"{U\x04typeU\x01U\x07payload{U\x03pos[$U#L\x7f\xff\xff\xff\xff\xff\xff\xff}}".to_vec(),
+		// string with a length far beyond the message
+		b"{U\x04typeU\x01U\x04nickSl\x7f\xff\xff\xff}".to_vec(),
+		// key with a negative length
+		b"{i\xffU\x01}".to_vec(),
+		// array of nulls, which take no bytes each
+		b"{U\x04typeU\x01U\x07payload[$Z#l\x7f\xff\xff\xff}".to_vec(),
+		// nested too deeply
+		deep,
+	] {
+		let err = Client::new(Canned(io::Cursor::new(framed(&m))), None)
+			.err()
+			.unwrap();
+		assert_eq!(err.kind(), ErrorKind::InvalidData);
+		assert!(err.to_string().contains("UBJSON"), "{}", err);
+	}
+}
@@ -0,0 +1,154 @@
+use std::{
+	fs::{self, File},
+	path::{Path, PathBuf},
+};
+
+use arrow2::{
+	array::{Array, PrimitiveArray, Utf8Array},
+	chunk::Chunk,
+	datatypes::Schema,
+	io::ipc::read::{read_file_metadata, FileReader},
+};
+use pretty_assertions::assert_eq;
+
+use peppi::{
+	game::immutable::Game,
+	io::{dataset, slippi, ErrorKind},
+};
+
+mod common;
+use common::{game, get_path};
+
+fn hashed_game(name: &str) -> Game {
+	slippi::read_slice(
+		&fs::read(get_path(name)).unwrap(),
+		Some(&slippi::de::Opts {
+			compute_hash: true,
+			..Default::default()
+		}),
+	)
+	.unwrap()
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+	std::env::temp_dir().join(format!("peppi-dataset-{}-{}", name, std::process::id()))
+}
+
+fn read_arrow(path: &Path) -> (Schema, Vec<Chunk<Box<dyn Array>>>) {
+	let mut f = File::open(path).unwrap();
+	let metadata = read_file_metadata(&mut f).unwrap();
+	let schema = metadata.schema.clone();
+	let chunks = FileReader::new(f, metadata, None, None)
+		.map(|c| c.unwrap())
+		.collect();
+	(schema, chunks)
+}
+
+fn column<'a, T: 'static>(schema: &Schema, chunk: &'a Chunk<Box<dyn Array>>, name: &str) -> &'a T {
+	let i = schema.fields.iter().position(|f| f.name == name).unwrap();
+	chunk.arrays()[i].as_any().downcast_ref::<T>().unwrap()
+}
+
+#[test]
+fn game_id() {
+	let hashed = hashed_game("v3.16");
+	assert!(hashed.hash.is_some());
+	assert_eq!(dataset::game_id(&hashed), hashed.hash);
+
+	// falls back to the match ID
+	let unhashed = game("v3.16");
+	let m = unhashed.start.r#match.as_ref().unwrap();
+	assert_eq!(
+		dataset::game_id(&unhashed),
+		Some(format!("{}.{}.{}", m.id, m.game, m.tiebreaker))
+	);
+
+	// no match ID before v3.14
+	assert_eq!(dataset::game_id(&game("v3.12")), None);
+}
+
+#[test]
+fn write() {
+	let dir = temp_dir("write");
+	let names = ["v2.0", "ics2", "v3.16"];
+	let mut writer = dataset::Writer::new(&dir, None).unwrap();
+	let schema = writer.schema().clone();
+	let ids: Vec<_> = names
+		.iter()
+		.map(|name| writer.write(hashed_game(name)).unwrap())
+		.collect();
+	writer.finish().unwrap();
+
+	for (name, id) in names.iter().zip(&ids) {
+		let expected = game(name);
+		let len = expected.frames.len();
+		let path = dir
+			.join("player_frames")
+			.join(format!("{}.arrow", id.replace(':', "%3A")));
+		let (actual_schema, chunks) = read_arrow(&path);
+		assert_eq!(actual_schema, schema);
+		// one chunk per port
+		assert_eq!(chunks.len(), expected.frames.ports.len());
+		for (chunk, port) in chunks.iter().zip(&expected.frames.ports) {
+			assert_eq!(chunk.len(), len);
+			let game_ids = column::<Utf8Array<i32>>(&schema, chunk, "game_id");
+			assert!(game_ids.values_iter().all(|v| v == id));
+			let ports = column::<PrimitiveArray<u8>>(&schema, chunk, "port");
+			assert!(ports.values_iter().all(|p| *p == u8::from(port.port)));
+			let frame_ids = column::<PrimitiveArray<i32>>(&schema, chunk, "frame_id");
+			assert_eq!(frame_ids, &expected.frames.id);
+
+			let x = column::<PrimitiveArray<f32>>(&schema, chunk, "leader.post.position.x");
+			assert_eq!(x, &port.leader.post.position.x);
+
+			let follower_x =
+				column::<PrimitiveArray<f32>>(&schema, chunk, "follower.post.position.x");
+			match &port.follower {
+				Some(f) => assert_eq!(follower_x, &f.post.position.x),
+				None => assert_eq!(follower_x.null_count(), len),
+			}
+
+			// added in v3.8
+			let hitlag = column::<PrimitiveArray<f32>>(&schema, chunk, "leader.post.hitlag");
+			match &port.leader.post.hitlag {
+				Some(h) => assert_eq!(hitlag, h),
+				None => assert_eq!(hitlag.null_count(), len),
+			}
+		}
+	}
+
+	let (games_schema, chunks) = read_arrow(&dir.join("games.arrow"));
+	fs::remove_dir_all(&dir).unwrap();
+	assert_eq!(chunks.len(), 1);
+	let game_ids = column::<Utf8Array<i32>>(&games_schema, &chunks[0], "game_id");
+	assert_eq!(game_ids.values_iter().collect::<Vec<_>>(), ids);
+	let versions = column::<Utf8Array<i32>>(&games_schema, &chunks[0], "slippi_version");
+	assert_eq!(
+		versions.values_iter().collect::<Vec<_>>(),
+		["2.0.1", "3.12.0", "3.16.0"]
+	);
+	let frame_counts = column::<PrimitiveArray<u32>>(&games_schema, &chunks[0], "frame_count");
+	let expected: Vec<_> = names.iter().map(|n| game(n).frames.len() as u32).collect();
+	assert_eq!(frame_counts.values().as_slice(), expected);
+}
+
+#[test]
+fn errors() {
+	let dir = temp_dir("errors");
+	let mut writer = dataset::Writer::new(&dir, None).unwrap();
+	assert_eq!(
+		writer.write(game("v3.12")).err().map(|e| e.kind()),
+		Some(ErrorKind::InvalidData)
+	);
+	writer.write(hashed_game("v3.12")).unwrap();
+	assert_eq!(
+		writer.write(hashed_game("v3.12")).err().map(|e| e.kind()),
+		Some(ErrorKind::InvalidData)
+	);
+
+	// fields too new to be in the schema aren't silently dropped
+	let err = writer.write(hashed_game("future_version")).unwrap_err();
+	assert_eq!(err.kind(), ErrorKind::InvalidData);
+	assert!(err.to_string().contains(".trailing"), "{}", err);
+	fs::remove_dir_all(&dir).unwrap();
+}
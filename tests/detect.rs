@@ -0,0 +1,90 @@
+use std::{fs, io::Write};
+
+use peppi::io::{
+	self,
+	detect::{detect, Compression, Detected, Format},
+	peppi as io_peppi, slippi, ErrorKind,
+};
+
+mod common;
+use common::{game, get_path};
+
+fn gzip(bytes: &[u8]) -> Vec<u8> {
+	let mut e = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+	e.write_all(bytes).unwrap();
+	e.finish().unwrap()
+}
+
+fn zstd(bytes: &[u8]) -> Vec<u8> {
+	zstd::encode_all(bytes, 0).unwrap()
+}
+
+#[cfg(feature = "xz")]
+fn xz(bytes: &[u8]) -> Vec<u8> {
+	let mut e = xz2::write::XzEncoder::new(Vec::new(), 6);
+	e.write_all(bytes).unwrap();
+	e.finish().unwrap()
+}
+
+fn assert_same(bytes: &[u8]) {
+	let expected = game("v3.12");
+	let actual = io::read(bytes, None).unwrap();
+	assert_eq!(actual.start, expected.start);
+	assert_eq!(actual.end, expected.end);
+	assert_eq!(actual.metadata, expected.metadata);
+	assert_eq!(actual.frames.len(), expected.frames.len());
+}
+
+#[test]
+fn slippi() {
+	let bytes = fs::read(get_path("v3.12")).unwrap();
+	assert_eq!(detect(&bytes), Some(Detected::Replay(Format::Slippi)));
+	assert_same(&bytes);
+
+	let gz = gzip(&bytes);
+	assert_eq!(detect(&gz), Some(Detected::Compressed(Compression::Gzip)));
+	assert_same(&gz);
+
+	let zst = zstd(&bytes);
+	assert_eq!(detect(&zst), Some(Detected::Compressed(Compression::Zstd)));
+	assert_same(&zst);
+
+	#[cfg(feature = "xz")]
+	{
+		let xz = xz(&bytes);
+		assert_eq!(detect(&xz), Some(Detected::Compressed(Compression::Xz)));
+		assert_same(&xz);
+	}
+}
+
+#[test]
+fn peppi() {
+	let mut bytes = Vec::new();
+	io_peppi::write(&mut bytes, game("v3.12"), Default::default()).unwrap();
+	assert_eq!(detect(&bytes), Some(Detected::Replay(Format::Peppi)));
+	assert_same(&bytes);
+	assert_same(&gzip(&bytes));
+	assert_same(&zstd(&bytes));
+}
+
+#[test]
+fn opts() {
+	let bytes = fs::read(get_path("v3.12")).unwrap();
+	let opts = io::detect::Opts {
+		slippi: Some(slippi::de::Opts {
+			skip_frames: true,
+			..Default::default()
+		}),
+		..Default::default()
+	};
+	let game = io::read(&*zstd(&bytes), Some(&opts)).unwrap();
+	assert_eq!(game.frames.len(), 0);
+}
+
+#[test]
+fn unknown() {
+	for bytes in [&b"not a replay"[..], &[], &gzip(&gzip(b"{U\x03raw"))] {
+		let err = io::read(bytes, None).unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::InvalidData);
+	}
+}
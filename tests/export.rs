@@ -0,0 +1,184 @@
+use std::collections::HashSet;
+
+use pretty_assertions::assert_eq;
+use serde_json::{json, Value};
+
+use peppi::{
+	frame::Rollbacks,
+	io::{
+		export::{self, Opts},
+		ErrorKind,
+	},
+};
+
+mod common;
+use common::game;
+
+fn csv(name: &str, opts: Option<&Opts>) -> Vec<Vec<String>> {
+	let game = game(name);
+	let mut buf = vec![];
+	export::write_csv(&mut buf, game.frames, game.start.slippi.version, opts).unwrap();
+	String::from_utf8(buf)
+		.unwrap()
+		.lines()
+		.map(|l| l.split(',').map(String::from).collect())
+		.collect()
+}
+
+fn ndjson(name: &str, opts: Option<&Opts>) -> Vec<Value> {
+	let game = game(name);
+	let mut buf = vec![];
+	export::write_ndjson(&mut buf, game.frames, game.start.slippi.version, opts).unwrap();
+	String::from_utf8(buf)
+		.unwrap()
+		.lines()
+		.map(|l| serde_json::from_str(l).unwrap())
+		.collect()
+}
+
+#[test]
+fn csv_all_columns() {
+	let rows = csv("v3.16", None);
+	let header = &rows[0];
+	assert_eq!(header[0], "id");
+	assert!(header.contains(&"ports.P1.leader.post.position.x".to_string()));
+	assert!(!header.iter().any(|h| h.starts_with("item")));
+	assert_eq!(rows.len(), game("v3.16").frames.len() + 1);
+	assert!(rows.iter().all(|r| r.len() == header.len()));
+}
+
+#[test]
+fn csv_versioned_fields() {
+	// same ports, so same columns
+	let old = csv("v2.0", None);
+	let new = csv("v3.16", None);
+	assert_eq!(old[0], new[0]);
+
+	// hitlag was added in v3.8
+	let hitlag = old[0]
+		.iter()
+		.position(|h| h == "ports.P1.leader.post.hitlag")
+		.unwrap();
+	assert!(old[1..].iter().all(|r| r[hitlag].is_empty()));
+	assert!(new[1..].iter().all(|r| !r[hitlag].is_empty()));
+}
+
+#[test]
+fn column_selection() {
+	let opts = Opts {
+		columns: Some(vec![
+			"id".to_string(),
+			"ports.*.leader.post.position".to_string(),
+		]),
+		..Default::default()
+	};
+	let rows = csv("v3.16", Some(&opts));
+	assert_eq!(
+		rows[0],
+		[
+			"id",
+			"ports.P1.leader.post.position.x",
+			"ports.P1.leader.post.position.y",
+			"ports.P2.leader.post.position.x",
+			"ports.P2.leader.post.position.y",
+		]
+	);
+
+	let game = game("v3.16");
+	let opts = Opts {
+		columns: Some(vec!["nonexistent".to_string()]),
+		..Default::default()
+	};
+	assert_eq!(
+		export::write_csv(vec![], game.frames, game.start.slippi.version, Some(&opts))
+			.unwrap_err()
+			.kind(),
+		ErrorKind::InvalidData
+	);
+}
+
+#[test]
+fn rollbacks() {
+	let expected = game("ics2");
+	let ids: Vec<_> = expected.frames.id.values_iter().copied().collect();
+	let unique: HashSet<_> = ids.iter().collect();
+	assert!(unique.len() < ids.len());
+
+	let opts = Opts {
+		columns: Some(vec!["id".to_string()]),
+		..Default::default()
+	};
+	assert_eq!(csv("ics2", Some(&opts)).len(), ids.len() + 1);
+
+	let opts = Opts {
+		columns: Some(vec!["id".to_string()]),
+		rollbacks: Some(Rollbacks::ExceptLast),
+	};
+	let rows = csv("ics2", Some(&opts));
+	assert_eq!(rows.len(), unique.len() + 1);
+	let rollbacks = expected.frames.rollbacks(Rollbacks::ExceptLast);
+	let kept: Vec<_> = ids
+		.iter()
+		.zip(rollbacks)
+		.filter(|(_, r)| !r)
+		.map(|(id, _)| id.to_string())
+		.collect();
+	assert_eq!(
+		rows[1..].iter().map(|r| &r[0]).collect::<Vec<_>>(),
+		kept.iter().collect::<Vec<_>>()
+	);
+}
+
+#[test]
+fn ndjson_rows() {
+	let opts = Opts {
+		columns: Some(vec![
+			"id".to_string(),
+			"ports.P1.leader.post.hitlag".to_string(),
+			"ports.P1.follower.post.stocks".to_string(),
+		]),
+		..Default::default()
+	};
+	let rows = ndjson("ics2", Some(&opts));
+	let expected = game("ics2");
+	assert_eq!(rows.len(), expected.frames.len());
+	let follower = expected.frames.ports[0].follower.as_ref().unwrap();
+	let dead = (0..expected.frames.len())
+		.find(|i| !follower.validity.as_ref().unwrap().get_bit(*i))
+		.unwrap();
+	assert_eq!(
+		rows[dead],
+		json!({
+			"id": expected.frames.id.value(dead),
+			"ports.P1.leader.post.hitlag": expected.frames.ports[0].leader.post.hitlag.as_ref().unwrap().value(dead),
+			"ports.P1.follower.post.stocks": null,
+		})
+	);
+
+	// fields newer than the game's version are null
+	let rows = ndjson("v2.0", Some(&opts));
+	assert!(rows
+		.iter()
+		.all(|r| r["ports.P1.leader.post.hitlag"].is_null()));
+}
+
+#[test]
+fn items() {
+	let opts = Opts {
+		columns: Some(vec!["id".to_string(), "item".to_string()]),
+		..Default::default()
+	};
+	let rows = ndjson("items", Some(&opts));
+	assert!(rows.iter().any(|r| r["item"].as_array().unwrap().len() > 0));
+	let item = rows.iter().find_map(|r| r["item"].get(0)).unwrap();
+	assert!(item["type"].is_number());
+	assert!(item["position"]["x"].is_number());
+
+	let game = game("items");
+	assert_eq!(
+		export::write_csv(vec![], game.frames, game.start.slippi.version, Some(&opts))
+			.unwrap_err()
+			.kind(),
+		ErrorKind::InvalidData
+	);
+}
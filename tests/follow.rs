@@ -0,0 +1,109 @@
+use std::{
+	fs,
+	io::{Seek, SeekFrom, Write},
+	path::PathBuf,
+	thread,
+	time::Duration,
+};
+
+use peppi::io::slippi::{
+	follow::{Follower, Opts},
+	parser::ParsedEvent,
+};
+
+mod common;
+use common::{game, get_path};
+
+fn temp_path(name: &str) -> PathBuf {
+	std::env::temp_dir().join(format!("peppi-follow-{}-{}.slp", name, std::process::id()))
+}
+
+fn opts(idle_timeout: Option<Duration>) -> Option<Opts> {
+	Some(Opts {
+		poll_interval: Duration::from_millis(1),
+		idle_timeout,
+		..Default::default()
+	})
+}
+
+/// Writes `bytes` to `path` in chunks, the way Dolphin does: with a raw
+/// event stream size of 0 until the end of the game.
+fn write_slowly(path: PathBuf, bytes: Vec<u8>, chunk_size: usize) -> thread::JoinHandle<()> {
+	thread::spawn(move || {
+		let mut f = fs::OpenOptions::new().write(true).open(path).unwrap();
+		let mut header = bytes[..15].to_vec();
+		header[11..].fill(0);
+		f.write_all(&header).unwrap();
+		for chunk in bytes[15..].chunks(chunk_size) {
+			f.write_all(chunk).unwrap();
+			f.flush().unwrap();
+			thread::sleep(Duration::from_micros(100));
+		}
+		f.seek(SeekFrom::Start(11)).unwrap();
+		f.write_all(&bytes[11..15]).unwrap();
+	})
+}
+
+#[test]
+fn growing() {
+	let path = temp_path("growing");
+	let bytes = fs::read(get_path("v3.12")).unwrap();
+	// create the file up front, so the follower can open it
+	fs::File::create(&path).unwrap();
+	let writer = write_slowly(path.clone(), bytes, 1000);
+
+	let expected = game("v3.12");
+	let version = expected.start.slippi.version;
+	let mut frames = 0;
+	let mut end = false;
+	let follower = Follower::open(&path, opts(None)).unwrap();
+	let game = follower
+		.run(|event, state| match event {
+			ParsedEvent::Frame(f) => {
+				assert_eq!(f, expected.frames.transpose_one(frames, version));
+				assert!(state.frames().len() > frames);
+				frames += 1;
+			}
+			ParsedEvent::End(_) => end = true,
+			_ => (),
+		})
+		.unwrap()
+		.unwrap();
+	writer.join().unwrap();
+	fs::remove_file(&path).unwrap();
+
+	assert!(end);
+	assert_eq!(frames, expected.frames.len());
+	assert_eq!(game.start, expected.start);
+	assert_eq!(game.end, expected.end);
+	assert_eq!(game.metadata, expected.metadata);
+}
+
+#[test]
+fn complete() {
+	let mut follower = Follower::open(get_path("v3.12"), opts(None)).unwrap();
+	let events = follower.poll().unwrap().unwrap();
+	assert!(follower.is_done());
+	assert_eq!(events.len(), 126);
+	assert!(follower.next_events().unwrap().is_none());
+	assert_eq!(follower.into_game().unwrap().frames.len(), 124);
+}
+
+#[test]
+fn idle_timeout() {
+	let path = temp_path("idle");
+	let bytes = fs::read(get_path("v3.12")).unwrap();
+	// cut off in the middle of the first Frame Post
+	fs::write(&path, &bytes[..47940]).unwrap();
+
+	let mut follower = Follower::open(&path, opts(Some(Duration::from_millis(20)))).unwrap();
+	assert_eq!(follower.next_events().unwrap().unwrap().len(), 1);
+	assert!(follower.poll().unwrap().is_none());
+	assert!(follower.next_events().unwrap().is_none());
+	assert!(!follower.is_done());
+
+	let game = follower.run(|_, _| ()).unwrap().unwrap();
+	fs::remove_file(&path).unwrap();
+	assert_eq!(game.frames.len(), 1);
+	assert_eq!(game.end, None);
+}
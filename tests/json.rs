@@ -1,8 +1,79 @@
+use std::{
+	fs,
+	io::{Cursor, Read},
+};
+
 use pretty_assertions::assert_eq;
 use serde_json::{from_str, json, to_string, Value};
 
+use peppi::{
+	game::{immutable::Game, shift_jis::MeleeString, Bytes, End, EndMethod, Port, Start},
+	io::{
+		json, peppi as io_peppi,
+		slippi::{self, Version},
+	},
+};
+
 mod common;
-use common::game;
+use common::{game, get_path};
+
+const GAMES: [&str; 9] = [
+	"game",
+	"v0.1",
+	"v2.0",
+	"v3.12",
+	"v3.16",
+	"ics2",
+	"items",
+	"netplay",
+	"crazy_name_tags",
+];
+
+fn assert_same_frames(actual: &Game, expected: &Game) {
+	let version = expected.start.slippi.version;
+	assert_eq!(actual.frames.len(), expected.frames.len());
+	for i in 0..expected.frames.len() {
+		assert_eq!(
+			actual.frames.transpose_one(i, version),
+			expected.frames.transpose_one(i, version),
+		);
+	}
+}
+
+/// Writes `game` as `.slp` and reads it back.
+fn slp_round_trip(game: &Game) -> Game {
+	let mut buf = vec![];
+	slippi::write(&mut buf, game).unwrap();
+	slippi::read(Cursor::new(buf), None).unwrap()
+}
+
+/// Writes `game` as `.slpp`, and returns the archive's entries.
+fn slpp_entries(game: Game) -> Vec<(String, Vec<u8>)> {
+	let mut buf = vec![];
+	io_peppi::write(&mut buf, game, None).unwrap();
+	tar::Archive::new(&*buf)
+		.entries()
+		.unwrap()
+		.map(|e| {
+			let mut e = e.unwrap();
+			let mut data = vec![];
+			e.read_to_end(&mut data).unwrap();
+			(e.path().unwrap().to_str().unwrap().to_string(), data)
+		})
+		.collect()
+}
+
+/// Builds a `.slpp` archive from `entries`.
+fn slpp(entries: &[(String, Vec<u8>)]) -> Vec<u8> {
+	let mut b = tar::Builder::new(vec![]);
+	for (name, data) in entries {
+		let mut header = tar::Header::new_gnu();
+		header.set_size(data.len() as u64);
+		header.set_cksum();
+		b.append_data(&mut header, name, &data[..]).unwrap();
+	}
+	b.into_inner().unwrap()
+}
 
 #[test]
 fn json_metadata() {
@@ -128,3 +199,186 @@ fn json_end() {
 		})
 	);
 }
+
+#[test]
+fn json_start_end_deserialize() {
+	for name in GAMES {
+		let game = game(name);
+		let mut start: Start = from_str(&to_string(&game.start).unwrap()).unwrap();
+		assert!(start.bytes.0.is_empty());
+		start.bytes = game.start.bytes.clone();
+		assert_eq!(start, game.start, "{}", name);
+
+		let expected = game.end.unwrap();
+		let mut end: End = from_str(&to_string(&expected).unwrap()).unwrap();
+		end.bytes = expected.bytes.clone();
+		assert_eq!(end, expected, "{}", name);
+	}
+}
+
+#[test]
+fn json_lras_initiator() {
+	for lras_initiator in [None, Some(None), Some(Some(Port::P3))] {
+		let end = End {
+			method: EndMethod::NoContest,
+			bytes: Bytes::default(),
+			lras_initiator,
+			players: None,
+		};
+		assert_eq!(from_str::<End>(&to_string(&end).unwrap()).unwrap(), end);
+	}
+}
+
+#[test]
+fn json_regenerate_bytes() {
+	for name in GAMES {
+		let expected = game(name);
+		// unchanged, so the original bytes are kept as-is
+		assert_eq!(
+			slippi::ser::start_bytes(&expected.start).unwrap(),
+			expected.start.bytes.0
+		);
+		let end = expected.end.as_ref().unwrap();
+		assert_eq!(
+			slippi::ser::end_bytes(end, expected.start.slippi.version).unwrap(),
+			end.bytes.0
+		);
+
+		// from scratch
+		let mut game = game(name);
+		game.start.bytes = Bytes::default();
+		game.end.as_mut().unwrap().bytes = Bytes::default();
+		let bytes = slippi::ser::start_bytes(&game.start).unwrap();
+		assert_eq!(bytes.len(), expected.start.bytes.0.len(), "{}", name);
+		let actual = slp_round_trip(&game);
+		assert_eq!(actual.start.bytes.0, bytes);
+		let mut start = actual.start.clone();
+		start.bytes = expected.start.bytes.clone();
+		assert_eq!(start, expected.start, "{}", name);
+		let mut end = actual.end.clone().unwrap();
+		end.bytes = expected.end.as_ref().unwrap().bytes.clone();
+		assert_eq!(&end, expected.end.as_ref().unwrap(), "{}", name);
+		assert_same_frames(&actual, &expected);
+	}
+}
+
+#[test]
+fn json_edited_start() {
+	let expected = game("v3.12");
+	let mut start = expected.start.clone();
+	start.stage = 0x1f;
+	start.players[1].netplay.as_mut().unwrap().code = MeleeString("ZZ＃9".to_string());
+	let bytes = slippi::ser::start_bytes(&start).unwrap();
+	let diff: Vec<_> = (0..bytes.len())
+		.filter(|&i| bytes[i] != expected.start.bytes.0[i])
+		.collect();
+	// stage, and P2's connect code ("YYYY＃222" is 9 bytes, "ZZ＃9" is 5)
+	assert_eq!(diff, [0x13, 554, 555, 556, 557, 558, 559, 560, 561, 562]);
+
+	start.players[0].netplay.as_mut().unwrap().code = MeleeString("ABCDEFGH#123".to_string());
+	assert!(slippi::ser::start_bytes(&start).is_err());
+}
+
+#[test]
+fn json_game() {
+	for name in GAMES {
+		let expected = game(name);
+		let mut buf = vec![];
+		json::write(&mut buf, &expected).unwrap();
+		let game = json::read(&*buf).unwrap();
+
+		let mut start = game.start.clone();
+		start.bytes = expected.start.bytes.clone();
+		assert_eq!(start, expected.start, "{}", name);
+		assert_eq!(game.metadata, expected.metadata);
+		assert_same_frames(&game, &expected);
+
+		let actual = slp_round_trip(&game);
+		assert_eq!(actual.start.bytes.0, game.start.bytes.0);
+		assert_eq!(actual.end, game.end);
+		assert_same_frames(&actual, &expected);
+	}
+}
+
+#[test]
+fn json_edited_game() {
+	let mut buf = vec![];
+	json::write(&mut buf, &game("v3.16")).unwrap();
+	let mut value: Value = from_str(std::str::from_utf8(&buf).unwrap()).unwrap();
+	value["start"]["stage"] = json!(8);
+	value["frames"][100]["ports"][0]["leader"]["post"]["percent"] = json!(42.5);
+	let game = json::read(to_string(&value).unwrap().as_bytes()).unwrap();
+	let actual = slp_round_trip(&game);
+	assert_eq!(actual.start.stage, 8);
+	assert_eq!(actual.frames.ports[0].leader.post.percent.value(100), 42.5);
+
+	// frames must match the players in `start`
+	value["frames"][100]["ports"][0]["port"] = json!("P4");
+	assert!(json::read(to_string(&value).unwrap().as_bytes()).is_err());
+}
+
+#[test]
+fn slpp_raw_before_json() {
+	let expected = game("v3.12");
+	let mut entries = slpp_entries(game("v3.12"));
+	let names: Vec<_> = entries.iter().map(|(n, _)| n.as_str()).collect();
+	assert_eq!(
+		names[2..6],
+		["start.json", "start.raw", "end.json", "end.raw"]
+	);
+	entries.swap(2, 3);
+	entries.swap(4, 5);
+	let game = io_peppi::read(&*slpp(&entries), None).unwrap();
+	assert_eq!(game.start, expected.start);
+	assert_eq!(game.end, expected.end);
+
+	// JSON we can't read, so we fall back to the raw events
+	entries[3].1 = b"{}".to_vec();
+	entries[5].1 = b"{}".to_vec();
+	let game = io_peppi::read(&*slpp(&entries), None).unwrap();
+	assert_eq!(game.start, expected.start);
+	assert_eq!(game.end, expected.end);
+
+	// ... but only if there are some
+	entries.remove(2);
+	assert!(io_peppi::read(&*slpp(&entries), None).is_err());
+}
+
+#[test]
+fn slpp_regenerate_raw() {
+	let expected = game("v3.12");
+	let mut game = game("v3.12");
+	game.start.bytes = Bytes::default();
+	game.end.as_mut().unwrap().bytes = Bytes::default();
+	let start = slippi::ser::start_bytes(&game.start).unwrap();
+	let end = slippi::ser::end_bytes(game.end.as_ref().unwrap(), Version(3, 12, 0)).unwrap();
+	let entries = slpp_entries(game);
+	let raw = |name| &entries.iter().find(|(n, _)| n == name).unwrap().1;
+	assert_eq!(*raw("start.raw"), start);
+	assert_eq!(*raw("end.raw"), end);
+
+	// readable without the JSON
+	let entries: Vec<_> = entries
+		.iter()
+		.filter(|(n, _)| !n.ends_with(".json") || n == "peppi.json")
+		.cloned()
+		.collect();
+	let actual = io_peppi::read(&*slpp(&entries), None).unwrap();
+	let mut start = actual.start.clone();
+	start.bytes = expected.start.bytes.clone();
+	assert_eq!(start, expected.start);
+	assert_eq!(actual.end.unwrap().method, expected.end.unwrap().method);
+}
+
+#[test]
+fn json_lossless() {
+	for name in GAMES.iter().chain(&["unknown_event", "future_version"]) {
+		let expected = fs::read(get_path(name)).unwrap();
+		let mut buf = vec![];
+		json::write(&mut buf, &game(name)).unwrap();
+		let game = json::read(&*buf).unwrap();
+		let mut actual = vec![];
+		slippi::write(&mut actual, &game).unwrap();
+		assert!(actual == expected, "{}", name);
+	}
+}
@@ -0,0 +1,174 @@
+#![cfg(feature = "parquet")]
+
+use std::{fs, io::Cursor};
+
+use arrow2::io::parquet::{
+	read::{infer_schema, read_metadata},
+	write::CompressionOptions,
+};
+use pretty_assertions::assert_eq;
+
+use peppi::{
+	frame::immutable::Frame,
+	game::immutable::Game,
+	io::{
+		dataset,
+		parquet::{self, Opts, END_KEY, HASH_KEY, METADATA_KEY, START_KEY, VERSION_KEY},
+	},
+};
+
+mod common;
+use common::game;
+
+fn write(game: Game, opts: Option<&Opts>) -> Vec<u8> {
+	let mut buf = vec![];
+	parquet::write(&mut buf, game, opts).unwrap();
+	buf
+}
+
+fn assert_same_frames(actual: &Frame, expected: &Frame, game: &Game) {
+	let version = game.start.slippi.version;
+	assert_eq!(actual.len(), expected.len());
+	for i in 0..expected.len() {
+		assert_eq!(
+			actual.transpose_one(i, version),
+			expected.transpose_one(i, version),
+		);
+	}
+}
+
+#[test]
+fn round_trip() {
+	for name in ["v0.1", "v2.0", "ics2", "items", "v3.16"] {
+		let expected = game(name);
+		let buf = write(game(name), None);
+		let actual = parquet::read(Cursor::new(buf)).unwrap();
+		assert_same_frames(&actual, &expected.frames, &expected);
+	}
+}
+
+#[test]
+fn columns() {
+	let buf = write(game("ics2"), None);
+	let metadata = read_metadata(&mut Cursor::new(buf)).unwrap();
+	let schema = infer_schema(&metadata).unwrap();
+	let names: Vec<_> = schema.fields.iter().map(|f| f.name.as_str()).collect();
+	assert_eq!(names[0], "id");
+	assert!(names.contains(&"ports.P1.leader.post.position.x"));
+	assert!(names.contains(&"ports.P1.follower.pre.joystick.y"));
+	assert!(names.contains(&"item"));
+	assert!(!names.contains(&"ports.P1.leader.post.position"));
+}
+
+#[test]
+fn follower_nulls() {
+	let expected = game("ics2");
+	let buf = write(game("ics2"), None);
+	let metadata = read_metadata(&mut Cursor::new(buf.clone())).unwrap();
+	let schema = infer_schema(&metadata).unwrap();
+	let i = schema
+		.fields
+		.iter()
+		.position(|f| f.name == "ports.P1.follower.post.position.x")
+		.unwrap();
+	let column = arrow2::io::parquet::read::FileReader::new(
+		Cursor::new(buf),
+		metadata.row_groups,
+		schema,
+		None,
+		None,
+		None,
+	)
+	.next()
+	.unwrap()
+	.unwrap()
+	.arrays()[i]
+		.clone();
+	let follower = expected.frames.ports[0].follower.as_ref().unwrap();
+	assert_eq!(
+		column.null_count(),
+		follower.validity.as_ref().unwrap().unset_bits()
+	);
+	assert!(column.null_count() > 0);
+}
+
+#[test]
+fn key_value_metadata() {
+	let game = game("v3.16");
+	let start = serde_json::to_string(&game.start).unwrap();
+	let end = serde_json::to_string(game.end.as_ref().unwrap()).unwrap();
+	let metadata = serde_json::to_string(game.metadata.as_ref().unwrap()).unwrap();
+	let buf = write(game, None);
+	let kv = parquet::read_metadata(&mut Cursor::new(buf)).unwrap();
+	assert_eq!(kv[VERSION_KEY], "3.16.0");
+	assert_eq!(kv[START_KEY], start);
+	assert_eq!(kv[END_KEY], end);
+	assert_eq!(kv[METADATA_KEY], metadata);
+	assert!(!kv.contains_key(HASH_KEY));
+}
+
+#[test]
+fn row_groups() {
+	let expected = game("v3.16");
+	let len = expected.frames.len();
+	let opts = Opts {
+		compression: Some(CompressionOptions::Zstd(None)),
+		row_group_size: Some(1000),
+	};
+	let buf = write(game("v3.16"), Some(&opts));
+	let metadata = read_metadata(&mut Cursor::new(buf.clone())).unwrap();
+	assert_eq!(metadata.row_groups.len(), (len + 999) / 1000);
+	let actual = parquet::read(Cursor::new(buf)).unwrap();
+	assert_same_frames(&actual, &expected.frames, &expected);
+
+	let opts = Opts {
+		row_group_size: Some(0),
+		..Default::default()
+	};
+	assert!(parquet::write(&mut vec![], game("v3.16"), Some(&opts)).is_err());
+}
+
+#[test]
+fn dataset() {
+	let dir = std::env::temp_dir().join(format!("peppi-parquet-dataset-{}", std::process::id()));
+	let opts = dataset::Opts {
+		format: dataset::Format::Parquet(Some(CompressionOptions::Snappy)),
+	};
+	let mut writer = dataset::Writer::new(&dir, Some(&opts)).unwrap();
+	let names: Vec<_> = writer
+		.schema()
+		.fields
+		.iter()
+		.map(|f| f.name.clone())
+		.collect();
+	let ids: Vec<_> = ["ics2", "v3.16"]
+		.into_iter()
+		.map(|name| {
+			let mut game = game(name);
+			game.hash = Some(name.to_string());
+			writer.write(game).unwrap()
+		})
+		.collect();
+	writer.finish().unwrap();
+
+	for (name, id) in ["ics2", "v3.16"].into_iter().zip(ids) {
+		let expected = game(name);
+		let path = dir.join("player_frames").join(format!("{}.parquet", id));
+		let metadata = read_metadata(&mut fs::File::open(path).unwrap()).unwrap();
+		let schema = infer_schema(&metadata).unwrap();
+		assert_eq!(
+			schema.fields.iter().map(|f| &f.name).collect::<Vec<_>>(),
+			names.iter().collect::<Vec<_>>()
+		);
+		// one row group per port
+		assert_eq!(metadata.row_groups.len(), expected.frames.ports.len());
+		assert_eq!(
+			metadata.num_rows,
+			expected.frames.len() * expected.frames.ports.len()
+		);
+	}
+
+	let metadata = read_metadata(&mut fs::File::open(dir.join("games.parquet")).unwrap()).unwrap();
+	assert_eq!(metadata.num_rows, 2);
+	fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,149 @@
+use std::{fs, io::Cursor};
+
+use peppi::{
+	game::immutable::Game,
+	io::{
+		slippi::{
+			self,
+			de::Opts,
+			parser::{ParsedEvent, Parser},
+		},
+		ErrorKind, Warning,
+	},
+};
+
+mod common;
+use common::{game, get_path};
+
+/// Feeds `bytes` to `parser` in chunks of `chunk_size`, returning everything it yields.
+fn feed(parser: &mut Parser, bytes: &[u8], chunk_size: usize) -> Vec<ParsedEvent> {
+	bytes
+		.chunks(chunk_size)
+		.flat_map(|chunk| parser.feed(chunk).unwrap())
+		.collect()
+}
+
+fn assert_same(actual: &Game, expected: &Game) {
+	assert_eq!(actual.start, expected.start);
+	assert_eq!(actual.end, expected.end);
+	assert_eq!(actual.metadata, expected.metadata);
+	assert_eq!(actual.gecko_codes, expected.gecko_codes);
+	assert_eq!(actual.hash, expected.hash);
+	assert_eq!(actual.quirks.is_some(), expected.quirks.is_some());
+	assert_eq!(actual.unknown_events, expected.unknown_events);
+	assert_eq!(actual.frames.len(), expected.frames.len());
+	let version = expected.start.slippi.version;
+	for i in 0..expected.frames.len() {
+		assert_eq!(
+			actual.frames.transpose_one(i, version),
+			expected.frames.transpose_one(i, version),
+		);
+	}
+}
+
+#[test]
+fn same_as_read() {
+	let opts = Opts {
+		compute_hash: true,
+		..Default::default()
+	};
+	// v0.1 & v2.0 predate Frame End, and v3.12 uses message splitters for its Gecko codes
+	for name in [
+		"v0.1",
+		"v2.0",
+		"v3.12",
+		"duplicate_game_end",
+		"unknown_event",
+	] {
+		let bytes = fs::read(get_path(name)).unwrap();
+		let expected = slippi::read(Cursor::new(&bytes[..]), Some(&opts)).unwrap();
+		for chunk_size in [1, 7, 4096, bytes.len()] {
+			let mut parser = Parser::new(Some(opts.clone()));
+			let events = feed(&mut parser, &bytes, chunk_size);
+			assert!(parser.is_done());
+
+			let frames: Vec<_> = events
+				.iter()
+				.filter_map(|e| match e {
+					ParsedEvent::Frame(f) => Some(f),
+					_ => None,
+				})
+				.collect();
+			let version = expected.start.slippi.version;
+			assert_eq!(frames.len(), expected.frames.len());
+			for (i, f) in frames.into_iter().enumerate() {
+				assert_eq!(f, &expected.frames.transpose_one(i, version));
+			}
+			assert!(matches!(events.first(), Some(ParsedEvent::Start(_))));
+			assert!(matches!(events.last(), Some(ParsedEvent::End(_))));
+
+			assert_same(&parser.into_game().unwrap(), &expected);
+		}
+	}
+}
+
+#[test]
+fn raw() {
+	let bytes = fs::read(get_path("v3.12")).unwrap();
+	let raw_len = u32::from_be_bytes(bytes[11..15].try_into().unwrap()) as usize;
+	let mut parser = Parser::raw(None);
+	let events = feed(&mut parser, &bytes[15..15 + raw_len], 100);
+	assert!(parser.is_done());
+	assert!(matches!(events.last(), Some(ParsedEvent::End(_))));
+
+	let game = parser.into_game().unwrap();
+	assert_eq!(game.metadata, None);
+	assert_eq!(game.frames.len(), 124);
+}
+
+#[test]
+fn incomplete() {
+	let bytes = fs::read(get_path("v3.12")).unwrap();
+
+	// not enough for Game Start
+	let mut parser = Parser::new(None);
+	assert!(feed(&mut parser, &bytes[..100], 10).is_empty());
+	assert!(parser.state().is_none());
+
+	// cut off in the middle of the first Frame Post
+	let mut parser = Parser::new(None);
+	let events = feed(&mut parser, &bytes[..47940], 10);
+	assert_eq!(events.len(), 1);
+	assert!(!parser.is_done());
+	assert_eq!(parser.state().unwrap().frames().len(), 1);
+
+	// ... and the rest
+	let events = feed(&mut parser, &bytes[47940..], 10);
+	assert_eq!(events.len(), 125);
+	assert!(parser.is_done());
+	assert_same(&parser.into_game().unwrap(), &game("v3.12"));
+}
+
+#[test]
+fn errors() {
+	let mut bytes = fs::read(get_path("v3.12")).unwrap();
+	// first Frame Post, for a port that doesn't exist
+	bytes[0xbb2f + 15 + 5] = 9;
+
+	let mut parser = Parser::new(None);
+	let err = parser.feed(&bytes).unwrap_err();
+	assert_eq!(err.kind(), ErrorKind::InvalidPort);
+	assert_eq!(err.context().map(|c| c.offset), Some(0xbb2f));
+
+	let mut parser = Parser::new(Some(Opts {
+		lenient: true,
+		..Default::default()
+	}));
+	let events = parser.feed(&bytes).unwrap();
+	assert!(parser.is_done());
+	assert!(matches!(events.last(), Some(ParsedEvent::Frame(_))));
+	let game = parser.into_game().unwrap();
+	assert_eq!(game.frames.len(), 1);
+	assert!(matches!(
+		game.warnings.as_slice(),
+		[Warning::Truncated {
+			incomplete_frame: Some(-123),
+			..
+		}]
+	));
+}
@@ -0,0 +1,180 @@
+#![cfg(feature = "tokio")]
+
+use std::{
+	fs,
+	io::{self, Cursor},
+	pin::Pin,
+	task::{Context, Poll},
+};
+
+use tokio::io::{AsyncRead, ReadBuf};
+
+use peppi::{
+	game::immutable::Game,
+	io::{
+		slippi::{self, de::Opts, de_async},
+		ErrorKind, Warning,
+	},
+};
+
+mod common;
+use common::{game, get_path, read_game};
+
+fn assert_same(actual: &Game, expected: &Game) {
+	assert_eq!(actual.start, expected.start);
+	assert_eq!(actual.end, expected.end);
+	assert_eq!(actual.metadata, expected.metadata);
+	assert_eq!(actual.gecko_codes, expected.gecko_codes);
+	assert_eq!(actual.hash, expected.hash);
+	assert_eq!(actual.unknown_events, expected.unknown_events);
+	assert_eq!(actual.frames.len(), expected.frames.len());
+	for i in 0..expected.frames.len() {
+		let version = expected.start.slippi.version;
+		assert_eq!(
+			actual.frames.transpose_one(i, version),
+			expected.frames.transpose_one(i, version),
+		);
+	}
+}
+
+#[tokio::test]
+async fn same_as_blocking() {
+	let opts = Opts {
+		compute_hash: true,
+		..Default::default()
+	};
+	for name in ["v0.1", "v3.12", "ics2", "unknown_event", "future_version"] {
+		let bytes = fs::read(get_path(name)).unwrap();
+		let expected = slippi::read(Cursor::new(&bytes[..]), Some(&opts)).unwrap();
+		let file = tokio::fs::File::open(get_path(name)).await.unwrap();
+		let actual = de_async::read(file, Some(&opts)).await.unwrap();
+		assert_same(&actual, &expected);
+	}
+}
+
+#[tokio::test]
+async fn skip_frames() {
+	let opts = Opts {
+		skip_frames: true,
+		..Default::default()
+	};
+	let file = tokio::fs::File::open(get_path("v3.12")).await.unwrap();
+	let actual = de_async::read(file, Some(&opts)).await.unwrap();
+	let expected = read_game(get_path("v3.12"), true).unwrap();
+	assert_same(&actual, &expected);
+	assert_eq!(actual.frames.len(), 0);
+}
+
+#[tokio::test]
+async fn truncated() {
+	let bytes = fs::read(get_path("v3.12")).unwrap();
+	// cut off in the middle of the first Frame Post
+	let bytes = &bytes[..47940];
+
+	let err = de_async::read(bytes, None).await.unwrap_err();
+	let expected = slippi::read(Cursor::new(bytes), None).unwrap_err();
+	assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+	assert_eq!(err.context(), expected.context());
+
+	let opts = Opts {
+		lenient: true,
+		..Default::default()
+	};
+	let game = de_async::read(bytes, Some(&opts)).await.unwrap();
+	assert_eq!(game.frames.len(), 1);
+	assert!(matches!(
+		game.warnings.as_slice(),
+		[Warning::Truncated {
+			incomplete_frame: Some(-123),
+			..
+		}]
+	));
+}
+
+/// Yields the given bytes, then fails.
+struct Failing<'a>(&'a [u8]);
+
+impl AsyncRead for Failing<'_> {
+	fn poll_read(
+		mut self: Pin<&mut Self>,
+		_: &mut Context<'_>,
+		buf: &mut ReadBuf<'_>,
+	) -> Poll<io::Result<()>> {
+		if self.0.is_empty() {
+			return Poll::Ready(Err(io::ErrorKind::ConnectionReset.into()));
+		}
+		let n = self.0.len().min(buf.remaining());
+		buf.put_slice(&self.0[..n]);
+		self.0 = &self.0[n..];
+		Poll::Ready(Ok(()))
+	}
+}
+
+#[tokio::test]
+async fn truncated_metadata() {
+	let bytes = fs::read(get_path("v3.12")).unwrap();
+	let raw_len = u32::from_be_bytes(bytes[11..15].try_into().unwrap()) as usize;
+	// cut off a few bytes into the metadata
+	let bytes = &bytes[..15 + raw_len + 20];
+
+	let lenient = Opts {
+		lenient: true,
+		..Default::default()
+	};
+	let expected = slippi::read(Cursor::new(bytes), Some(&lenient)).unwrap();
+	assert!(matches!(
+		expected.warnings.as_slice(),
+		[Warning::InvalidMetadata { .. }]
+	));
+
+	let actual = de_async::read(bytes, Some(&lenient)).await.unwrap();
+	assert_same(&actual, &expected);
+	assert_eq!(actual.warnings.len(), 1);
+	let actual = de_async::read(Failing(bytes), Some(&lenient))
+		.await
+		.unwrap();
+	assert_same(&actual, &expected);
+	assert!(matches!(
+		actual.warnings.as_slice(),
+		[Warning::InvalidMetadata { .. }]
+	));
+
+	assert!(slippi::read(Cursor::new(bytes), None).is_err());
+	assert!(de_async::read(bytes, None).await.is_err());
+	let err = de_async::read(Failing(bytes), None).await.unwrap_err();
+	assert_eq!(err.kind(), ErrorKind::Io);
+}
+
+#[tokio::test]
+async fn live() {
+	let bytes = fs::read(get_path("v3.12")).unwrap();
+	let mut r = &bytes[..];
+	let size = de_async::parse_header(&mut r, None).await.unwrap() as usize;
+	let mut state = de_async::parse_start(&mut r, None).await.unwrap();
+	let mut frames = 0;
+	while de_async::parse_event(&mut r, &mut state, None)
+		.await
+		.unwrap()
+		!= slippi::de::Event::GameEnd as u8
+		&& state.bytes_read() < size
+	{
+		frames = state.frames().len();
+	}
+	assert_eq!(frames, 124);
+	// `U` (0x55) means metadata next
+	assert_eq!(r[0], 0x55);
+	de_async::parse_metadata(&r[1..], &mut state, None)
+		.await
+		.unwrap();
+}
+
+#[tokio::test]
+async fn spawn() {
+	// the returned future must be `Send` to run on a multi-threaded runtime
+	let file = tokio::fs::File::open(get_path("v3.12")).await.unwrap();
+	let actual = tokio::spawn(async move { de_async::read(file, None).await })
+		.await
+		.unwrap()
+		.unwrap();
+	assert_same(&actual, &game("v3.12"));
+}